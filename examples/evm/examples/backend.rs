@@ -0,0 +1,22 @@
+use revm::primitives::U256;
+use rustry_macros::compile;
+use rustry_test::prelude::*;
+
+/// The `hex` backend of the `rustry.toml` of the crate
+macro_rules! hex {
+    ($($args:tt)*) => { compile!(backend = "hex", $($args)*) };
+}
+
+#[test]
+fn test_answer() {
+    let mut provider = Provider::default();
+    // returns 42 whatever the call
+    let answer = hex!(
+        abi("function answer() view returns (uint256)"),
+        "600a600c600039600a6000f3602a60005260206000f3"
+    )
+    .deploy(&mut provider);
+    assert_eq!(answer.methods.answer(&mut provider), U256::from(42));
+}
+
+fn main() {}
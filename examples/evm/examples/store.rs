@@ -0,0 +1,70 @@
+use revm::primitives::U256;
+use rustry_macros::{evm, rustry_test};
+use rustry_test::prelude::*;
+
+evm!(
+    module = store,
+    abi(
+        "function get() view returns (uint256)",
+        "function set(uint256 value)",
+        "event Set(uint256 value)"
+    ),
+    "
+    PUSH1 0x00 CALLDATALOAD PUSH1 0xe0 SHR
+    DUP1 PUSH4 0x6d4ce63c EQ PUSH get JUMPI   // get()
+    DUP1 PUSH4 0x60fe47b1 EQ PUSH set JUMPI   // set(uint256)
+    PUSH1 0x00 DUP1 REVERT
+
+    get: JUMPDEST
+    PUSH1 0x00 SLOAD PUSH1 0x00 MSTORE
+    PUSH1 0x20 PUSH1 0x00 RETURN
+
+    set: JUMPDEST
+    PUSH1 0x04 CALLDATALOAD DUP1 PUSH1 0x00 SSTORE
+    PUSH1 0x00 MSTORE
+    PUSH32 0xdf7a95aebff315db1b7716215d602ab537373cdb769232aae6055c06e798425b
+    PUSH1 0x20 PUSH1 0x00 LOG1
+    STOP
+    "
+);
+
+#[allow(unused)]
+fn set_up() {
+    let mut provider = Provider::default();
+
+    let store = store::ContractInstance::compiled().deploy(&mut provider);
+}
+
+#[rustry_test(set_up)]
+fn test_set() {
+    store.methods.set(&mut provider, U256::from(42));
+    let set = store::SetEvent::decode(&provider.logs[0]).unwrap();
+    assert_eq!(set.value, U256::from(42));
+
+    assert_eq!(store.methods.get(&mut provider), U256::from(42));
+}
+
+#[rustry_test(set_up)]
+fn test_unknown_selector() {
+    assert!(store.methods.try_get(&mut provider).is_ok());
+    let ret = provider.call(store.address, vec![0xde, 0xad, 0xbe, 0xef].into());
+    assert!(!ret.is_success());
+}
+
+#[test]
+fn test_creation_code() {
+    let mut provider = Provider::default();
+    // the constructor returns the runtime code, answering 42 to any call
+    let answer = evm!(
+        creation = true,
+        abi("function answer() view returns (uint256)"),
+        "
+        PUSH10 0x602a60005260206000f3 PUSH1 0x00 MSTORE
+        PUSH1 0x0a PUSH1 0x16 RETURN
+        "
+    )
+    .deploy(&mut provider);
+    assert_eq!(answer.methods.answer(&mut provider), U256::from(42));
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+// compiles the contracts to Foundry artifacts, bound with `artifact!`
+fn main() {
+    let root = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    rustry_test::project::Project::new(root)
+        .unwrap()
+        .compile()
+        .unwrap();
+    println!("cargo:rerun-if-changed=contracts");
+}
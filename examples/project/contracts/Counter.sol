@@ -0,0 +1,14 @@
+// SPDX-License-Identifier: AGPLv3
+pragma solidity 0.8.20;
+
+contract Counter {
+    uint256 public number;
+
+    function setNumber(uint256 _number) public {
+        number = _number;
+    }
+
+    function increment() public {
+        number++;
+    }
+}
@@ -0,0 +1,21 @@
+use revm::primitives::U256;
+use rustry_macros::artifact;
+use rustry_test::prelude::*;
+
+#[test]
+fn test_increment() {
+    let mut provider = Provider::default();
+    let counter = artifact!("Counter").deploy(&mut provider);
+    counter.methods.increment(&mut provider);
+    assert_eq!(counter.methods.number(&mut provider), U256::from(1));
+}
+
+#[test]
+fn test_qualified_name() {
+    let mut provider = Provider::default();
+    let counter = artifact!("Counter.sol:Counter").deploy(&mut provider);
+    counter.methods.setNumber(&mut provider, U256::from(42));
+    assert_eq!(counter.methods.number(&mut provider), U256::from(42));
+}
+
+fn main() {}
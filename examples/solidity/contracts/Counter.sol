@@ -0,0 +1,16 @@
+// SPDX-License-Identifier: AGPLv3
+pragma solidity 0.8.20;
+
+import {Math} from "./Math.sol";
+
+contract Counter {
+    uint256 public number;
+
+    function setNumber(uint256 _number) public {
+        number = _number;
+    }
+
+    function increment() public {
+        number = Math.add(number, 1);
+    }
+}
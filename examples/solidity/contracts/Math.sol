@@ -0,0 +1,8 @@
+// SPDX-License-Identifier: AGPLv3
+pragma solidity 0.8.20;
+
+library Math {
+    function add(uint256 a, uint256 b) public pure returns (uint256) {
+        return a + b;
+    }
+}
@@ -0,0 +1,24 @@
+// SPDX-License-Identifier: AGPLv3
+pragma solidity 0.8.20;
+
+import {Counter} from "./Counter.sol";
+
+contract Proxy {
+    address immutable implementation;
+
+    constructor(address _implementation) {
+        implementation = _implementation;
+    }
+
+    fallback() external payable {
+        (bool success, bytes memory data) = implementation.delegatecall(msg.data);
+        if (!success) {
+            assembly {
+                revert(add(data, 32), mload(data))
+            }
+        }
+        assembly {
+            return(add(data, 32), mload(data))
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use revm::primitives::{Address, U256};
+use rustry_macros::{abigen, evm};
+use rustry_test::prelude::*;
+
+abigen!(Token, "abis/Token.json");
+
+/// A token whose every call is `transfer(to, amount)`, up to a balance of 100
+fn deploy_token(provider: &mut Provider) -> DeployedToken {
+    let token = evm!(
+        "
+        PUSH1 0x24 CALLDATALOAD
+        DUP1 PUSH1 100 LT PUSH insufficient JUMPI
+
+        // emit Transfer(msg.sender, to, amount) and return true
+        PUSH1 0x00 MSTORE
+        PUSH1 0x04 CALLDATALOAD CALLER
+        PUSH32 0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef
+        PUSH1 0x20 PUSH1 0x00 LOG3
+        PUSH1 0x01 PUSH1 0x00 MSTORE
+        PUSH1 0x20 PUSH1 0x00 RETURN
+
+        // revert InsufficientBalance(100, amount)
+        insufficient: JUMPDEST
+        PUSH32 0xcf47918100000000000000000000000000000000000000000000000000000000
+        PUSH1 0x00 MSTORE
+        PUSH1 100 PUSH1 0x04 MSTORE
+        PUSH1 0x24 MSTORE
+        PUSH1 0x44 PUSH1 0x00 REVERT
+        "
+    )
+    .deploy(provider);
+    DeployedToken::at(token.address)
+}
+
+#[test]
+fn test_event() {
+    let mut provider = Provider::default();
+    let token = deploy_token(&mut provider);
+    let bob = Address::repeat_byte(0xb0);
+
+    assert!(token.methods.transfer(&mut provider, bob, U256::from(42)));
+    assert_eq!(provider.logs.len(), 1);
+    let transfer = TransferEvent::decode(&provider.logs[0]).unwrap();
+    assert_eq!(transfer.to, bob);
+    assert_eq!(transfer.value, U256::from(42));
+}
+
+#[test]
+fn test_error() {
+    let mut provider = Provider::default();
+    let token = deploy_token(&mut provider);
+
+    let ret = token
+        .methods
+        .try_transfer(&mut provider, Address::ZERO, U256::from(500))
+        .unwrap_err();
+    let error = InsufficientBalanceError::decode(ret.get_data()).unwrap();
+    assert_eq!(error.available, U256::from(100));
+    assert_eq!(error.required, U256::from(500));
+}
+
+fn main() {}
@@ -65,6 +65,117 @@ fn test_increment_method() {
     assert_eq!(number, U256::from(1));
 }
 
+#[rustry_test(set_up)]
+fn test_set_number_method() {
+    counter.methods.setNumber(&mut provider, U256::from(42));
+    let number = counter.methods.number(&mut provider);
+    assert_eq!(number, U256::from(42));
+}
+
+#[rustry_test(set_up)]
+fn test_storage() {
+    provider
+        .write_var(&counter, "number", U256::from(41))
+        .unwrap();
+    counter.methods.increment(&mut provider);
+    assert_eq!(
+        provider.read_var(&counter, "number").unwrap(),
+        StorageValue::Uint(U256::from(42))
+    );
+}
+
+solidity!(
+    module = counters,
+    "
+        // SPDX-License-Identifier: AGPLv3
+        pragma solidity 0.8.20;
+
+        contract Counter {
+            uint256 public number;
+
+            function increment() public {
+                number++;
+            }
+        }
+    "
+);
+
+#[test]
+fn test_module() {
+    let mut provider = Provider::default();
+    let counter = counters::Counter::compiled().deploy(&mut provider);
+    counter.methods.increment(&mut provider);
+
+    let counter = counters::DeployedCounter::at(counter.address);
+    assert_eq!(counter.methods.number(&mut provider), U256::from(1));
+}
+
+solidity!(
+    module = tokens,
+    aliases("transfer(address,uint256)" = send),
+    "
+        // SPDX-License-Identifier: AGPLv3
+        pragma solidity 0.8.20;
+
+        contract Counter {
+            uint256 public number;
+
+            function increment() public {
+                number++;
+            }
+        }
+
+        contract Token {
+            mapping(address => uint256) public balanceOf;
+
+            function transfer(address to, uint256 amount) public {
+                balanceOf[to] += amount;
+            }
+
+            function transfer(address to) public {
+                balanceOf[to] += 1;
+            }
+        }
+    "
+);
+
+#[test]
+fn test_module_aliases() {
+    let mut provider = Provider::default();
+    let token = tokens::Token::compiled().deploy(&mut provider);
+    let bob = Address::repeat_byte(0xb0);
+    token.methods.send(&mut provider, bob, U256::from(41));
+    token.methods.transfer_address(&mut provider, bob);
+    assert_eq!(token.methods.balanceOf(&mut provider, bob), U256::from(42));
+
+    // the alias doesn't apply to the contracts without the function
+    let counter = tokens::Counter::compiled().deploy(&mut provider);
+    counter.methods.increment(&mut provider);
+}
+
+#[test]
+fn test_path() {
+    let mut provider = Provider::default();
+    let counter = solidity!(path = "contracts/Counter.sol", contract = "Counter");
+    let counter = counter.deploy(&mut provider);
+    counter.methods.increment(&mut provider);
+    assert_eq!(counter.methods.number(&mut provider), U256::from(1));
+}
+
+#[test]
+fn test_proxy() {
+    let mut provider = Provider::default();
+    let counter = solidity!(path = "contracts/Counter.sol", contract = "Counter");
+    let counter = counter.deploy(&mut provider);
+    let proxy = solidity!(path = "contracts/Proxy.sol", implementation = "Counter");
+    let proxy = proxy.with_args(counter.address).deploy(&mut provider);
+
+    proxy.methods.increment(&mut provider);
+    assert_eq!(proxy.methods.number(&mut provider), U256::from(1));
+    // the state is the one of the proxy
+    assert_eq!(counter.methods.number(&mut provider), U256::ZERO);
+}
+
 fn get_number(caddr: Address, provider: &mut Provider) -> U256 {
     let ret = provider.staticcall(caddr, abi_encode_signature("number()", vec![]).into());
     assert!(ret.is_success());
@@ -0,0 +1,27 @@
+use revm::primitives::U256;
+use rustry_macros::{evm, solidity};
+use rustry_test::prelude::*;
+
+#[test]
+fn test_deploys_libraries() {
+    let mut provider = Provider::default();
+    // `Math.add` is public, so called in the deployed library
+    let counter = solidity!(path = "contracts/Counter.sol", contract = "Counter");
+    let counter = counter.deploy(&mut provider);
+    counter.methods.increment(&mut provider);
+    counter.methods.increment(&mut provider);
+    assert_eq!(counter.methods.number(&mut provider), U256::from(2));
+}
+
+#[test]
+fn test_links_deployed_libraries() {
+    let mut provider = Provider::default();
+    // a `Math` whose `add` always returns 7
+    let math = evm!("PUSH1 7 PUSH0 MSTORE PUSH1 0x20 PUSH0 RETURN").deploy(&mut provider);
+    let counter = solidity!(path = "contracts/Counter.sol", contract = "Counter");
+    let counter = counter.link("Math", math.address).deploy(&mut provider);
+    counter.methods.increment(&mut provider);
+    assert_eq!(counter.methods.number(&mut provider), U256::from(7));
+}
+
+fn main() {}
@@ -0,0 +1,37 @@
+use revm::primitives::{Address, U256};
+use rustry_macros::solidity;
+use rustry_test::prelude::*;
+
+solidity!(
+    module = geometry,
+    "
+        // SPDX-License-Identifier: AGPLv3
+        pragma solidity 0.8.20;
+
+        contract Geometry {
+            struct Point {
+                uint256 x;
+                int64 y;
+            }
+
+            function echo(Point calldata p) external pure returns (Point memory) {
+                return p;
+            }
+        }
+    "
+);
+
+#[test]
+fn test_struct_argument() {
+    let mut provider = Provider::default();
+    let geometry = geometry::Geometry::compiled().deploy(&mut provider);
+
+    let point = geometry::Point {
+        x: U256::from(3),
+        y: -4,
+    };
+    assert_eq!(geometry.methods.echo(&mut provider, point.clone()), point);
+    assert_ne!(geometry.address, Address::ZERO);
+}
+
+fn main() {}
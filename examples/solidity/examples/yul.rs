@@ -0,0 +1,29 @@
+use revm::primitives::U256;
+use rustry_macros::yul;
+use rustry_test::prelude::*;
+
+#[test]
+fn test_answer() {
+    let mut provider = Provider::default();
+    let answer = yul!(
+        abi("function answer() view returns (uint256)"),
+        r#"
+            object "Answer" {
+                code {
+                    datacopy(0, dataoffset("runtime"), datasize("runtime"))
+                    return(0, datasize("runtime"))
+                }
+                object "runtime" {
+                    code {
+                        mstore(0, 42)
+                        return(0, 32)
+                    }
+                }
+            }
+        "#
+    )
+    .deploy(&mut provider);
+    assert_eq!(answer.methods.answer(&mut provider), U256::from(42));
+}
+
+fn main() {}
@@ -1,4 +1,4 @@
-use revm::primitives::Address;
+use revm::primitives::{Address, U256};
 use rustry_macros::*;
 use rustry_test::prelude::*;
 
@@ -10,12 +10,16 @@ fn set_up() {
         r#"
 # Setup private variables (only callable from within the contract)
 
-funders: HashMap[address, uint256]
+funders: public(HashMap[address, uint256])
 beneficiary: address
 deadline: public(uint256)
 goal: public(uint256)
 timelimit: public(uint256)
 
+event Participated:
+    funder: indexed(address)
+    amount: uint256
+
 # Setup global variables
 @external
 def __init__(_beneficiary: address, _goal: uint256, _timelimit: uint256):
@@ -31,6 +35,7 @@ def participate():
     assert block.timestamp < self.deadline, "deadline not met (yet)"
 
     self.funders[msg.sender] += msg.value
+    log Participated(msg.sender, msg.value)
 
 # Enough money was raised! Send funds to the beneficiary
 @external
@@ -53,13 +58,30 @@ def refund():
         "#
     };
 
-    // TODO constructor args at the end
-    let mut crowdfund = crowdfund.deploy(&mut provider);
+    let beneficiary = Address::repeat_byte(0xbe);
+    let crowdfund = crowdfund
+        .with_args(beneficiary, U256::from(100), U256::from(3600))
+        .deploy(&mut provider);
 }
 
 #[rustry_test(set_up)]
 fn test_deployment() {
     assert_ne!(crowdfund.address, Address::ZERO);
+    assert_eq!(crowdfund.methods.goal(&mut provider), U256::from(100));
+    assert_eq!(crowdfund.methods.timelimit(&mut provider), U256::from(3600));
+}
+
+#[rustry_test(set_up)]
+fn test_participate() {
+    let sender = provider.sender;
+    provider.mint(U256::from(u128::MAX), sender);
+    crowdfund.methods.participate(&mut provider, U256::from(10));
+
+    assert_eq!(provider.logs.len(), 1);
+    let participated = ParticipatedEvent::decode(&provider.logs[0]).unwrap();
+    assert_eq!(participated.funder, sender);
+    assert_eq!(participated.amount, U256::from(10));
+    assert_eq!(crowdfund.methods.funders(&mut provider, sender), U256::from(10));
 }
 
 fn main() {}
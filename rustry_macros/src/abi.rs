@@ -0,0 +1,219 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{
+    parse_quote, Data, DataEnum, DeriveInput, Error, Fields, GenericParam, Generics, Index, Member,
+};
+
+enum Shape {
+    /// Structs are ABI tuples, with their fields in declaration order
+    Tuple(Vec<(Member, syn::Type)>),
+    /// Fieldless enums are `uint8`, with the variants in declaration order
+    Enum(Vec<syn::Ident>),
+}
+
+fn shape(input: &DeriveInput) -> Result<Shape, Error> {
+    match &input.data {
+        Data::Struct(data) => {
+            let members = match &data.fields {
+                Fields::Named(fields) => fields
+                    .named
+                    .iter()
+                    .map(|f| (Member::Named(f.ident.clone().unwrap()), f.ty.clone()))
+                    .collect(),
+                Fields::Unnamed(fields) => fields
+                    .unnamed
+                    .iter()
+                    .enumerate()
+                    .map(|(i, f)| (Member::Unnamed(Index::from(i)), f.ty.clone()))
+                    .collect(),
+                Fields::Unit => vec![],
+            };
+            Ok(Shape::Tuple(members))
+        }
+        Data::Enum(DataEnum { variants, .. }) => {
+            if variants.len() > 256 {
+                return Err(Error::new_spanned(
+                    &input.ident,
+                    "enums are encoded as uint8 and can't have more than 256 variants",
+                ));
+            }
+            variants
+                .iter()
+                .map(|variant| {
+                    if !matches!(variant.fields, Fields::Unit) {
+                        Err(Error::new_spanned(
+                            variant,
+                            "only fieldless enums map to a Solidity enum",
+                        ))
+                    } else if let Some((_, discriminant)) = &variant.discriminant {
+                        Err(Error::new_spanned(
+                            discriminant,
+                            "Solidity enums are numbered from 0, explicit discriminants are not supported",
+                        ))
+                    } else {
+                        Ok(variant.ident.clone())
+                    }
+                })
+                .collect::<Result<_, _>>()
+                .map(Shape::Enum)
+        }
+        Data::Union(data) => Err(Error::new_spanned(
+            data.union_token,
+            "unions have no ABI representation",
+        )),
+    }
+}
+
+fn add_bound(mut generics: Generics, bound: syn::TypeParamBound) -> Generics {
+    for param in &mut generics.params {
+        if let GenericParam::Type(ty) = param {
+            ty.bounds.push(bound.clone());
+        }
+    }
+    generics
+}
+
+pub fn derive_sol_type(input: DeriveInput) -> TokenStream {
+    let shape = match shape(&input) {
+        Ok(shape) => shape,
+        Err(err) => return err.to_compile_error(),
+    };
+    let name = &input.ident;
+    let generics = add_bound(
+        input.generics.clone(),
+        parse_quote!(rustry_test::utils::abi::SolType),
+    );
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let body = match shape {
+        Shape::Tuple(fields) => {
+            let types: Vec<_> = fields.iter().map(|(_, ty)| ty).collect();
+            quote! {
+                fn sol_type() -> String {
+                    let types: Vec<String> = vec![
+                        #(<#types as rustry_test::utils::abi::SolType>::sol_type()),*
+                    ];
+                    format!("({})", types.join(","))
+                }
+
+                fn is_dynamic() -> bool {
+                    false #(|| <#types as rustry_test::utils::abi::SolType>::is_dynamic())*
+                }
+
+                fn head_len() -> usize {
+                    if <Self as rustry_test::utils::abi::SolType>::is_dynamic() {
+                        32
+                    } else {
+                        0 #(+ <#types as rustry_test::utils::abi::SolType>::head_len())*
+                    }
+                }
+            }
+        }
+        Shape::Enum(_) => quote! {
+            fn sol_type() -> String {
+                String::from("uint8")
+            }
+        },
+    };
+
+    quote! {
+        impl #impl_generics rustry_test::utils::abi::SolType for #name #ty_generics #where_clause {
+            #body
+        }
+    }
+}
+
+pub fn derive_abi_encode(input: DeriveInput) -> TokenStream {
+    let shape = match shape(&input) {
+        Ok(shape) => shape,
+        Err(err) => return err.to_compile_error(),
+    };
+    let name = &input.ident;
+    let generics = add_bound(
+        input.generics.clone(),
+        parse_quote!(rustry_test::utils::abi::AbiEncode),
+    );
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let body = match shape {
+        Shape::Tuple(fields) => {
+            let fields = fields.iter().map(|(member, ty)| {
+                quote! {
+                    (
+                        <#ty as rustry_test::utils::abi::SolType>::is_dynamic(),
+                        rustry_test::utils::abi::AbiEncode::abi_encode(&self.#member),
+                    )
+                }
+            });
+            quote! {
+                rustry_test::utils::abi::encode_tuple(vec![#(#fields),*])
+            }
+        }
+        Shape::Enum(variants) => {
+            let indexes = (0..variants.len()).map(|i| i as u8);
+            quote! {
+                let index: u8 = match self {
+                    #(Self::#variants => #indexes,)*
+                };
+                rustry_test::utils::abi::AbiEncode::abi_encode(&index)
+            }
+        }
+    };
+
+    quote! {
+        impl #impl_generics rustry_test::utils::abi::AbiEncode for #name #ty_generics #where_clause {
+            fn abi_encode(&self) -> Vec<u8> {
+                #body
+            }
+        }
+    }
+}
+
+pub fn derive_abi_decode(input: DeriveInput) -> TokenStream {
+    let shape = match shape(&input) {
+        Ok(shape) => shape,
+        Err(err) => return err.to_compile_error(),
+    };
+    let name = &input.ident;
+    let generics = add_bound(
+        input.generics.clone(),
+        parse_quote!(rustry_test::utils::abi::AbiDecode),
+    );
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let body = match shape {
+        Shape::Tuple(fields) => {
+            let members = fields.iter().map(|(member, _)| member);
+            quote! {
+                #[allow(unused_mut)]
+                let mut decoder = rustry_test::utils::abi::TupleDecoder::new(data, offset);
+                Ok(Self {
+                    #(#members: decoder.decode()?,)*
+                })
+            }
+        }
+        Shape::Enum(variants) => {
+            let indexes = (0..variants.len()).map(|i| i as u8);
+            quote! {
+                let index = <u8 as rustry_test::utils::abi::AbiDecode>::abi_decode_at(data, offset)?;
+                match index {
+                    #(#indexes => Ok(Self::#variants),)*
+                    _ => Err(rustry_test::utils::abi::AbiError::invalid::<Self>(
+                        format!("no variant for {index}"),
+                    )),
+                }
+            }
+        }
+    };
+
+    quote! {
+        impl #impl_generics rustry_test::utils::abi::AbiDecode for #name #ty_generics #where_clause {
+            fn abi_decode_at(
+                data: &[u8],
+                offset: usize,
+            ) -> Result<Self, rustry_test::utils::abi::AbiError> {
+                #body
+            }
+        }
+    }
+}
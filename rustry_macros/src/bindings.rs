@@ -0,0 +1,558 @@
+use crate::options::Alias;
+use proc_macro2::{Ident, TokenStream};
+use quote::{format_ident, quote};
+use rustry_test::{
+    compilers::{
+        artifact::{AbiEntry, EntryUtils, TypeType},
+        solidity::types::{canonical_type, internal_to_type, StructDef, Structs},
+    },
+    utils::abi::get_sig,
+};
+use std::collections::HashMap;
+use syn::{Error, LitStr};
+
+/// A Rust identifier for a Solidity name, escaping the Rust keywords
+pub fn ident(name: &str) -> Ident {
+    match name {
+        "self" | "Self" | "super" | "crate" | "_" => {
+            Ident::new(&format!("{name}_"), proc_macro2::Span::call_site())
+        }
+        name if syn::parse_str::<Ident>(name).is_err() => {
+            Ident::new_raw(name, proc_macro2::Span::call_site())
+        }
+        name => Ident::new(name, proc_macro2::Span::call_site()),
+    }
+}
+
+/// `get_balance` -> `GetBalance`
+fn pascal_case(name: &str) -> String {
+    name.split('_')
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            let first = chars.next().unwrap().to_ascii_uppercase();
+            format!("{first}{}", chars.as_str())
+        })
+        .collect()
+}
+
+/// `(uint256,address)[]` -> `tuple_uint256_address_array`
+fn type_suffix(ty: &str) -> String {
+    let mut suffix = String::new();
+    let mut chars = ty.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '(' => suffix.push_str("tuple_"),
+            ')' => {}
+            ',' => suffix.push('_'),
+            '[' => {
+                suffix.push_str("_array");
+                suffix.extend(chars.by_ref().take_while(|c| *c != ']'));
+            }
+            c => suffix.push(c),
+        }
+    }
+    suffix
+}
+
+/// Fails on an alias renaming none of the functions of `abi`
+pub fn check_aliases<'a>(
+    aliases: &[Alias],
+    abi: impl Iterator<Item = &'a AbiEntry> + Clone,
+) -> Result<(), Error> {
+    for alias in aliases {
+        if !abi.clone().any(|entry| {
+            entry.entry_type == "function" && entry.signature() == alias.signature.value()
+        }) {
+            return Err(Error::new_spanned(
+                &alias.signature,
+                format!("no function {} to alias", alias.signature.value()),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Names of the methods, in the order of `functions`. Overloaded functions are suffixed with
+/// their parameter types (`transfer_address_uint256`), unless aliased by the user, the aliases
+/// of other functions being ignored.
+/// Fails when two functions share a selector, as the contract couldn't dispatch them.
+pub fn method_names(
+    functions: &[&AbiEntry],
+    aliases: &[Alias],
+    source: &LitStr,
+) -> Result<Vec<Ident>, Error> {
+    let mut selectors: HashMap<[u8; 4], String> = HashMap::new();
+    for func in functions {
+        let signature = func.signature();
+        if let Some(other) = selectors.insert(get_sig(&signature), signature.clone()) {
+            return Err(Error::new_spanned(
+                source,
+                format!(
+                    "{signature} and {other} share the selector 0x{}",
+                    hex::encode(get_sig(&signature))
+                ),
+            ));
+        }
+    }
+
+    let mut occurences: HashMap<&str, usize> = HashMap::new();
+    for func in functions {
+        *occurences.entry(&func.name).or_default() += 1;
+    }
+
+    let names: Vec<Ident> = functions
+        .iter()
+        .map(|func| {
+            let signature = func.signature();
+            if let Some(alias) = aliases
+                .iter()
+                .find(|alias| alias.signature.value() == signature)
+            {
+                alias.name.clone()
+            } else if occurences[func.name.as_str()] > 1 && !func.inputs.is_empty() {
+                let types: Vec<_> = func
+                    .inputs
+                    .iter()
+                    .map(|input| type_suffix(&canonical_type(input)))
+                    .collect();
+                ident(&format!("{}_{}", func.name, types.join("_")))
+            } else {
+                ident(&func.name)
+            }
+        })
+        .collect();
+
+    for (i, name) in names.iter().enumerate() {
+        // each method comes with a `try_` one
+        if names[..i].iter().any(|other| {
+            other == name
+                || format_ident!("try_{}", other) == *name
+                || format_ident!("try_{}", name) == *other
+        }) {
+            return Err(Error::new_spanned(
+                source,
+                format!("two functions are bound to `{name}`, use `aliases(..)` to rename one"),
+            ));
+        }
+    }
+
+    Ok(names)
+}
+
+/// Generated structs for the Solidity structs used in the ABI
+pub fn type_defs<'a>(structs: impl Iterator<Item = &'a StructDef>) -> TokenStream {
+    let defs = structs.map(|def| {
+        let name = ident(&def.name);
+        let fields = def.fields.iter().map(|(fname, ftype)| {
+            let fname = ident(fname);
+            let ftype: TokenStream = ftype.parse().unwrap();
+            quote! {
+                pub #fname: #ftype
+            }
+        });
+
+        quote! {
+            #[allow(non_snake_case)]
+            #[derive(
+                Debug,
+                Clone,
+                PartialEq,
+                Eq,
+                rustry_macros::AbiType,
+                rustry_macros::AbiEncode,
+                rustry_macros::AbiDecode
+            )]
+            pub struct #name {
+                #(#fields),*
+            }
+        }
+    });
+
+    quote! {
+        #(#defs)*
+    }
+}
+
+/// The Rust type returned by a method. Several outputs are returned as a tuple,
+/// or as a generated struct when they are all named.
+fn output_type(
+    func: &AbiEntry,
+    meth_name: &Ident,
+    structs: &mut Structs,
+) -> Result<TokenStream, String> {
+    let types = func
+        .outputs
+        .iter()
+        .map(|output| internal_to_type(output, structs))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let output = match types.as_slice() {
+        [] => String::from("()"),
+        [ty] => ty.clone(),
+        _ if func.outputs.iter().all(|output| !output.name.is_empty()) => {
+            let name = format!("{}Output", pascal_case(&meth_name.to_string()));
+            let fields = func
+                .outputs
+                .iter()
+                .map(|output| output.name.clone())
+                .zip(types)
+                .collect();
+            structs.insert(StructDef {
+                name: name.clone(),
+                fields,
+            });
+            name
+        }
+        _ => format!("({})", types.join(", ")),
+    };
+
+    Ok(output.parse().unwrap())
+}
+
+/// The arguments of a function or constructor and their Rust types
+fn params(
+    func: &AbiEntry,
+    structs: &mut Structs,
+) -> Result<(Vec<Ident>, Vec<TokenStream>), String> {
+    let payable = func.state_mutability == "payable";
+
+    let mut args = Vec::with_capacity(func.inputs.len());
+    let mut types = Vec::with_capacity(func.inputs.len());
+    for (i, input) in func.inputs.iter().enumerate() {
+        let arg = match input.name.as_str() {
+            "" => format_ident!("arg{i}"),
+            // taken by the method
+            "provider" => format_ident!("provider_"),
+            "value" if payable => format_ident!("value_"),
+            name => ident(name),
+        };
+        let ty: TokenStream = internal_to_type(input, structs)?.parse().unwrap();
+        args.push(arg);
+        types.push(ty);
+    }
+    Ok((args, types))
+}
+
+/// A method of `ContractMethods`, encoding its arguments and decoding the return data, along
+/// with a `try_` one returning the failed calls
+pub fn method(
+    func: &AbiEntry,
+    meth_name: &Ident,
+    structs: &mut Structs,
+) -> Result<TokenStream, String> {
+    let signature = func.signature();
+
+    let payable = func.state_mutability == "payable";
+    let (args, types) = params(func, structs)?;
+    let output = output_type(func, meth_name, structs)?;
+
+    let fn_call = match func.state_mutability.as_str() {
+        "nonpayable" => quote! {
+            rustry_test::prelude::Frontend::call(provider, self.address, data.into())
+        },
+        "payable" => quote! {
+            rustry_test::prelude::Frontend::call_value(provider, self.address, data.into(), value)
+        },
+        "view" | "pure" => quote! {
+            rustry_test::prelude::Frontend::staticcall(provider, self.address, data.into())
+        },
+        mutability => {
+            return Err(format!(
+                "{signature} has an unknown state mutability: {mutability}"
+            ))
+        }
+    };
+    let value_arg = if payable {
+        quote! { value: revm::primitives::U256, }
+    } else {
+        TokenStream::new()
+    };
+
+    let decode = if func.outputs.len() == 1 {
+        quote! {
+            <(#output,) as rustry_test::utils::abi::AbiDecode>::abi_decode(ret.get_data())
+                .map(|(ret,)| ret)
+        }
+    } else {
+        quote! {
+            <#output as rustry_test::utils::abi::AbiDecode>::abi_decode(ret.get_data())
+        }
+    };
+
+    let try_name = format_ident!("try_{}", meth_name);
+    let value = if payable {
+        quote! { value, }
+    } else {
+        TokenStream::new()
+    };
+
+    Ok(quote! {
+        /// Returns the result of the call instead of panicking when it reverts or halts
+        #[allow(clippy::unused_unit, clippy::too_many_arguments, non_snake_case)]
+        pub fn #try_name(
+            &self,
+            provider: &mut rustry_test::provider::Provider,
+            #value_arg
+            #(#args: #types),*
+        ) -> Result<#output, rustry_test::provider::db::ExecRes> {
+            let data = rustry_test::utils::abi::abi_encode_call(#signature, &(#(#args,)*));
+            let ret = #fn_call;
+            if !ret.is_success() {
+                return Err(ret);
+            }
+            Ok(#decode.unwrap_or_else(|err| panic!("{} returned {:?}: {}", #signature, ret, err)))
+        }
+
+        #[allow(clippy::unused_unit, clippy::too_many_arguments, non_snake_case)]
+        pub fn #meth_name(
+            &self,
+            provider: &mut rustry_test::provider::Provider,
+            #value_arg
+            #(#args: #types),*
+        ) -> #output {
+            self.#try_name(provider, #value #(#args),*)
+                .unwrap_or_else(|ret| panic!("{} failed: {:?}", #signature, ret))
+        }
+    })
+}
+
+/// `with_args(..)` of `ContractInstance`, appending the encoded arguments of the constructor to
+/// the creation code, when it takes some
+pub fn constructor<'a>(
+    mut abi: impl Iterator<Item = &'a AbiEntry>,
+    structs: &mut Structs,
+) -> Result<TokenStream, String> {
+    let Some(constructor) = abi.find(|entry| entry.entry_type == "constructor") else {
+        return Ok(TokenStream::new());
+    };
+    if constructor.inputs.is_empty() {
+        return Ok(TokenStream::new());
+    }
+    let (args, types) = params(constructor, structs)?;
+
+    Ok(quote! {
+        #[allow(clippy::too_many_arguments, non_snake_case)]
+        pub fn with_args(self, #(#args: #types),*) -> Self {
+            let args = rustry_test::utils::abi::AbiEncode::abi_encode(&(#(#args,)*));
+            Self {
+                code: [self.code.to_vec(), args].concat().into(),
+                ..self
+            }
+        }
+    })
+}
+
+/// Whether an indexed parameter is logged as its value, rather than as the hash of its encoding
+fn is_value_type(param: &TypeType) -> bool {
+    !matches!(param.type_type.as_str(), "string" | "bytes")
+        && !param.type_type.contains('[')
+        && param.components.is_none()
+}
+
+/// A `TransferEvent` struct for an event, decoded from the logs of the provider. The indexed
+/// strings, bytes, arrays and tuples are only logged as a hash, which is kept as a `B256`.
+pub fn event(event: &AbiEntry, structs: &mut Structs) -> Result<TokenStream, String> {
+    let name = format_ident!("{}Event", event.name);
+    let signature = event.signature();
+    let topic = revm::primitives::keccak256(signature.as_bytes()).0;
+
+    let mut fields = vec![];
+    let mut types = vec![];
+    let mut topics = vec![];
+    let (mut data_fields, mut data_types) = (vec![], vec![]);
+    let first_topic = usize::from(!event.anonymous);
+    for (i, input) in event.inputs.iter().enumerate() {
+        let field = match input.name.as_str() {
+            "" => format_ident!("arg{i}"),
+            name => ident(name),
+        };
+        let ty: TokenStream = if input.indexed && !is_value_type(input) {
+            quote! { revm::primitives::B256 }
+        } else {
+            internal_to_type(input, structs)?.parse().unwrap()
+        };
+        if input.indexed {
+            let index = first_topic + topics.len();
+            topics.push(if is_value_type(input) {
+                quote! {
+                    let #field = <#ty as rustry_test::utils::abi::AbiDecode>::abi_decode(
+                        log.topics[#index].as_slice()
+                    ).ok()?;
+                }
+            } else {
+                quote! { let #field = log.topics[#index]; }
+            });
+        } else {
+            data_fields.push(field.clone());
+            data_types.push(ty.clone());
+        }
+        fields.push(field);
+        types.push(ty);
+    }
+
+    let topic_count = first_topic + topics.len();
+    let check_topic = if event.anonymous {
+        TokenStream::new()
+    } else {
+        quote! {
+            if log.topics[0] != Self::TOPIC {
+                return None;
+            }
+        }
+    };
+    let decode_data = if data_fields.is_empty() {
+        TokenStream::new()
+    } else {
+        quote! {
+            let (#(#data_fields,)*) =
+                <(#(#data_types,)*) as rustry_test::utils::abi::AbiDecode>::abi_decode(&log.data)
+                    .ok()?;
+        }
+    };
+
+    Ok(quote! {
+        #[allow(non_snake_case)]
+        #[derive(Debug, Clone, PartialEq, Eq)]
+        pub struct #name {
+            #(pub #fields: #types),*
+        }
+
+        impl #name {
+            #[doc = concat!("The first topic of `", #signature, "`, unless anonymous")]
+            pub const TOPIC: revm::primitives::B256 = revm::primitives::B256::new([#(#topic),*]);
+
+            /// Decodes a log of the event, `None` for the other logs
+            pub fn decode(log: &revm::primitives::Log) -> Option<Self> {
+                if log.topics.len() != #topic_count {
+                    return None;
+                }
+                #check_topic
+                #decode_data
+                #(#topics)*
+                Some(Self { #(#fields),* })
+            }
+        }
+    })
+}
+
+/// An `InsufficientBalanceError` struct for a custom error, decoded from revert data
+pub fn error(error: &AbiEntry, structs: &mut Structs) -> Result<TokenStream, String> {
+    let name = format_ident!("{}Error", error.name);
+    let signature = error.signature();
+    let selector = get_sig(&signature);
+    let (args, types) = params(error, structs)?;
+
+    let decode = if args.is_empty() {
+        quote! { args.is_empty().then_some(Self {}) }
+    } else {
+        quote! {
+            let (#(#args,)*) =
+                <(#(#types,)*) as rustry_test::utils::abi::AbiDecode>::abi_decode(args).ok()?;
+            Some(Self { #(#args),* })
+        }
+    };
+
+    Ok(quote! {
+        #[allow(non_snake_case)]
+        #[derive(Debug, Clone, PartialEq, Eq)]
+        pub struct #name {
+            #(pub #args: #types),*
+        }
+
+        impl #name {
+            #[doc = concat!("The selector of `", #signature, "`")]
+            pub const SELECTOR: [u8; 4] = [#(#selector),*];
+
+            /// Decodes the revert data of the error, `None` for other data
+            pub fn decode(data: &[u8]) -> Option<Self> {
+                let args = data.strip_prefix(&Self::SELECTOR)?;
+                #decode
+            }
+        }
+    })
+}
+
+/// `receive()` and `fallback()` helpers, when declared in the ABI
+pub fn special_methods<'a>(abi: impl Iterator<Item = &'a AbiEntry>) -> Vec<TokenStream> {
+    abi.filter_map(|entry| match entry.entry_type.as_str() {
+        "receive" => Some(quote! {
+            pub fn receive(
+                &self,
+                provider: &mut rustry_test::provider::Provider,
+                value: revm::primitives::U256,
+            ) -> rustry_test::provider::db::ExecRes {
+                rustry_test::prelude::Frontend::send(provider, self.address, value)
+            }
+        }),
+        "fallback" if entry.state_mutability == "payable" => Some(quote! {
+            pub fn fallback(
+                &self,
+                provider: &mut rustry_test::provider::Provider,
+                value: revm::primitives::U256,
+                data: Vec<u8>,
+            ) -> rustry_test::provider::db::ExecRes {
+                rustry_test::prelude::Frontend::call_value(provider, self.address, data.into(), value)
+            }
+        }),
+        "fallback" => Some(quote! {
+            pub fn fallback(
+                &self,
+                provider: &mut rustry_test::provider::Provider,
+                data: Vec<u8>,
+            ) -> rustry_test::provider::db::ExecRes {
+                rustry_test::prelude::Frontend::call(provider, self.address, data.into())
+            }
+        }),
+        _ => None,
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::method_names;
+    use proc_macro2::Span;
+    use rustry_test::compilers::artifact::AbiEntry;
+    use syn::LitStr;
+
+    fn names(functions: &[&str]) -> Result<Vec<String>, String> {
+        let functions: Vec<AbiEntry> = functions
+            .iter()
+            .map(|function| function.parse().unwrap())
+            .collect();
+        let functions: Vec<_> = functions.iter().collect();
+        method_names(&functions, &[], &LitStr::new("", Span::call_site()))
+            .map(|names| names.iter().map(ToString::to_string).collect())
+            .map_err(|err| err.to_string())
+    }
+
+    #[test]
+    fn suffixes_overloads() {
+        assert_eq!(
+            names(&[
+                "function transfer(address to, uint256 amount)",
+                "function transfer(address to)",
+                "function type()",
+            ]),
+            Ok(vec![
+                String::from("transfer_address_uint256"),
+                String::from("transfer_address"),
+                String::from("r#type"),
+            ])
+        );
+    }
+
+    #[test]
+    fn rejects_selector_clashes() {
+        assert_eq!(
+            names(&[
+                "function burn(uint256 amount)",
+                "function collate_propagate_storage(bytes16 data)",
+            ]),
+            Err(String::from(
+                "collate_propagate_storage(bytes16) and burn(uint256) share the selector 0x42966c68"
+            ))
+        );
+    }
+}
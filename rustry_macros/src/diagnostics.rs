@@ -0,0 +1,236 @@
+use crate::options::MacroOptions;
+use proc_macro::{Diagnostic, Level};
+use proc_macro2::{Span, TokenStream, TokenTree};
+use quote::quote;
+use rustry_test::compilers::{
+    builder::{CompilerError, Diagnostics},
+    solidity::solc::{self, OutError, Severity},
+    vyper::vyperc,
+};
+use std::ops::Range;
+use syn::{Error, LitStr};
+
+/// A location reported by a compiler, as a byte range of its source file
+pub trait Location {
+    fn file(&self) -> &str;
+    fn range(&self, content: &str) -> Option<Range<usize>>;
+}
+
+impl Location for solc::SourceLocation {
+    fn file(&self) -> &str {
+        &self.file
+    }
+
+    fn range(&self, _: &str) -> Option<Range<usize>> {
+        let start = usize::try_from(self.start).ok()?;
+        let end = usize::try_from(self.end).ok()?;
+        Some(start..end.max(start))
+    }
+}
+
+impl Location for vyperc::SourceLocation {
+    fn file(&self) -> &str {
+        &self.file
+    }
+
+    /// Vyper only gives the start of the node, which is extended to the end of its first word
+    fn range(&self, content: &str) -> Option<Range<usize>> {
+        let line = content
+            .split_inclusive('\n')
+            .nth(self.lineno?.checked_sub(1)?)?;
+        let line_start = line.as_ptr() as usize - content.as_ptr() as usize;
+        let start = line_start + self.col_offset.unwrap_or(0).min(line.trim_end().len());
+        let rest = &content[start..];
+        let word = rest
+            .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len());
+        let end = start + word.max(rest.chars().next().map_or(0, char::len_utf8));
+        Some(start..end)
+    }
+}
+
+/// Points the compiler diagnostics at the code of the macro literal
+pub struct SourceSpans<'a> {
+    lit_str: &'a LitStr,
+    /// Name of the inline source given to the compiler, `None` when the code is read from a file
+    entry: Option<&'a str>,
+    content: String,
+}
+
+impl<'a> SourceSpans<'a> {
+    pub fn new(lit_str: &'a LitStr, entry: &'a str, opts: &MacroOptions) -> Self {
+        Self {
+            lit_str,
+            entry: opts.path.is_none().then_some(entry),
+            content: lit_str.value(),
+        }
+    }
+
+    /// Sub-span of the literal covering a location of the inline source, if it can be resolved
+    fn span(&self, location: &impl Location) -> Option<Span> {
+        if Some(location.file()) != self.entry {
+            return None;
+        }
+        let range = location.range(&self.content)?;
+        let offsets = value_offsets(&self.lit_str.token().to_string())?;
+        let (start, end) = (*offsets.get(range.start)?, *offsets.get(range.end)?);
+
+        let literal = TokenStream::from(TokenTree::Literal(self.lit_str.token()));
+        match proc_macro::TokenStream::from(literal).into_iter().next()? {
+            proc_macro::TokenTree::Literal(literal) => literal.subspan(start..end).map(Span::from),
+            _ => None,
+        }
+    }
+
+    /// The span of a diagnostic and its message, which only keeps its first line when the
+    /// diagnostic is located, the others quoting the code
+    fn locate<S: Location, E>(&self, err: &OutError<S, E>) -> (Span, String) {
+        let formatted = err.formatted().trim_end();
+        match err.source_location.as_ref().and_then(|loc| self.span(loc)) {
+            Some(span) => (
+                span,
+                formatted.lines().next().unwrap_or_default().to_string(),
+            ),
+            None => (self.lit_str.span(), formatted.to_string()),
+        }
+    }
+
+    /// Emits the secondary locations of a diagnostic as notes
+    fn notes<S, E>(&self, err: &OutError<S, E>) {
+        for location in err.secondary_source_locations.iter().flatten() {
+            let message = location.message.as_deref().unwrap_or("related location");
+            let span = self.span(location).unwrap_or_else(|| self.lit_str.span());
+            Diagnostic::spanned(span.unwrap(), Level::Note, message).emit();
+        }
+    }
+}
+
+/// Offsets in the code of a string literal of each byte of its value, followed by the offset of
+/// its closing quote
+fn value_offsets(repr: &str) -> Option<Vec<usize>> {
+    if let Some(raw) = repr.strip_prefix('r') {
+        let hashes = raw.len() - raw.trim_start_matches('#').len();
+        return Some((1 + hashes + 1..=repr.len() - 1 - hashes).collect());
+    }
+
+    let mut offsets = vec![];
+    let mut chars = repr.char_indices().skip(1).peekable();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => {
+                offsets.push(i);
+                return Some(offsets);
+            }
+            '\\' => match chars.next()?.1 {
+                // line continuation, skipping the leading whitespace of the next line
+                '\n' => while chars.next_if(|(_, c)| c.is_whitespace()).is_some() {},
+                'x' => {
+                    chars.nth(1)?;
+                    offsets.push(i);
+                }
+                'u' => {
+                    let code: String = chars
+                        .by_ref()
+                        .map(|(_, c)| c)
+                        .take_while(|c| *c != '}')
+                        .filter(|c| *c != '{')
+                        .collect();
+                    let c = char::from_u32(u32::from_str_radix(&code, 16).ok()?)?;
+                    offsets.resize(offsets.len() + c.len_utf8(), i);
+                }
+                _ => offsets.push(i),
+            },
+            c => offsets.extend(i..i + c.len_utf8()),
+        }
+    }
+    None
+}
+
+/// Emits the warnings and infos of a compilation at their location in the code
+pub fn warn<S: Location, E>(errors: &[OutError<S, E>], spans: &SourceSpans) {
+    for err in errors {
+        let level = match err.severity {
+            Severity::Error => continue,
+            Severity::Warning => Level::Warning,
+            Severity::Info => Level::Note,
+        };
+        let (span, message) = spans.locate(err);
+        let message = ["Warning: ", "Info: "]
+            .iter()
+            .find_map(|prefix| message.strip_prefix(prefix))
+            .unwrap_or(&message);
+        Diagnostic::spanned(span.unwrap(), level, message).emit();
+        spans.notes(err);
+    }
+}
+
+fn errors<S: Location, E>(
+    errors: &[OutError<S, E>],
+    opts: &MacroOptions,
+    spans: &SourceSpans,
+) -> TokenStream {
+    warn(errors, spans);
+    let errors = errors
+        .iter()
+        .filter(|err| err.is_error())
+        .map(|err| {
+            spans.notes(err);
+            let (span, message) = spans.locate(err);
+            Error::new(span, message).to_compile_error()
+        })
+        .collect::<Vec<_>>();
+    // several `compile_error!` only expand as statements
+    if opts.module.is_some() {
+        quote! { const _: () = { #(#errors)* }; }
+    } else {
+        quote! { { #(#errors)* } }
+    }
+}
+
+/// Reports a failed compilation, every compiler error being its own `compile_error!`
+pub fn compiler_error(err: CompilerError, opts: &MacroOptions, spans: &SourceSpans) -> TokenStream {
+    let lit_str = spans.lit_str;
+    match err {
+        CompilerError::VersionError(err) => {
+            Error::new_spanned(opts.version.as_ref().unwrap_or(lit_str), err).to_compile_error()
+        }
+        CompilerError::Diagnostics(Diagnostics::Solc(errs)) => errors(&errs, opts, spans),
+        CompilerError::Diagnostics(Diagnostics::Vyper(errs)) => errors(&errs, opts, spans),
+        err => Error::new_spanned(lit_str, err).to_compile_error(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::value_offsets;
+
+    #[test]
+    fn plain() {
+        assert_eq!(value_offsets(r#""ab""#), Some(vec![1, 2, 3]));
+        // two bytes for `é`
+        assert_eq!(value_offsets(r#""é""#), Some(vec![1, 2, 3]));
+        assert_eq!(value_offsets(r#""ab"#), None);
+    }
+
+    #[test]
+    fn escapes() {
+        assert_eq!(value_offsets(r#""a\nb""#), Some(vec![1, 2, 4, 5]));
+        assert_eq!(value_offsets(r#""\"""#), Some(vec![1, 3]));
+        assert_eq!(value_offsets(r#""\x41b""#), Some(vec![1, 5, 6]));
+        // `é` again, both bytes at the escape
+        assert_eq!(value_offsets(r#""\u{e9}""#), Some(vec![1, 1, 7]));
+    }
+
+    #[test]
+    fn raw_strings() {
+        assert_eq!(value_offsets(r#"r"a\n""#), Some(vec![2, 3, 4, 5]));
+        assert_eq!(value_offsets(r##"r#"a"b"#"##), Some(vec![3, 4, 5, 6]));
+    }
+
+    #[test]
+    fn multi_line() {
+        assert_eq!(value_offsets("\"a\nb\""), Some(vec![1, 2, 3, 4]));
+        // the continuation skips the indentation of the next line
+        assert_eq!(value_offsets("\"a\\\n    b\""), Some(vec![1, 8, 9]));
+    }
+}
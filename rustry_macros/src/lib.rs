@@ -1,22 +1,37 @@
+#![feature(proc_macro_diagnostic)]
 #![feature(proc_macro_span)]
 #![feature(slice_take)]
 
+mod abi;
+mod bindings;
+mod diagnostics;
 mod harness; // TODO wat do ?
+mod options;
 
-use proc_macro::{Span, TokenStream};
-use proc_macro2::Ident;
-use quote::{quote, ToTokens};
-use rustry_test::compilers::{
-    builder::{BinError, Compiler, CompilerError, CompilerKinds},
-    huff::huffc::HuffcOut,
-    solidity::{
-        solc::{self, EntryUtils, SolcOut},
-        types::internal_to_type,
+use options::MacroOptions;
+use proc_macro::{Diagnostic, Level, Span, TokenStream};
+use quote::{format_ident, quote, ToTokens};
+use rustry_test::{
+    compilers::{
+        artifact::{AbiEntry, Artifact, Artifacts, EntryUtils},
+        backend::{self, BackendOutput},
+        builder::{Compiler, CompilerError, CompilerKinds},
+        huff::huffc::HuffSettings,
+        solidity::{
+            solc::{SolcOut, SolcSettings},
+            types::Structs,
+        },
+        sources::SourceFiles,
+        vyper::vyperc::VypercOut,
     },
-    vyper::vyperc::VypercOut,
+    project::Project,
+    utils::asm,
+};
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
 };
-use std::{collections::HashMap, iter};
-use syn::{parse_macro_input, Error, ItemFn};
+use syn::{parse_macro_input, DeriveInput, Error, ItemFn, LitStr};
 
 /// # Examples
 ///
@@ -104,310 +119,1175 @@ pub fn rustry_test(args: TokenStream, input: TokenStream) -> TokenStream {
     .into()
 }
 
-// TODO figure out the source mappings
+/// Compiles Solidity code and returns a `ContractInstance` with a method per function.
+///
+/// The code is given inline or read from `path = "src/Vault.sol"`, relative to the crate root.
+/// Imported files are compiled along, and the crate is rebuilt when one of them changes.
+/// As in a Foundry project, imports go through the remappings of the crate's `foundry.toml` and
+/// `remappings.txt`, and are searched in its `libs`.
+///
+/// The sole or last-defined contract is bound, unless another one is selected with
+/// `contract = "Vault"`. `module = contracts` binds every contract instead, as a module with
+/// a `Vault`, `VaultMethods` and `DeployedVault` type per contract.
+///
+/// The structs of the ABI are generated as Rust structs, e.g. `Position` for `Vault.Position`,
+/// which are named through the module, as `contracts::Position`. The other macros take a `module`
+/// as well, binding their contract as `ContractInstance` in it. Without one, the macro is an
+/// expression whose types can't be named.
+///
+/// The solc version is the latest installed in `~/.rustry/compilers/solc-<version>` satisfying
+/// the pragmas, or `version = "0.8.20"`. Without any installed there, the `solc` of `PATH` is used.
+///
+/// The errors and warnings of solc point at their code in the literal, with the related locations
+/// as notes.
+///
+/// Outputs are cached in `target/rustry-cache`, so that the same contract is compiled once across
+/// the invocations and the builds. Set `RUSTRY_NO_CACHE` to always run the compiler.
+///
+/// The compilation settings are options as well: `optimizer(runs = 1000)`, `via_ir = true`,
+/// `evm_version = "paris"`, `bytecode_hash = "none"`, `append_cbor = false`,
+/// `revert_strings = "strip"` and `libraries("src/Math.sol:Math" = "0x..")`.
+///
+/// The external libraries not given to `libraries(..)` are deployed along with the contract, in
+/// dependency order, unless linked to deployed ones with `.link("Math", address)` before
+/// `.deploy(&mut provider)`.
+///
+/// The methods panic when the call fails, their `try_` variant (`try_transfer`) returning the
+/// `ExecRes` of the failed call instead. Overloaded functions are suffixed with their parameter
+/// types (`transfer_address_uint256`), or renamed with `aliases("transfer(address,uint256)" =
+/// send, ..)`.
+///
+/// Each event is bound as a struct decoding the logs of the provider, e.g. `TransferEvent`, and
+/// each custom error as one decoding revert data, e.g. `InsufficientBalanceError`, as for the
+/// other languages.
+///
+/// A proxy is bound along with the functions of the contract it delegates its calls to, with
+/// `contract = "Proxy", implementation = "Vault"`. Two of their functions sharing a selector fail
+/// the binding, as the proxy would shadow the one of the implementation.
+///
+/// A constructor taking arguments is given them with `ContractInstance::with_args(..)` before
+/// deploying.
 #[proc_macro]
 pub fn solidity(input: TokenStream) -> TokenStream {
-    let lit_str = parse_macro_input!(input as syn::LitStr);
-    let source_code = lit_str.value();
+    let opts = parse_macro_input!(input as MacroOptions);
+    let lit_str = match opts
+        .allow(options::SOLIDITY_OPTIONS)
+        .and_then(|_| match (&opts.contract, &opts.module) {
+            (Some(_), Some(module)) => Err(Error::new_spanned(
+                module,
+                "`module` binds every contract and can't be combined with `contract`",
+            )),
+            _ => Ok(()),
+        })
+        .and_then(|_| opts.source())
+    {
+        Ok(lit_str) => lit_str,
+        Err(err) => return err.to_compile_error().into(),
+    };
+    let files = match opts.sources(&CompilerKinds::Solc, "source_code.sol") {
+        Ok(files) => files,
+        Err(err) => return err.to_compile_error().into(),
+    };
+    let tracking = options::track_files(&files.paths);
+    let spans = diagnostics::SourceSpans::new(lit_str, &files.entry, &opts);
 
     let solc = Compiler {
         kind: CompilerKinds::Solc,
-        sources: HashMap::from([(String::from("source_code.sol"), source_code.clone())]),
+        sources: files.sources.clone(),
+        entry: files.entry.clone(),
+        remappings: files.remappings.clone(),
+        settings: opts.settings.clone(),
+        version: opts.version.as_ref().map(LitStr::value),
+        huff: HuffSettings::default(),
     };
 
     match solc.run() {
         Ok(out) => {
-            let solc_out = SolcOut::try_from(out).unwrap();
-            let contracts = solc_out.contracts.unwrap();
-            let contract = contracts
-                .get("source_code.sol")
-                .unwrap()
-                .get("Counter")
-                .unwrap();
-
-            let bytecode = &contract
-                .evm
-                .as_ref()
-                .unwrap()
-                .bytecode
-                .as_ref()
-                .unwrap()
-                .object;
-
-            let functions: Vec<_> = contract
-                .abi
-                .as_ref()
-                .unwrap()
+            let solc_out = match SolcOut::try_from(out) {
+                Ok(solc_out) => solc_out,
+                Err(err) => return Error::new_spanned(lit_str, err).to_compile_error().into(),
+            };
+            diagnostics::warn(solc_out.errors.as_deref().unwrap_or_default(), &spans);
+            let available = solc_out.contract_names(&files.entry);
+            // the libraries called by the contracts may be defined in the other files
+            let artifacts = solc_out.artifacts();
+            let contracts: HashMap<String, Artifact> = artifacts
                 .iter()
-                .filter(|entry| entry.entry_type == "function")
+                .filter(|artifact| artifact.file == files.entry)
+                .map(|artifact| (artifact.name.clone(), artifact.clone()))
                 .collect();
+            if contracts.is_empty() {
+                return Error::new_spanned(lit_str, "no contract is defined")
+                    .to_compile_error()
+                    .into();
+            }
 
-            // ugly shit, use Iterator::partition
-            let mut names_occur: HashMap<String, usize> = HashMap::new();
-            functions.iter().for_each(|func| {
-                let entry = names_occur.entry(func.name.clone()).or_default();
-                *entry += 1;
-            });
-            let mut names_occur: HashMap<_, _> =
-                names_occur.into_iter().filter(|(_, v)| *v > 1).collect();
-            let functions: Vec<(&solc::AbiEntry, Ident)> = functions
-                .into_iter()
-                .map(|func| {
-                    let new_name = if let Some(count) = names_occur.get_mut(&func.name) {
-                        *count -= 1;
-                        format!("{}{count}", func.name)
-                    } else {
-                        func.name.clone()
-                    };
+            if let Some(module) = &opts.module {
+                let bound: Vec<_> = available
+                    .iter()
+                    .filter_map(|name| Some((name, contracts.get(name)?)))
+                    .collect();
+                let mut structs = Structs::new(bound.iter().flat_map(|(_, contract)| &contract.abi));
+                let mut logs = Logs::new();
+                let checked = bindings::check_aliases(
+                    &opts.aliases,
+                    bound.iter().flat_map(|(_, contract)| &contract.abi),
+                );
+                if let Err(err) = checked {
+                    return err.to_compile_error().into();
+                }
+                let items = bound
+                    .into_iter()
+                    .map(|(name, contract)| {
+                        let bindings = abi_bindings(
+                            &contract.abi,
+                            &opts,
+                            lit_str,
+                            &mut structs,
+                            &mut logs,
+                        )?;
+                        Ok(contract_items(
+                            &ContractNames::of(name),
+                            bindings,
+                            contract,
+                            &artifacts,
+                        ))
+                    })
+                    .collect::<Result<Vec<_>, Error>>();
+                let items = match items {
+                    Ok(items) => items,
+                    Err(err) => return err.to_compile_error().into(),
+                };
+                let type_defs = type_defs(&structs, &logs);
 
-                    (func, Ident::new(&new_name, proc_macro2::Span::call_site()))
-                })
-                .rev()
-                .collect();
+                return quote! {
+                    #[allow(clippy::upper_case_acronyms)]
+                    pub mod #module {
+                        #tracking
 
-            let impl_fns = functions.iter().map(|(func, meth_name)| {
-                let signature: proc_macro2::TokenStream = func.signature().parse().unwrap();
-                let inputs_w_types = func.inputs.iter().map(|input| {
-                    let iname: proc_macro2::TokenStream = input.name.clone().parse().unwrap();
-                    let itype: proc_macro2::TokenStream =
-                        internal_to_type(&input.type_type).parse().unwrap();
-                    quote! {
-                        #iname: #itype
-                    }
-                });
-
-                // let outputs = func.outputs.iter().map(|output| {
-                //     let otype: proc_macro2::TokenStream =
-                //         internal_to_type(&output.type_type).parse().unwrap();
-                //     quote! {
-                //         #otype
-                //     }
-                // });
-
-                let fn_call = match func.state_mutability.as_str() {
-                    "nonpayable" => quote! {
-                        provider.call(self.address, abi_encode_signature(stringify!(#signature), vec![]).into());
-                    },
-                    "view" => quote! {
-                        let ret = provider.staticcall(
-                            self.address, 
-                            abi_encode_signature(stringify!(#signature), vec![]).into()
-                        );
-                    },
-                    _ => unimplemented!(),
-                };
+                        #type_defs
 
-                let mut outputs = func.outputs.iter();
-                let (output, fn_ret) = if let Some(output) = outputs.next() {
-                    let output: proc_macro2::TokenStream = internal_to_type(&output.type_type).parse().unwrap();
-                    if outputs.next().is_some() {
-                        return syn::Error::new_spanned(
-                            lit_str.clone(), 
-                            "cannot use > 1 output param"
-                        ).to_compile_error();
+                        #(#items)*
                     }
+                }
+                .into();
+            }
 
-                    // let fn_ret = func.outputs.iter().map(|_| 0u128);
-                    // TODO let fn_ret = func.outputs.iter().map(|_| revm::primitives::U256::ZERO);
-                    // let output = func.outputs[0];
-                    (
-                        quote! {
-                            // TODO once we support non U256 types
-                            // stringify!(#output)
-                            U256
-                        },
-                        quote! {
-                            let data = ret.get_data();
-                            U256::from_be_bytes::<32>(abi_decode(data, vec![AbiType::Uint]).try_into().unwrap())
-                        }
-                    )
-                } else {
-                    (
-                        quote! { () },
-                        proc_macro2::TokenStream::new()
+            let contract = match &opts.contract {
+                Some(name) => contracts.get(&name.value()).ok_or_else(|| {
+                    Error::new_spanned(
+                        name,
+                        format!(
+                            "no contract `{}`, available contracts: {}",
+                            name.value(),
+                            available.join(", ")
+                        ),
                     )
+                }),
+                None => solc_out
+                    .default_contract(&files.entry)
+                    .and_then(|name| contracts.get(&name))
+                    .ok_or_else(|| {
+                        Error::new_spanned(
+                            lit_str,
+                            format!(
+                                "select the contract to bind with `contract = \"..\"`, available contracts: {}",
+                                available.join(", ")
+                            ),
+                        )
+                    }),
+            };
+            let contract = contract.and_then(|contract| {
+                let Some(name) = &opts.implementation else {
+                    return Ok(contract.clone());
                 };
-
-                quote! {
-                    #[allow(clippy::unused_unit)]
-                    pub fn #meth_name<'a>(
-                        &self,
-                        provider: &'a mut rustry_test::provider::Provider,
-                        #(#inputs_w_types),*
-                    // ) -> (#(#outputs),*) {
-                    ) -> #output {
-                        #fn_call
-
-                        // (#(#fn_ret),*)
-                        #fn_ret
-                    }
-                    // pub fn #meth_name() {}
-                }
+                // the implementation may be imported from another file
+                let implementation = contracts
+                    .get(&name.value())
+                    .or_else(|| artifacts.iter().find(|a| a.name == name.value()))
+                    .ok_or_else(|| {
+                        Error::new_spanned(
+                            name,
+                            format!(
+                                "no contract `{}`, available contracts: {}",
+                                name.value(),
+                                available.join(", ")
+                            ),
+                        )
+                    })?;
+                Ok(with_implementation(contract, implementation))
             });
-
-            make_contract_instance(impl_fns, bytecode)
-        }
-        Err(err) => match err {
-            CompilerError::BuilderError(_) => todo!(),
-            CompilerError::BinError(err) => match err {
-                BinError::Json(json_err) => {
-                    Error::new_spanned(source_code, json_err.message).to_compile_error()
+            match contract {
+                Ok(contract) => {
+                    artifact_instance(&contract, &artifacts, &opts, lit_str, tracking)
                 }
-            },
-        },
+                Err(err) => err.to_compile_error(),
+            }
+        }
+        Err(err) => diagnostics::compiler_error(err, &opts, &spans),
     }
     .into()
 }
 
+/// A proxy bound with the functions, events and errors of the implementation it delegates its
+/// calls to. A function of the implementation sharing the selector of one of the proxy would be
+/// shadowed by it, which fails the binding.
+fn with_implementation(proxy: &Artifact, implementation: &Artifact) -> Artifact {
+    let mut abi = proxy.abi.clone();
+    abi.extend(
+        implementation
+            .abi
+            .iter()
+            .filter(|entry| matches!(entry.entry_type.as_str(), "function" | "event" | "error"))
+            .cloned(),
+    );
+    Artifact {
+        method_identifiers: Artifact::method_identifiers(&abi),
+        abi,
+        ..proxy.clone()
+    }
+}
+
+/// A `ContractInstance` of a compiled contract, whichever its language, deploying the libraries
+/// it calls found in `libraries`
+fn artifact_instance(
+    artifact: &Artifact,
+    libraries: &[Artifact],
+    opts: &MacroOptions,
+    lit_str: &LitStr,
+    tracking: proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+    let mut structs = Structs::new(&artifact.abi);
+    let mut logs = Logs::new();
+    let bindings = bindings::check_aliases(&opts.aliases, artifact.abi.iter())
+        .and_then(|_| abi_bindings(&artifact.abi, opts, lit_str, &mut structs, &mut logs));
+    let bindings = match bindings {
+        Ok(bindings) => bindings,
+        Err(err) => return err.to_compile_error(),
+    };
+
+    let type_defs = type_defs(&structs, &logs);
+    make_contract_instance(
+        bindings,
+        quote! {
+            #tracking
+            #type_defs
+        },
+        artifact,
+        libraries,
+        opts.module.as_ref(),
+    )
+}
+
+/// The items generated from the ABI of a contract, whichever its language
+struct AbiBindings {
+    /// The methods of `ContractMethods`
+    methods: Vec<proc_macro2::TokenStream>,
+    /// `with_args(..)` of `ContractInstance`, if the constructor takes arguments
+    constructor: proc_macro2::TokenStream,
+}
+
+/// The `TransferEvent` and `InsufficientBalanceError` types met in the ABIs, by Rust name, with
+/// the signature they decode
+type Logs = BTreeMap<String, (String, proc_macro2::TokenStream)>;
+
+/// The bindings of a contract, with the structs, events and errors of its ABI collected in
+/// `structs` and `logs`
+fn abi_bindings(
+    abi: &[AbiEntry],
+    opts: &MacroOptions,
+    lit_str: &syn::LitStr,
+    structs: &mut Structs,
+    logs: &mut Logs,
+) -> Result<AbiBindings, Error> {
+    let functions: Vec<_> = abi
+        .iter()
+        .filter(|entry| entry.entry_type == "function")
+        .collect();
+
+    let names = bindings::method_names(&functions, &opts.aliases, lit_str)?;
+
+    let mut impl_fns = functions
+        .iter()
+        .zip(&names)
+        .map(|(func, meth_name)| bindings::method(func, meth_name, structs))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| Error::new_spanned(lit_str, err))?;
+    impl_fns.extend(bindings::special_methods(abi.iter()));
+    let constructor = bindings::constructor(abi.iter(), structs)
+        .map_err(|err| Error::new_spanned(lit_str, err))?;
+
+    for entry in abi
+        .iter()
+        .filter(|entry| matches!(entry.entry_type.as_str(), "event" | "error"))
+    {
+        let name = match entry.entry_type.as_str() {
+            "event" => format!("{}Event", entry.name),
+            _ => format!("{}Error", entry.name),
+        };
+        let signature = entry.signature();
+        // the contracts of a module share the events and errors they inherit
+        match logs.get(&name) {
+            Some((known, _)) if *known == signature => continue,
+            Some(_) => {
+                return Err(Error::new_spanned(
+                    lit_str,
+                    format!(
+                        "the {} {} is overloaded, which the bindings don't support",
+                        entry.entry_type, entry.name
+                    ),
+                ))
+            }
+            None => {}
+        }
+        let item = match entry.entry_type.as_str() {
+            "event" => bindings::event(entry, structs),
+            _ => bindings::error(entry, structs),
+        }
+        .map_err(|err| Error::new_spanned(lit_str, err))?;
+        logs.insert(name, (signature, item));
+    }
+
+    Ok(AbiBindings {
+        methods: impl_fns,
+        constructor,
+    })
+}
+
+/// The structs, events and errors collected while binding ABIs
+fn type_defs(structs: &Structs, logs: &Logs) -> proc_macro2::TokenStream {
+    let type_defs = bindings::type_defs(structs.values());
+    let logs = logs.values().map(|(_, item)| item);
+    quote! {
+        #type_defs
+
+        #(#logs)*
+    }
+}
+
+/// Compiles Vyper code, given inline or read from `path = "contracts/Crowdfund.vy"` relative to
+/// the crate root, and returns a `ContractInstance`. The EVM version is set with
+/// `evm_version = "shanghai"`, and the compiler picked from `~/.rustry/compilers/vyper-<version>`
+/// with the `# @version` pragma or `version = "0.3.10"`. Compiler errors point at their line in
+/// the code.
+///
+/// The functions, events and errors are bound as for Solidity, e.g.
+/// `crowdfund.methods.participate(&mut provider, value)` and `ParticipatedEvent`, and the
+/// constructor arguments are encoded with `ContractInstance::with_args(beneficiary, goal,
+/// timelimit)`.
 #[proc_macro]
 pub fn vyper(input: TokenStream) -> TokenStream {
-    let lit_str = parse_macro_input!(input as syn::LitStr);
-    let source_code = lit_str.value();
+    let opts = parse_macro_input!(input as MacroOptions);
+    let checked = opts
+        .allow(options::VYPER_OPTIONS)
+        .and_then(|_| opts.source())
+        .and_then(|lit_str| {
+            Ok((
+                lit_str,
+                opts.sources(&CompilerKinds::Vyper, "source_code.vy")?,
+            ))
+        });
+    let (lit_str, files) = match checked {
+        Ok(checked) => checked,
+        Err(err) => return err.to_compile_error().into(),
+    };
+    let tracking = options::track_files(&files.paths);
+    let spans = diagnostics::SourceSpans::new(lit_str, &files.entry, &opts);
+    // Vyper names the contract after its file
+    let name = Path::new(&files.entry)
+        .file_stem()
+        .unwrap()
+        .to_string_lossy();
 
     let vyperc = Compiler {
         kind: CompilerKinds::Vyper,
-        sources: HashMap::from([(String::from("source_code.vy"), source_code.clone())]),
+        sources: files.sources.clone(),
+        entry: files.entry.clone(),
+        remappings: vec![],
+        settings: SolcSettings {
+            evm_version: opts.settings.evm_version.clone(),
+            ..Default::default()
+        },
+        version: opts.version.as_ref().map(LitStr::value),
+        huff: HuffSettings::default(),
     };
 
     match vyperc.run() {
         Ok(out) => {
-            let vyc_out = VypercOut::try_from(out).unwrap();
-            let contracts = vyc_out.contracts.unwrap();
-            let contract = contracts
-                .get("source_code.vy")
-                .unwrap()
-                .get("source_code")
-                .unwrap();
-
-            let bytecode = &contract
-                .evm
-                .as_ref()
-                .unwrap()
-                .bytecode
-                .as_ref()
-                .unwrap()
-                .object
-                .trim_start_matches("0x")
-                .to_string();
-
-            make_contract_instance(iter::empty::<proc_macro2::TokenStream>(), bytecode)
+            let vyc_out = match VypercOut::try_from(out) {
+                Ok(vyc_out) => vyc_out,
+                Err(err) => return Error::new_spanned(lit_str, err).to_compile_error().into(),
+            };
+            diagnostics::warn(vyc_out.errors.as_deref().unwrap_or_default(), &spans);
+            match vyc_out.artifact(&files.entry, &name) {
+                Some(contract) => artifact_instance(&contract, &[], &opts, lit_str, tracking),
+                None => Error::new_spanned(lit_str, format!("no contract `{name}` in the output"))
+                    .to_compile_error(),
+            }
         }
-        Err(err) => match err {
-            CompilerError::BuilderError(_) => todo!(),
-            CompilerError::BinError(err) => match err {
-                BinError::Json(json_err) => {
-                    Error::new_spanned(source_code, json_err.message).to_compile_error()
-                }
-            },
-        },
+        Err(err) => diagnostics::compiler_error(err, &opts, &spans),
     }
     .into()
 }
 
+/// Compiles Huff code, given inline or read from `path = "contracts/Store.huff"` relative to
+/// the crate root along with the files it `#include`s, and returns a `ContractInstance`. A
+/// literal ending with `.huff` is read as a path relative to the working directory.
+///
+/// The constructor arguments are given with `constructor_args("0x..", "42")`, the constants
+/// overridden with `constants(OWNER = "0x..", FEE = 100)`, and other entry points picked with
+/// `alt_main = "MAIN_V2"` and `alt_constructor = "CONSTRUCTOR_V2"`. The EVM version is set with
+/// `evm_version = "shanghai"`.
+///
+/// The functions declared with `#define function` are bound as for Solidity, e.g.
+/// `store.methods.setValue(&mut provider, value)`.
 #[proc_macro]
 pub fn huff(input: TokenStream) -> TokenStream {
-    let lit_str = parse_macro_input!(input as syn::LitStr);
-    let input_str = lit_str.value();
-
-    // heuristics for checking whether we're referencing a file or raw code
-    let source_code = if input_str.ends_with(".huff") {
-        std::fs::read_to_string(&input_str)
-            .unwrap_or_else(|_| panic!("Unable to read file: {}", input_str))
-    } else {
-        input_str
+    let opts = parse_macro_input!(input as MacroOptions);
+    let checked = opts
+        .allow(options::HUFF_OPTIONS)
+        .and_then(|_| opts.source())
+        .and_then(|lit_str| {
+            // heuristics for checking whether we're referencing a file or raw code
+            let files = if opts.path.is_none() && lit_str.value().ends_with(".huff") {
+                let root = std::env::current_dir().unwrap_or_default();
+                SourceFiles::read(&root, &lit_str.value(), &CompilerKinds::Huff)
+                    .map_err(|err| Error::new_spanned(lit_str, err))?
+            } else {
+                opts.sources(&CompilerKinds::Huff, "source_code.huff")?
+            };
+            Ok((lit_str, files))
+        });
+    let (lit_str, files) = match checked {
+        Ok(checked) => checked,
+        Err(err) => return err.to_compile_error().into(),
     };
+    let tracking = options::track_files(&files.paths);
+    let spans = diagnostics::SourceSpans::new(lit_str, &files.entry, &opts);
 
     let huffc = Compiler {
         kind: CompilerKinds::Huff,
-        sources: HashMap::from([(String::from("source_code.huff"), source_code.clone())]),
+        sources: files.sources.clone(),
+        entry: files.entry.clone(),
+        remappings: vec![],
+        settings: SolcSettings::default(),
+        version: None,
+        huff: HuffSettings {
+            evm_version: opts.settings.evm_version.clone(),
+            ..opts.huff.clone()
+        },
     };
 
     match huffc.run() {
+        Ok(out) => match out.artifacts().first() {
+            Some(contract) => artifact_instance(contract, &[], &opts, lit_str, tracking),
+            None => Error::new_spanned(lit_str, "no contract in the output").to_compile_error(),
+        },
+        Err(err) => diagnostics::compiler_error(err, &opts, &spans),
+    }
+    .into()
+}
+
+/// Compiles another EVM language with a registered `CompilerBackend`, given inline or read from
+/// `path = "contracts/Vault.fe"` relative to the crate root, and returns a `ContractInstance`.
+///
+/// The built-in backends are `yul` (solc), `fe` and `etk` (`eas`). The others are the commands
+/// declared in the `rustry.toml` of the crate, see `CommandBackend` for their output. Being
+/// expanded by rustc, the macro cannot see backends that the crate would add at runtime.
+///
+/// ```toml
+/// [backends.mydsl]
+/// command = ["mydslc", "{input}", "--out-dir", "{out}"]
+/// extensions = ["mydsl"]
+/// ```
+///
+/// A language gets its own macro by forwarding to this one:
+///
+/// ```text
+/// macro_rules! mydsl {
+///     ($($args:tt)*) => { rustry_macros::compile!(backend = "mydsl", $($args)*) };
+/// }
+///
+/// let vault = mydsl!(path = "contracts/Vault.mydsl").deploy(&mut provider);
+/// ```
+///
+/// The sole or last contract of the output is bound, unless another one is selected with
+/// `contract = "Vault"`. A contract without ABI is given one with `abi("function ..", ..)`, as
+/// for `yul!`. The compilation settings and `version` are passed to the backend, and its
+/// diagnostics reported as for Solidity.
+#[proc_macro]
+pub fn compile(input: TokenStream) -> TokenStream {
+    let opts = parse_macro_input!(input as MacroOptions);
+    match &opts.backend {
+        Some(name) => backend_contract(&opts, name, options::COMPILE_OPTIONS),
+        None => {
+            Error::new(Span::call_site().into(), "missing `backend = \"..\"`").to_compile_error()
+        }
+    }
+    .into()
+}
+
+/// Compiles Yul objects through solc, given inline or read from `path = "yul/Answer.yul"`
+/// relative to the crate root, and returns a `ContractInstance`. The sole or last object is
+/// bound, unless another one is selected with `contract = "Answer"`.
+///
+/// Yul has no ABI, which is given in Solidity's human-readable form to bind the methods:
+/// `abi("function get() view returns (uint256)", "function set(uint256 value)")`. The options
+/// are otherwise those of `solidity!`: `optimizer(..)`, `evm_version = ".."` and
+/// `version = "0.8.20"`.
+#[proc_macro]
+pub fn yul(input: TokenStream) -> TokenStream {
+    let opts = parse_macro_input!(input as MacroOptions);
+    let name = LitStr::new("yul", Span::call_site().into());
+    backend_contract(&opts, &name, options::YUL_OPTIONS).into()
+}
+
+/// The entries of the `abi(..)` option
+fn human_readable_abi(opts: &MacroOptions) -> syn::Result<Vec<AbiEntry>> {
+    opts.abi
+        .iter()
+        .map(|entry| {
+            entry
+                .value()
+                .parse::<AbiEntry>()
+                .map_err(|err| Error::new_spanned(entry, err))
+        })
+        .collect()
+}
+
+/// A `ContractInstance` of sources compiled by the backend `name`
+fn backend_contract(
+    opts: &MacroOptions,
+    name: &LitStr,
+    supported: &[&str],
+) -> proc_macro2::TokenStream {
+    let root = std::env::var("CARGO_MANIFEST_DIR").unwrap_or_else(|_| String::from("."));
+    let checked = opts
+        .allow(supported)
+        .and_then(|_| human_readable_abi(opts))
+        .and_then(|abi| {
+            let config = backend::load_config(Path::new(&root))
+                .map_err(|err| Error::new_spanned(name, err))?;
+            match config.get(&name.value()) {
+                Some(backend) => Ok((abi, backend, config)),
+                None => Err(Error::new_spanned(
+                    name,
+                    CompilerError::UnknownBackend(name.value()),
+                )),
+            }
+        });
+    let (abi, backend, config) = match checked {
+        Ok(checked) => checked,
+        Err(err) => return err.to_compile_error(),
+    };
+    let kind = CompilerKinds::Backend(name.value());
+    let inline_name = match backend.extensions().first() {
+        Some(ext) => format!("source_code.{ext}"),
+        None => String::from("source_code"),
+    };
+    let checked = opts
+        .source()
+        .and_then(|lit_str| Ok((lit_str, opts.sources(&kind, &inline_name)?)));
+    let (lit_str, files) = match checked {
+        Ok(checked) => checked,
+        Err(err) => return err.to_compile_error(),
+    };
+    let tracking =
+        options::track_files(&[files.paths.clone(), config.path.into_iter().collect()].concat());
+    let spans = diagnostics::SourceSpans::new(lit_str, &files.entry, opts);
+
+    let compiler = Compiler {
+        kind,
+        sources: files.sources.clone(),
+        entry: files.entry.clone(),
+        remappings: vec![],
+        settings: opts.settings.clone(),
+        version: opts.version.as_ref().map(LitStr::value),
+        huff: HuffSettings::default(),
+    };
+
+    match compiler.run_backend(&*backend) {
         Ok(out) => {
-            let huffc_out = HuffcOut::try_from(out).unwrap();
-            let bytecode = huffc_out.bytecode;
+            let out = match BackendOutput::try_from(out) {
+                Ok(out) => out,
+                Err(err) => return Error::new_spanned(lit_str, err).to_compile_error(),
+            };
+            diagnostics::warn(&out.errors, &spans);
+            let available: Vec<_> = out.artifacts.iter().map(|a| a.name.as_str()).collect();
+            let contract = match &opts.contract {
+                Some(name) => out
+                    .artifacts
+                    .iter()
+                    .find(|a| a.name == name.value())
+                    .ok_or_else(|| {
+                        Error::new_spanned(
+                            name,
+                            format!(
+                                "no contract `{}`, available contracts: {}",
+                                name.value(),
+                                available.join(", ")
+                            ),
+                        )
+                    }),
+                None => out
+                    .artifacts
+                    .last()
+                    .ok_or_else(|| Error::new_spanned(lit_str, "no contract in the output")),
+            };
+            match contract {
+                Ok(contract) if abi.is_empty() => {
+                    artifact_instance(contract, &[], opts, lit_str, tracking)
+                }
+                Ok(contract) => {
+                    let contract = Artifact {
+                        method_identifiers: Artifact::method_identifiers(&abi),
+                        abi,
+                        ..contract.clone()
+                    };
+                    artifact_instance(&contract, &[], opts, lit_str, tracking)
+                }
+                Err(err) => err.to_compile_error(),
+            }
+        }
+        Err(err) => diagnostics::compiler_error(err, opts, &spans),
+    }
+}
+
+/// Assembles EVM code in Rust, without any compiler, and returns a `ContractInstance`. The
+/// literal is either `0x`-prefixed hex bytecode or a listing of mnemonics, immediates and labels
+/// (see `rustry_test::utils::asm`).
+///
+/// The code is the runtime code of the contract, deployed by a minimal constructor, unless
+/// `creation = true` makes it the creation code. As for `yul!`, the methods are bound from a
+/// human-readable ABI:
+///
+/// ```no_run
+/// use revm::primitives::U256;
+/// use rustry_macros::evm;
+/// use rustry_test::prelude::*;
+///
+/// let mut provider = Provider::default();
+/// let echo = evm!(
+///     abi("function echo(uint256) returns (uint256)"),
+///     "PUSH1 0x04 CALLDATALOAD PUSH1 0x00 MSTORE PUSH1 0x20 PUSH1 0x00 RETURN"
+/// )
+/// .deploy(&mut provider);
+/// assert_eq!(echo.methods.echo(&mut provider, U256::from(7)), U256::from(7));
+/// ```
+#[proc_macro]
+pub fn evm(input: TokenStream) -> TokenStream {
+    let opts = parse_macro_input!(input as MacroOptions);
+    let checked = opts
+        .allow(options::EVM_OPTIONS)
+        .and_then(|_| human_readable_abi(&opts))
+        .and_then(|abi| {
+            let lit_str = opts.source()?;
+            let code =
+                asm::assemble(&lit_str.value()).map_err(|err| Error::new_spanned(lit_str, err))?;
+            Ok((abi, lit_str, code))
+        });
+    let (abi, lit_str, code) = match checked {
+        Ok(checked) => checked,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    let (bytecode, deployed_bytecode) = if opts.creation {
+        (code, vec![])
+    } else {
+        match asm::deployer(&code) {
+            Ok(bytecode) => (bytecode, code),
+            Err(err) => return Error::new_spanned(lit_str, err).to_compile_error().into(),
+        }
+    };
+    let artifact = Artifact {
+        name: String::from("Evm"),
+        file: String::new(),
+        method_identifiers: Artifact::method_identifiers(&abi),
+        abi,
+        bytecode: hex::encode(bytecode),
+        deployed_bytecode: hex::encode(deployed_bytecode),
+        source_map: None,
+        storage_layout: None,
+        link_references: Default::default(),
+        deployed_link_references: Default::default(),
+    };
+    artifact_instance(
+        &artifact,
+        &[],
+        &opts,
+        lit_str,
+        proc_macro2::TokenStream::new(),
+    )
+    .into()
+}
+
+/// Binds a contract from the artifacts of the project, without compiling it: `artifact!("Vault")`,
+/// or `artifact!("Vault.sol:Vault")` when several files define a `Vault`. The artifact is read
+/// from the `out` directory of the `foundry.toml` of the crate, in the format of Foundry or
+/// Hardhat, so that it can be written by `forge build` or by a build script:
+///
+/// ```text
+/// // build.rs
+/// fn main() {
+///     let root = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+///     rustry_test::project::Project::new(root).unwrap().compile().unwrap();
+///     println!("cargo:rerun-if-changed=src");
+/// }
+/// ```
+#[proc_macro]
+pub fn artifact(input: TokenStream) -> TokenStream {
+    let opts = parse_macro_input!(input as MacroOptions);
+    let checked = opts
+        .allow(options::ARTIFACT_OPTIONS)
+        .and_then(|_| opts.source());
+    let lit_str = match checked {
+        Ok(lit_str) => lit_str,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    let root = std::env::var("CARGO_MANIFEST_DIR").unwrap_or_else(|_| String::from("."));
+    let found = Project::new(root).and_then(|project| {
+        let (artifact, path) = project.artifact(&lit_str.value())?;
+        let libraries = project.libraries(&artifact)?;
+        Ok((artifact, path, libraries))
+    });
+    match found {
+        Ok((artifact, path, libraries)) => {
+            let mut paths = vec![path];
+            paths.extend(libraries.iter().map(|(_, path)| path.clone()));
+            let libraries: Vec<_> = libraries.into_iter().map(|(library, _)| library).collect();
+            let tracking = options::track_files(&paths);
+            artifact_instance(&artifact, &libraries, &opts, lit_str, tracking).into()
+        }
+        Err(err) => Error::new_spanned(lit_str, err).to_compile_error().into(),
+    }
+}
+
+/// Generates the bindings of a contract from its ABI, as JSON or in a Foundry or Hardhat
+/// artifact, relative to the crate root: `abigen!(Token, "abis/Token.json")`. Besides the
+/// `Token`, `TokenMethods` and `DeployedToken` types of `solidity!(module = ..)`, each event is
+/// bound as a struct decoding the logs of the provider, e.g. `TransferEvent`, and each custom
+/// error as one decoding revert data, e.g. `InsufficientBalanceError`.
+///
+/// The creation code comes from the artifact, or from a hex file with
+/// `bytecode = "abis/Token.bin"`. Without one, `DeployedToken::at(address)` binds a contract
+/// deployed by other means.
+///
+/// ```text
+/// abigen!(Token, "abis/Token.json", aliases("transfer(address,uint256)" = send));
+///
+/// let token = Token::compiled().with_args(supply).deploy(&mut provider);
+/// token.methods.send(&mut provider, bob, amount);
+/// let transfer = TransferEvent::decode(&provider.logs[0]).unwrap();
+/// ```
+#[proc_macro]
+pub fn abigen(input: TokenStream) -> TokenStream {
+    let options::Abigen { name, opts } = parse_macro_input!(input as options::Abigen);
+    let checked = opts
+        .allow(options::ABIGEN_OPTIONS)
+        .and_then(|_| opts.source());
+    let lit_str = match checked {
+        Ok(lit_str) => lit_str,
+        Err(err) => return err.to_compile_error().into(),
+    };
 
-            make_contract_instance(iter::empty::<proc_macro2::TokenStream>(), &bytecode)
+    let root =
+        PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap_or_else(|_| String::from(".")));
+    let path = root.join(lit_str.value());
+    let read = |path: &Path, lit: &LitStr| {
+        std::fs::read_to_string(path)
+            .map_err(|err| Error::new_spanned(lit, format!("{}: {err}", path.display())))
+    };
+    let mut artifact = match read(&path, lit_str).and_then(|content| {
+        Artifact::from_json(&content, &path)
+            .map_err(|err| Error::new_spanned(lit_str, format!("{}: {err}", path.display())))
+    }) {
+        Ok(artifact) => artifact,
+        Err(err) => return err.to_compile_error().into(),
+    };
+    let mut tracked = vec![path];
+    if let Some(bytecode) = &opts.bytecode {
+        let path = root.join(bytecode.value());
+        let code = match read(&path, bytecode) {
+            Ok(code) => code.trim().trim_start_matches("0x").to_string(),
+            Err(err) => return err.to_compile_error().into(),
+        };
+        if hex::decode(&code).is_err() {
+            return Error::new_spanned(bytecode, "expected hex creation code")
+                .to_compile_error()
+                .into();
         }
-        Err(err) => panic!("{:?}", err),
+        artifact.bytecode = code;
+        tracked.push(path);
+    }
+
+    let mut structs = Structs::new(&artifact.abi);
+    let mut logs = Logs::new();
+    let bindings = bindings::check_aliases(&opts.aliases, artifact.abi.iter())
+        .and_then(|_| abi_bindings(&artifact.abi, &opts, lit_str, &mut structs, &mut logs));
+    let bindings = match bindings {
+        Ok(bindings) => bindings,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    let tracking = options::track_files(&tracked);
+    let type_defs = type_defs(&structs, &logs);
+    let items = contract_items(
+        &ContractNames::of(&name.to_string()),
+        bindings,
+        &artifact,
+        &[],
+    );
+    quote! {
+        #tracking
+
+        #type_defs
+
+        #items
     }
     .into()
 }
 
+/// Reports the canonical Solidity type of a struct (as a tuple) or a fieldless enum (as `uint8`),
+/// by implementing `rustry_test::utils::abi::SolType`.
+///
+/// # Examples
+///
+/// ```
+/// use revm::primitives::{Address, U256};
+/// use rustry_macros::{AbiDecode, AbiEncode, AbiType};
+/// use rustry_test::utils::abi::{AbiDecode, AbiEncode, SolType};
+///
+/// #[derive(AbiType, AbiEncode, AbiDecode, Debug, PartialEq)]
+/// enum Status {
+///     Pending,
+///     Done,
+/// }
+///
+/// #[derive(AbiType, AbiEncode, AbiDecode, Debug, PartialEq)]
+/// struct Order {
+///     owner: Address,
+///     amounts: Vec<U256>,
+///     status: Status,
+/// }
+///
+/// assert_eq!(Order::sol_type(), "(address,uint256[],uint8)");
+///
+/// let order = Order {
+///     owner: Address::ZERO,
+///     amounts: vec![U256::from(1)],
+///     status: Status::Done,
+/// };
+/// assert_eq!(Order::abi_decode(&order.abi_encode()), Ok(order));
+/// ```
+#[proc_macro_derive(AbiType)]
+pub fn derive_abi_type(input: TokenStream) -> TokenStream {
+    abi::derive_sol_type(parse_macro_input!(input as DeriveInput)).into()
+}
+
+/// Encodes a struct as an ABI tuple of its fields, or a fieldless enum as the `uint8` index of
+/// its variant. Requires `#[derive(AbiType)]`.
+#[proc_macro_derive(AbiEncode)]
+pub fn derive_abi_encode(input: TokenStream) -> TokenStream {
+    abi::derive_abi_encode(parse_macro_input!(input as DeriveInput)).into()
+}
+
+/// Decodes what `#[derive(AbiEncode)]` encodes. Requires `#[derive(AbiType)]`.
+#[proc_macro_derive(AbiDecode)]
+pub fn derive_abi_decode(input: TokenStream) -> TokenStream {
+    abi::derive_abi_decode(parse_macro_input!(input as DeriveInput)).into()
+}
+
 fn default_set_up() -> proc_macro2::TokenStream {
     quote! {
         let provider = 0;
     }
 }
 
-fn make_contract_instance(
-    impl_fns: impl Iterator<Item = proc_macro2::TokenStream>,
-    bytecode: &String,
-) -> proc_macro2::TokenStream {
-    quote! {
+/// Names of the generated types
+struct ContractNames {
+    instance: proc_macro2::Ident,
+    methods: proc_macro2::Ident,
+    deployed: proc_macro2::Ident,
+}
+
+impl Default for ContractNames {
+    fn default() -> Self {
+        Self {
+            instance: format_ident!("ContractInstance"),
+            methods: format_ident!("ContractMethods"),
+            deployed: format_ident!("DeployedContract"),
+        }
+    }
+}
+
+impl ContractNames {
+    /// `Vault`, `VaultMethods` and `DeployedVault`
+    fn of(contract: &str) -> Self {
+        Self {
+            instance: bindings::ident(contract),
+            methods: format_ident!("{}Methods", contract),
+            deployed: format_ident!("Deployed{}", contract),
+        }
+    }
+}
+
+/// The creation code of a contract, zeroed at the addresses of the libraries it calls, and the
+/// `Linker` writing them, with the code of the libraries found in `libraries`. The others are
+/// named in a note, to be linked to deployed ones.
+fn creation_code(
+    artifact: &Artifact,
+    libraries: &[Artifact],
+) -> Result<(String, proc_macro2::TokenStream), String> {
+    let references = |artifact: &Artifact| {
+        let references = artifact
+            .link_offsets()
+            .into_iter()
+            .map(|(library, offsets)| quote! { (String::from(#library), vec![#(#offsets),*]) });
+        quote! { vec![#(#references),*] }
+    };
+    let unlinked = |artifact: &Artifact| {
+        let code = artifact.unlinked_bytecode();
+        match hex::decode(&code) {
+            Ok(_) => Ok(code),
+            Err(_) => Err(format!(
+                "the creation code of {} holds placeholders without link references",
+                artifact.name
+            )),
+        }
+    };
+    let code = unlinked(artifact)?;
+    if artifact.link_references.is_empty() {
+        return Ok((
+            code,
+            quote! { rustry_test::common::link::Linker::default() },
+        ));
+    }
+
+    // the libraries called by the contract, and by these libraries
+    let mut needed: Vec<_> = artifact
+        .link_offsets()
+        .into_iter()
+        .map(|(l, _)| l)
+        .collect();
+    let mut linked = BTreeMap::new();
+    let mut missing = vec![];
+    while let Some(name) = needed.pop() {
+        if linked.contains_key(&name) || missing.contains(&name) {
+            continue;
+        }
+        match libraries
+            .iter()
+            .find(|library| format!("{}:{}", library.file, library.name) == name)
         {
-            #[derive(Default, Debug)]
-            struct ContractMethods {
-                pub address: revm::primitives::Address,
-            };
+            Some(library) => {
+                needed.extend(library.link_offsets().into_iter().map(|(l, _)| l));
+                linked.insert(name, library);
+            }
+            None => missing.push(name),
+        }
+    }
+    if !missing.is_empty() {
+        missing.sort();
+        Diagnostic::spanned(
+            Span::call_site(),
+            Level::Note,
+            format!(
+                "{} calls libraries that are not compiled with it, link deployed ones with \
+                 `.link(name, address)` before deploying: {}",
+                artifact.name,
+                missing.join(", ")
+            ),
+        )
+        .emit();
+    }
+    let libraries = linked
+        .into_iter()
+        .map(|(name, library)| {
+            let code = unlinked(library)?;
+            let references = references(library);
+            Ok(quote! {
+                (
+                    String::from(#name),
+                    rustry_test::common::link::Library {
+                        code: hex::decode(#code).unwrap(),
+                        references: #references,
+                    },
+                )
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    let references = references(artifact);
 
-            impl ContractMethods {
-                fn new(address: revm::primitives::Address) -> Self {
-                    Self {
-                        address
-                    }
-                }
+    Ok((
+        code,
+        quote! {
+            rustry_test::common::link::Linker::new(
+                #references,
+                [#(#libraries),*].into_iter().collect(),
+            )
+        },
+    ))
+}
 
-                #(
-                    #impl_fns
-                 )*
+fn contract_items(
+    names: &ContractNames,
+    bindings: AbiBindings,
+    artifact: &Artifact,
+    libraries: &[Artifact],
+) -> proc_macro2::TokenStream {
+    let (bytecode, linker) = match creation_code(artifact, libraries) {
+        Ok(code) => code,
+        Err(err) => return quote! { compile_error!(#err); },
+    };
+    let ContractNames {
+        instance,
+        methods,
+        deployed,
+    } = names;
+    let storage_layout = match &artifact.storage_layout {
+        Some(layout) => {
+            let json = layout.to_json();
+            quote! {
+                fn storage_layout(&self) -> Option<(revm::primitives::Address, &rustry_test::compilers::solidity::solc::StorageLayout)> {
+                    // parsed on the first access only
+                    static LAYOUT: std::sync::OnceLock<rustry_test::compilers::solidity::solc::StorageLayout> = std::sync::OnceLock::new();
+                    let layout = LAYOUT.get_or_init(|| {
+                        rustry_test::compilers::solidity::solc::StorageLayout::from_json(#json)
+                            .expect("the storage layout serialized by the macro parses")
+                    });
+                    Some((self.address, layout))
+                }
             }
+        }
+        None => quote! {},
+    };
+    let AbiBindings {
+        methods: impl_fns,
+        constructor,
+    } = bindings;
+
+    quote! {
+        #[derive(Default, Debug)]
+        pub struct #methods {
+            pub address: revm::primitives::Address,
+        }
 
-            #[derive(Default, Debug)]
-            struct ContractInstance {
-                pub code: revm::primitives::Bytes,
+        impl #methods {
+            pub fn new(address: revm::primitives::Address) -> Self {
+                Self {
+                    address
+                }
             }
 
-            impl ContractInstance {
-                fn new(code: revm::primitives::Bytes) -> Self {
-                    Self {
-                        code,
-                    }
+            #(
+                #impl_fns
+             )*
+        }
+
+        #[derive(Default, Debug)]
+        pub struct #instance {
+            pub code: revm::primitives::Bytes,
+            /// Writes the addresses of the libraries in the code when deploying it
+            pub linker: rustry_test::common::link::Linker,
+        }
+
+        impl #instance {
+            pub fn new(code: revm::primitives::Bytes) -> Self {
+                Self {
+                    code,
+                    linker: Default::default(),
                 }
+            }
 
-                fn deploy<'a>(self, provider: &'a mut rustry_test::provider::Provider) -> DeployedContract {
-                    let address = provider.deploy(self.code).unwrap();
-                    DeployedContract {
-                        address,
-                        methods: ContractMethods::new(address)
-                    }
+            /// The compiled creation code, deploying the libraries it calls
+            ///
+            /// The libraries that were not compiled with the contract, named in a note when
+            /// expanding the macro, must be linked with `link(..)`, deploying panics otherwise.
+            pub fn compiled() -> Self {
+                let as_bytes = hex::decode(#bytecode).unwrap();
+                Self {
+                    code: as_bytes.into(),
+                    linker: #linker,
                 }
             }
 
-            struct DeployedContract {
-                pub address: revm::primitives::Address,
-                pub methods: ContractMethods
+            /// Links the library `name`, as `Math` or `src/Math.sol:Math`, to the one deployed at
+            /// `address` instead of deploying it
+            pub fn link(mut self, name: &str, address: revm::primitives::Address) -> Self {
+                self.linker.link(name, address);
+                self
             }
 
-            impl rustry_test::common::contract::Contract for DeployedContract {
-                fn call(&mut self, provider: &mut rustry_test::provider::Provider, data: Vec<u8>) -> rustry_test::provider::db::ExecRes {
-                    provider.call(self.address, data.into())
-                }
+            #constructor
 
-                fn staticcall(&mut self, provider: &mut rustry_test::provider::Provider, data: Vec<u8>) -> rustry_test::provider::db::ExecRes {
-                    provider.staticcall(self.address, data.into())
-                }
+            pub fn deploy<'a>(mut self, provider: &'a mut rustry_test::provider::Provider) -> #deployed {
+                let code = self.linker.link_code(provider, self.code.to_vec());
+                let address = rustry_test::prelude::Frontend::deploy(provider, code.into()).unwrap();
+                #deployed::at(address)
+            }
+        }
+
+        pub struct #deployed {
+            pub address: revm::primitives::Address,
+            pub methods: #methods
+        }
 
-                fn send(&mut self, provider: &mut rustry_test::provider::Provider, value: revm::primitives::alloy_primitives::Uint<256, 4>) -> rustry_test::provider::db::ExecRes {
-                    provider.send(self.address, value)
+        impl #deployed {
+            /// Binds a contract already deployed at `address`
+            pub fn at(address: revm::primitives::Address) -> Self {
+                Self {
+                    address,
+                    methods: #methods::new(address)
                 }
             }
+        }
+
+        impl rustry_test::common::contract::Contract for #deployed {
+            #storage_layout
+
+            fn call(&mut self, provider: &mut rustry_test::provider::Provider, data: Vec<u8>) -> rustry_test::provider::db::ExecRes {
+                rustry_test::prelude::Frontend::call(provider, self.address, data.into())
+            }
 
-            let as_bytes = hex::decode(#bytecode).unwrap();
+            fn staticcall(&mut self, provider: &mut rustry_test::provider::Provider, data: Vec<u8>) -> rustry_test::provider::db::ExecRes {
+                rustry_test::prelude::Frontend::staticcall(provider, self.address, data.into())
+            }
+
+            fn send(&mut self, provider: &mut rustry_test::provider::Provider, value: revm::primitives::alloy_primitives::Uint<256, 4>) -> rustry_test::provider::db::ExecRes {
+                rustry_test::prelude::Frontend::send(provider, self.address, value)
+            }
+        }
+    }
+}
+
+/// The `ContractInstance` of a contract, or the module of its types when bound in `module`
+fn make_contract_instance(
+    bindings: AbiBindings,
+    type_defs: proc_macro2::TokenStream,
+    artifact: &Artifact,
+    libraries: &[Artifact],
+    module: Option<&proc_macro2::Ident>,
+) -> proc_macro2::TokenStream {
+    let items = contract_items(&ContractNames::default(), bindings, artifact, libraries);
+
+    if let Some(module) = module {
+        return quote! {
+            #[allow(clippy::upper_case_acronyms)]
+            pub mod #module {
+                #type_defs
+
+                #items
+            }
+        };
+    }
+    quote! {
+        {
+            #type_defs
 
-            let _bytecode: revm::primitives::Bytes = as_bytes.into();
+            #items
 
-            ContractInstance::new(_bytecode)
+            ContractInstance::compiled()
         }
     }
 }
@@ -0,0 +1,477 @@
+use proc_macro2::{Ident, Span, TokenStream};
+use quote::quote;
+use revm::primitives::Address;
+use rustry_test::compilers::{
+    builder::CompilerKinds,
+    huff::huffc::HuffSettings,
+    solidity::solc::{DebugSettings, MetadataSettings, Optimizer, SolcSettings},
+    sources::SourceFiles,
+};
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+use syn::{
+    parenthesized,
+    parse::{Parse, ParseStream},
+    punctuated::Punctuated,
+    Error, Lit, LitBool, LitStr, Token,
+};
+
+/// `"transfer(address,uint256)" = send`
+pub struct Alias {
+    pub signature: LitStr,
+    pub name: Ident,
+}
+
+impl Parse for Alias {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let signature = input.parse()?;
+        input.parse::<Token![=]>()?;
+        let name = input.parse()?;
+        Ok(Alias { signature, name })
+    }
+}
+
+/// `runs = 1000`, `yul = false` or `OWNER = "0x.."`
+struct Setting {
+    key: Ident,
+    value: Lit,
+}
+
+impl Parse for Setting {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let key = input.parse()?;
+        input.parse::<Token![=]>()?;
+        let value = input.parse()?;
+        Ok(Setting { key, value })
+    }
+}
+
+/// `"src/Math.sol:Math" = "0x.."`
+struct Library {
+    target: LitStr,
+    address: LitStr,
+}
+
+impl Parse for Library {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let target = input.parse()?;
+        input.parse::<Token![=]>()?;
+        let address = input.parse()?;
+        Ok(Library { target, address })
+    }
+}
+
+/// Arguments of the contract macros: comma-separated options and the source code literal
+///
+/// ```text
+/// solidity!(contract = "Token", aliases("transfer(address,uint256)" = send), "contract Token { ... }")
+/// solidity!(module = contracts, "contract Token { ... } contract Vault { ... }")
+/// solidity!(path = "src/Vault.sol", contract = "Vault")
+/// solidity!(path = "src/Proxy.sol", contract = "Proxy", implementation = "Vault")
+/// solidity!(optimizer(runs = 1000, yul = false), via_ir = true, evm_version = "paris", "..")
+/// solidity!(bytecode_hash = "none", append_cbor = false, revert_strings = "strip", "..")
+/// solidity!(libraries("src/Math.sol:Math" = "0x.."), "..")
+/// vyper!(module = crowdfund, path = "contracts/Crowdfund.vy")
+/// huff!(constructor_args("0x..", "42"), constants(FEE = "0x64"), alt_main = "MAIN_V2", "..")
+/// compile!(backend = "fe", path = "contracts/Vault.fe", contract = "Vault")
+/// yul!(abi("function get() view returns (uint256)"), "object \"Store\" { .. }")
+/// evm!(creation = true, "PUSH1 0x2a PUSH0 MSTORE PUSH1 0x20 PUSH0 RETURN")
+/// artifact!(aliases("transfer(address,uint256)" = send), "Vault.sol:Vault")
+/// abigen!(Token, "abis/Token.json", bytecode = "abis/Token.bin")
+/// ```
+#[derive(Default)]
+pub struct MacroOptions {
+    pub source: Option<LitStr>,
+    /// A source file, relative to the crate root
+    pub path: Option<LitStr>,
+    pub aliases: Vec<Alias>,
+    /// The contract to bind, defaults to the sole or last-defined one
+    pub contract: Option<LitStr>,
+    /// Binds the contract in a module of that name, or every contract for solc
+    pub module: Option<Ident>,
+    /// The contract a bound proxy delegates its calls to, whose functions are bound along
+    pub implementation: Option<LitStr>,
+    pub settings: SolcSettings,
+    /// Requirement on the compiler version, instead of the pragmas
+    pub version: Option<LitStr>,
+    pub huff: HuffSettings,
+    /// Name of the `CompilerBackend` compiling the sources
+    pub backend: Option<LitStr>,
+    /// Human-readable ABI of a contract compiled without one
+    pub abi: Vec<LitStr>,
+    /// The assembled code is the creation code rather than the runtime code
+    pub creation: bool,
+    /// A file of hex creation code, for a contract bound from its ABI
+    pub bytecode: Option<LitStr>,
+    /// The options given, in order
+    pub keys: Vec<Ident>,
+}
+
+const OPTIONS: &[&str] = &[
+    "abi",
+    "aliases",
+    "alt_constructor",
+    "alt_main",
+    "append_cbor",
+    "backend",
+    "bytecode",
+    "bytecode_hash",
+    "constants",
+    "constructor_args",
+    "contract",
+    "creation",
+    "evm_version",
+    "implementation",
+    "libraries",
+    "module",
+    "optimizer",
+    "path",
+    "revert_strings",
+    "version",
+    "via_ir",
+];
+
+/// Options of `solidity!`
+pub const SOLIDITY_OPTIONS: &[&str] = &[
+    "aliases",
+    "append_cbor",
+    "bytecode_hash",
+    "contract",
+    "evm_version",
+    "implementation",
+    "libraries",
+    "module",
+    "optimizer",
+    "path",
+    "revert_strings",
+    "version",
+    "via_ir",
+];
+
+/// Options of `vyper!`
+pub const VYPER_OPTIONS: &[&str] = &["aliases", "evm_version", "module", "path", "version"];
+
+/// Options of `huff!`
+pub const HUFF_OPTIONS: &[&str] = &[
+    "aliases",
+    "alt_constructor",
+    "alt_main",
+    "constants",
+    "constructor_args",
+    "evm_version",
+    "module",
+    "path",
+];
+
+/// Options of `compile!`
+pub const COMPILE_OPTIONS: &[&str] = &[
+    "abi",
+    "aliases",
+    "append_cbor",
+    "backend",
+    "bytecode_hash",
+    "contract",
+    "evm_version",
+    "libraries",
+    "module",
+    "optimizer",
+    "path",
+    "revert_strings",
+    "version",
+    "via_ir",
+];
+
+/// Options of `yul!`, those of `compile!` but the backend
+pub const YUL_OPTIONS: &[&str] = &[
+    "abi",
+    "aliases",
+    "append_cbor",
+    "bytecode_hash",
+    "contract",
+    "evm_version",
+    "libraries",
+    "module",
+    "optimizer",
+    "path",
+    "revert_strings",
+    "version",
+    "via_ir",
+];
+
+/// Options of `evm!`
+pub const EVM_OPTIONS: &[&str] = &["abi", "aliases", "creation", "module"];
+
+/// Options of `artifact!`
+pub const ARTIFACT_OPTIONS: &[&str] = &["aliases", "module"];
+
+/// Options of `abigen!`
+pub const ABIGEN_OPTIONS: &[&str] = &["aliases", "bytecode"];
+
+fn value<T: Parse>(input: ParseStream) -> syn::Result<T> {
+    input.parse::<Token![=]>()?;
+    input.parse()
+}
+
+/// Parses the value of a string option
+fn value_from_str<T: FromStr<Err = String>>(input: ParseStream) -> syn::Result<T> {
+    let lit: LitStr = value(input)?;
+    lit.value()
+        .parse()
+        .map_err(|err| Error::new_spanned(lit, err))
+}
+
+/// `optimizer` with the default runs, or `optimizer(runs = 1000, yul = false)`
+fn optimizer(input: ParseStream) -> syn::Result<Optimizer> {
+    let mut optimizer = Optimizer::default();
+    if !input.peek(syn::token::Paren) {
+        return Ok(optimizer);
+    }
+
+    let content;
+    parenthesized!(content in input);
+    for Setting { key, value } in Punctuated::<Setting, Token![,]>::parse_terminated(&content)? {
+        match (key.to_string().as_str(), &value) {
+            ("runs", Lit::Int(runs)) => optimizer.runs = runs.base10_parse()?,
+            ("enabled", Lit::Bool(enabled)) => optimizer.enabled = enabled.value,
+            (step, Lit::Bool(enabled)) => {
+                let details = optimizer.details.get_or_insert_with(Default::default);
+                let switch = match step {
+                    "peephole" => &mut details.peephole,
+                    "inliner" => &mut details.inliner,
+                    "jumpdest_remover" => &mut details.jumpdest_remover,
+                    "order_literals" => &mut details.order_literals,
+                    "deduplicate" => &mut details.deduplicate,
+                    "cse" => &mut details.cse,
+                    "constant_optimizer" => &mut details.constant_optimizer,
+                    "yul" => &mut details.yul,
+                    _ => {
+                        return Err(Error::new_spanned(
+                            &key,
+                            format!("unknown optimizer setting `{key}`"),
+                        ))
+                    }
+                };
+                *switch = Some(enabled.value);
+            }
+            _ => {
+                return Err(Error::new_spanned(
+                    value,
+                    "expected `runs = <integer>` or a boolean optimizer step",
+                ))
+            }
+        }
+    }
+    Ok(optimizer)
+}
+
+impl Parse for MacroOptions {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut opts = MacroOptions::default();
+        while !input.is_empty() {
+            if input.peek(LitStr) {
+                let source: LitStr = input.parse()?;
+                if opts.source.is_some() {
+                    return Err(Error::new_spanned(source, "source code given twice"));
+                }
+                opts.source = Some(source);
+            } else {
+                let key: Ident = input.parse()?;
+                opts.keys.push(key.clone());
+                match key.to_string().as_str() {
+                    "aliases" => {
+                        let content;
+                        parenthesized!(content in input);
+                        let aliases = Punctuated::<Alias, Token![,]>::parse_terminated(&content)?;
+                        opts.aliases.extend(aliases);
+                    }
+                    "abi" => {
+                        let content;
+                        parenthesized!(content in input);
+                        let abi = Punctuated::<LitStr, Token![,]>::parse_terminated(&content)?;
+                        opts.abi.extend(abi);
+                    }
+                    "contract" => opts.contract = Some(value(input)?),
+                    "creation" => opts.creation = value::<LitBool>(input)?.value,
+                    "module" => opts.module = Some(value(input)?),
+                    "implementation" => opts.implementation = Some(value(input)?),
+                    "path" => opts.path = Some(value(input)?),
+                    "backend" => opts.backend = Some(value(input)?),
+                    "bytecode" => opts.bytecode = Some(value(input)?),
+                    "version" => opts.version = Some(value(input)?),
+                    "optimizer" => opts.settings.optimizer = Some(optimizer(input)?),
+                    "via_ir" => opts.settings.via_ir = Some(value::<LitBool>(input)?.value),
+                    "evm_version" => {
+                        opts.settings.evm_version = Some(value::<LitStr>(input)?.value())
+                    }
+                    "bytecode_hash" => {
+                        let metadata = opts
+                            .settings
+                            .metadata
+                            .get_or_insert_with(MetadataSettings::default);
+                        metadata.bytecode_hash = Some(value_from_str(input)?);
+                    }
+                    "append_cbor" => {
+                        let metadata = opts
+                            .settings
+                            .metadata
+                            .get_or_insert_with(MetadataSettings::default);
+                        metadata.append_cbor = Some(value::<LitBool>(input)?.value);
+                    }
+                    "constructor_args" => {
+                        let content;
+                        parenthesized!(content in input);
+                        let args = Punctuated::<LitStr, Token![,]>::parse_terminated(&content)?;
+                        opts.huff
+                            .constructor_args
+                            .extend(args.iter().map(LitStr::value));
+                    }
+                    "constants" => {
+                        let content;
+                        parenthesized!(content in input);
+                        for Setting { key, value } in
+                            Punctuated::<Setting, Token![,]>::parse_terminated(&content)?
+                        {
+                            let value = match value {
+                                Lit::Str(value) => value.value(),
+                                Lit::Int(value) => value.base10_digits().to_string(),
+                                value => {
+                                    return Err(Error::new_spanned(
+                                        value,
+                                        "expected a string or an integer",
+                                    ))
+                                }
+                            };
+                            opts.huff.constants.insert(key.to_string(), value);
+                        }
+                    }
+                    "alt_main" => opts.huff.alt_main = Some(value::<LitStr>(input)?.value()),
+                    "alt_constructor" => {
+                        opts.huff.alt_constructor = Some(value::<LitStr>(input)?.value())
+                    }
+                    "revert_strings" => {
+                        opts.settings.debug = Some(DebugSettings {
+                            revert_strings: Some(value_from_str(input)?),
+                        })
+                    }
+                    "libraries" => {
+                        let content;
+                        parenthesized!(content in input);
+                        let libraries =
+                            opts.settings.libraries.get_or_insert_with(Default::default);
+                        for lib in Punctuated::<Library, Token![,]>::parse_terminated(&content)? {
+                            let target = lib.target.value();
+                            let Some((file, name)) = target.rsplit_once(':') else {
+                                return Err(Error::new_spanned(
+                                    lib.target,
+                                    "expected the library as `path/to/File.sol:Name`",
+                                ));
+                            };
+                            if Address::from_str(&lib.address.value()).is_err() {
+                                return Err(Error::new_spanned(lib.address, "invalid address"));
+                            }
+                            libraries
+                                .entry(file.to_string())
+                                .or_default()
+                                .insert(name.to_string(), lib.address.value());
+                        }
+                    }
+                    _ => {
+                        return Err(Error::new_spanned(
+                            &key,
+                            format!(
+                                "unknown option `{key}`, expected one of: {}",
+                                OPTIONS.join(", ")
+                            ),
+                        ))
+                    }
+                }
+            }
+
+            if input.is_empty() {
+                break;
+            }
+            input.parse::<Token![,]>()?;
+        }
+
+        if let (Some(_), Some(path)) = (&opts.source, &opts.path) {
+            return Err(Error::new_spanned(
+                path,
+                "give either the source code or a `path`, not both",
+            ));
+        }
+        if let (Some(_), Some(module)) = (&opts.implementation, &opts.module) {
+            return Err(Error::new_spanned(
+                module,
+                "`module` binds every contract and can't be combined with `implementation`",
+            ));
+        }
+
+        Ok(opts)
+    }
+}
+
+/// `Token, "abis/Token.json", ..`: the name of the bound contract before the options
+pub struct Abigen {
+    pub name: Ident,
+    pub opts: MacroOptions,
+}
+
+impl Parse for Abigen {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let name = input.parse()?;
+        input.parse::<Token![,]>()?;
+        Ok(Abigen {
+            name,
+            opts: input.parse()?,
+        })
+    }
+}
+
+impl MacroOptions {
+    /// The source code, or the path of the source file
+    pub fn source(&self) -> syn::Result<&LitStr> {
+        self.source
+            .as_ref()
+            .or(self.path.as_ref())
+            .ok_or_else(|| Error::new(Span::call_site(), "missing the source code or a `path`"))
+    }
+
+    /// Reads the sources to compile and their imports, relative to the crate root.
+    /// Inline source code is named `inline_name`.
+    pub fn sources(&self, kind: &CompilerKinds, inline_name: &str) -> syn::Result<SourceFiles> {
+        let root = std::env::var("CARGO_MANIFEST_DIR").unwrap_or_else(|_| String::from("."));
+        let root = Path::new(&root);
+        let lit_str = self.source()?;
+
+        let files = match &self.path {
+            Some(path) => SourceFiles::read(root, &path.value(), kind),
+            None => SourceFiles::inline(root, inline_name, lit_str.value(), kind),
+        };
+        files.map_err(|err| Error::new_spanned(lit_str, err))
+    }
+
+    /// Rejects the options that a macro doesn't support, given those it does
+    pub fn allow(&self, supported: &[&str]) -> syn::Result<()> {
+        match self
+            .keys
+            .iter()
+            .find(|key| !supported.contains(&&*key.to_string()))
+        {
+            Some(key) => Err(Error::new_spanned(
+                key,
+                format!("`{key}` is not supported by this macro"),
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Makes cargo rebuild the crate when one of the source files changes
+pub fn track_files(paths: &[PathBuf]) -> TokenStream {
+    let paths = paths.iter().map(|path| path.to_string_lossy());
+    quote! {
+        #(const _: &[u8] = include_bytes!(#paths);)*
+    }
+}
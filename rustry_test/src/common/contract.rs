@@ -1,6 +1,15 @@
-use crate::provider::{db::ExecRes, Provider};
+use crate::{
+    compilers::solidity::solc::StorageLayout,
+    provider::{db::ExecRes, Provider},
+};
+use revm::primitives::Address;
 
 pub trait Contract {
+    /// The address of the contract and where its state variables are stored, for the Solidity
+    /// contracts whose layout solc output
+    fn storage_layout(&self) -> Option<(Address, &StorageLayout)> {
+        None
+    }
     fn call(&mut self, provider: &mut Provider, data: Vec<u8>) -> ExecRes;
     fn staticcall(&mut self, provider: &mut Provider, data: Vec<u8>) -> ExecRes;
     fn send(
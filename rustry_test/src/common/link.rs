@@ -0,0 +1,195 @@
+use crate::prelude::{Frontend, Provider};
+use revm::primitives::Address;
+use std::collections::BTreeMap;
+
+/// Offsets of the address of each library in some code, by fully qualified name
+/// (`src/Math.sol:Math`)
+pub type References = Vec<(String, Vec<usize>)>;
+
+/// A library a contract calls, deployed with it unless linked to a deployed one
+#[derive(Debug, Clone, Default)]
+pub struct Library {
+    /// The creation code, zeroed at the addresses of the libraries it calls
+    pub code: Vec<u8>,
+    pub references: References,
+}
+
+/// Writes the addresses of the libraries called by a contract in its creation code
+#[derive(Debug, Clone, Default)]
+pub struct Linker {
+    /// Where the creation code of the contract holds the address of each library
+    pub references: References,
+    /// The libraries compiled with the contract, by fully qualified name
+    pub libraries: BTreeMap<String, Library>,
+    /// The addresses of the linked libraries, by fully qualified name
+    pub addresses: BTreeMap<String, Address>,
+}
+
+impl Linker {
+    pub fn new(references: References, libraries: BTreeMap<String, Library>) -> Self {
+        Self {
+            references,
+            libraries,
+            addresses: BTreeMap::new(),
+        }
+    }
+
+    /// Links the library `name`, as `Math` or `src/Math.sol:Math`, to the one deployed at
+    /// `address`
+    ///
+    /// # Panics
+    ///
+    /// When neither the contract nor its libraries call `name`, or several libraries are named
+    /// so.
+    pub fn link(&mut self, name: &str, address: Address) {
+        let mut known: Vec<&String> = self
+            .references
+            .iter()
+            .chain(self.libraries.values().flat_map(|lib| &lib.references))
+            .map(|(library, _)| library)
+            .collect();
+        known.sort();
+        known.dedup();
+
+        let matching: Vec<_> = known
+            .iter()
+            .filter(|library| {
+                library.as_str() == name
+                    || library.rsplit_once(':').is_some_and(|(_, lib)| lib == name)
+            })
+            .collect();
+        match matching.as_slice() {
+            [library] => {
+                self.addresses.insert(library.to_string(), address);
+            }
+            [] => panic!(
+                "no library `{name}` to link, the contract calls: {}",
+                known
+                    .iter()
+                    .map(|library| library.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            _ => {
+                panic!("several libraries are named `{name}`, link it as `path/to/File.sol:{name}`")
+            }
+        }
+    }
+
+    /// Writes the addresses of the libraries in `code`, first deploying the libraries not linked,
+    /// in dependency order
+    ///
+    /// # Panics
+    ///
+    /// When a library is neither linked nor compiled with the contract, or its deployment fails.
+    pub fn link_code(&mut self, provider: &mut Provider, mut code: Vec<u8>) -> Vec<u8> {
+        for (library, offsets) in self.references.clone() {
+            let address = self.address(provider, &library);
+            write(&mut code, &offsets, address);
+        }
+        code
+    }
+
+    /// The address of `library`, deploying it and the libraries it calls if needed
+    fn address(&mut self, provider: &mut Provider, library: &str) -> Address {
+        if let Some(address) = self.addresses.get(library) {
+            return *address;
+        }
+        let Some(Library {
+            mut code,
+            references,
+        }) = self.libraries.get(library).cloned()
+        else {
+            panic!(
+                "library {library} is not compiled with the contract, link a deployed one with \
+                 `link(\"{library}\", address)`"
+            );
+        };
+        for (dependency, offsets) in references {
+            let address = self.address(provider, &dependency);
+            write(&mut code, &offsets, address);
+        }
+
+        let address = Frontend::deploy(provider, code.into())
+            .unwrap_or_else(|| panic!("deploying library {library} failed"));
+        self.addresses.insert(library.to_string(), address);
+        address
+    }
+}
+
+fn write(code: &mut [u8], offsets: &[usize], address: Address) {
+    for offset in offsets {
+        code[*offset..offset + 20].copy_from_slice(address.as_slice());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::asm;
+
+    /// Creation code of a library returning the address it pushes, at offset 11
+    fn library(address: &str) -> Vec<u8> {
+        asm::assemble(&format!(
+            "PUSH1 27 PUSH1 10 PUSH0 CODECOPY PUSH1 27 PUSH0 RETURN
+             PUSH20 {address} PUSH0 MSTORE PUSH1 0x20 PUSH0 RETURN"
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn deploys_libraries_in_dependency_order() {
+        let zero = format!("0x{}", "00".repeat(20));
+        let libraries = BTreeMap::from([
+            (
+                String::from("src/Math.sol:Math"),
+                Library {
+                    code: library(&zero),
+                    references: vec![(String::from("src/Math.sol:Inner"), vec![11])],
+                },
+            ),
+            (
+                String::from("src/Math.sol:Inner"),
+                Library {
+                    code: library(&zero),
+                    references: vec![],
+                },
+            ),
+        ]);
+        let mut linker = Linker::new(
+            vec![(String::from("src/Math.sol:Math"), vec![1, 30])],
+            libraries,
+        );
+        let mut provider = Provider::default();
+        let code = linker.link_code(&mut provider, vec![0; 60]);
+
+        let math = linker.addresses["src/Math.sol:Math"];
+        let inner = linker.addresses["src/Math.sol:Inner"];
+        assert_eq!(&code[1..21], math.as_slice());
+        assert_eq!(&code[30..50], math.as_slice());
+        let ret = Frontend::staticcall(&mut provider, math, vec![].into());
+        assert_eq!(&ret.get_data()[12..], inner.as_slice());
+    }
+
+    #[test]
+    fn links_deployed_libraries() {
+        let mut linker = Linker::new(
+            vec![(String::from("src/Math.sol:Math"), vec![0])],
+            BTreeMap::new(),
+        );
+        let address = Address::with_last_byte(7);
+        linker.link("Math", address);
+        let code = linker.link_code(&mut Provider::default(), vec![0; 20]);
+        assert_eq!(code, address.as_slice());
+    }
+
+    #[test]
+    #[should_panic(expected = "no library `Oracle` to link, the contract calls: src/Math.sol:Math")]
+    fn rejects_unknown_libraries() {
+        let mut linker = Linker::new(
+            vec![(String::from("src/Math.sol:Math"), vec![0])],
+            BTreeMap::new(),
+        );
+        linker.link("Oracle", Address::ZERO);
+    }
+}
@@ -0,0 +1,629 @@
+use crate::{
+    compilers::{
+        builder::CompilerOutput,
+        huff::huffc::HuffcOut,
+        solidity::{
+            solc::{BytecodeData, CodeRange, SolcOut, StorageLayout},
+            types::canonical_type,
+        },
+        vyper::vyperc::VypercOut,
+    },
+    utils::abi::get_sig,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::Path,
+    str::FromStr,
+};
+
+/// A parameter of the ABI
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TypeType {
+    #[serde(rename = "internalType", skip_serializing_if = "Option::is_none")]
+    pub internal_type: Option<String>,
+    pub name: String,
+    #[serde(rename = "type")]
+    pub type_type: String,
+    // for tuples
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub components: Option<Vec<TypeType>>,
+    /// Whether an event parameter is a topic rather than in the data of the log
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub indexed: bool,
+}
+
+pub trait EntryUtils {
+    fn signature(&self) -> String;
+}
+
+// constructor, receive, fallback, event and error entries lack some of the fields
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AbiEntry {
+    #[serde(default)]
+    pub inputs: Vec<TypeType>,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub outputs: Vec<TypeType>,
+    #[serde(rename = "stateMutability", default)]
+    pub state_mutability: String,
+    #[serde(rename = "type")]
+    pub entry_type: String,
+    /// Whether an event is logged without its signature as first topic
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub anonymous: bool,
+}
+
+impl EntryUtils for AbiEntry {
+    fn signature(&self) -> String {
+        let mut inner_types: String = self
+            .inputs
+            .iter()
+            .map(|tt| format!("{},", canonical_type(tt)))
+            .collect();
+        inner_types.pop();
+        format!("{0}({inner_types})", self.name)
+    }
+}
+
+/// Reads a human-readable ABI entry, as written in Solidity:
+/// `function transfer(address to, uint256 amount) external returns (bool)`,
+/// `event Transfer(address indexed from, address indexed to, uint256)`, `error Unauthorized()`,
+/// `constructor(address owner)`, `receive() external payable` or `fallback()`.
+/// Tuples are written `(uint256 x, uint256 y)[]` or `tuple(uint256,uint256)[]`.
+impl FromStr for AbiEntry {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = HumanReadable {
+            rest: s.trim().trim_end_matches(';'),
+        };
+        let entry_type = parser
+            .word()
+            .ok_or_else(|| format!("invalid ABI entry `{s}`"))?;
+        let name = match entry_type {
+            "function" | "event" | "error" => parser
+                .word()
+                .ok_or_else(|| format!("the {entry_type} of `{s}` has no name"))?,
+            "constructor" | "receive" | "fallback" => "",
+            _ => {
+                return Err(format!(
+                    "`{s}` is not a function, event, error, constructor, receive or fallback"
+                ))
+            }
+        };
+        let inputs = parser.params()?;
+
+        let mut outputs = vec![];
+        let mut anonymous = false;
+        let mut state_mutability = match entry_type {
+            "function" | "constructor" | "fallback" => "nonpayable",
+            "receive" => "payable",
+            _ => "",
+        };
+        while let Some(word) = parser.word() {
+            match word {
+                "view" | "pure" | "payable" | "nonpayable" => state_mutability = word,
+                "returns" => outputs = parser.params()?,
+                "anonymous" => anonymous = true,
+                "external" | "public" | "virtual" | "override" => {}
+                word => return Err(format!("unexpected `{word}` in `{s}`")),
+            }
+        }
+        if !parser.rest.is_empty() {
+            return Err(format!("unexpected `{}` in `{s}`", parser.rest));
+        }
+
+        Ok(AbiEntry {
+            inputs,
+            name: name.to_string(),
+            outputs,
+            state_mutability: state_mutability.to_string(),
+            entry_type: entry_type.to_string(),
+            anonymous,
+        })
+    }
+}
+
+struct HumanReadable<'a> {
+    rest: &'a str,
+}
+
+impl<'a> HumanReadable<'a> {
+    /// Consumes `c`, after whitespace
+    fn eat(&mut self, c: char) -> bool {
+        self.rest = self.rest.trim_start();
+        match self.rest.strip_prefix(c) {
+            Some(rest) => {
+                self.rest = rest;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// An identifier or keyword, after whitespace
+    fn word(&mut self) -> Option<&'a str> {
+        self.rest = self.rest.trim_start();
+        let end = self
+            .rest
+            .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '$'))
+            .unwrap_or(self.rest.len());
+        let (word, rest) = self.rest.split_at(end);
+        self.rest = rest;
+        (!word.is_empty()).then_some(word)
+    }
+
+    /// `(type name, ..)`
+    fn params(&mut self) -> Result<Vec<TypeType>, String> {
+        if !self.eat('(') {
+            return Err(format!("expected `(` at `{}`", self.rest));
+        }
+        let mut params = vec![];
+        if self.eat(')') {
+            return Ok(params);
+        }
+        loop {
+            params.push(self.param()?);
+            if self.eat(')') {
+                return Ok(params);
+            }
+            if !self.eat(',') {
+                return Err(format!("expected `,` or `)` at `{}`", self.rest));
+            }
+        }
+    }
+
+    /// `uint256[2] indexed amounts`
+    fn param(&mut self) -> Result<TypeType, String> {
+        self.rest = self.rest.trim_start();
+        let (mut type_type, components) = if self.rest.starts_with('(') {
+            (String::from("tuple"), Some(self.params()?))
+        } else {
+            let ty = self
+                .word()
+                .ok_or_else(|| format!("expected a type at `{}`", self.rest))?;
+            match ty {
+                "tuple" => (String::from("tuple"), Some(self.params()?)),
+                "uint" | "int" => (format!("{ty}256"), None),
+                "byte" => (String::from("bytes1"), None),
+                ty => (ty.to_string(), None),
+            }
+        };
+        while self.eat('[') {
+            let end = self
+                .rest
+                .find(']')
+                .ok_or_else(|| format!("unclosed `[` at `{}`", self.rest))?;
+            type_type.push_str(&format!("[{}]", self.rest[..end].trim()));
+            self.rest = &self.rest[end + 1..];
+        }
+
+        let mut name = String::new();
+        let mut indexed = false;
+        while let Some(word) = self.word() {
+            match word {
+                "indexed" => indexed = true,
+                "memory" | "calldata" | "storage" | "payable" => {}
+                word if name.is_empty() => name = word.to_string(),
+                word => return Err(format!("unexpected `{word}` after the parameter `{name}`")),
+            }
+        }
+
+        Ok(TypeType {
+            internal_type: None,
+            name,
+            type_type,
+            components,
+            indexed,
+        })
+    }
+}
+
+/// The ABI of a contract, in the JSON model of Solidity that Vyper shares and Huff converts to
+pub type Abi = Vec<AbiEntry>;
+
+/// Placeholders of the library addresses in some code, by source file and library name
+pub type LinkReferences = BTreeMap<String, BTreeMap<String, Vec<CodeRange>>>;
+
+/// A compiled contract, whichever its language
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Artifact {
+    pub name: String,
+    /// The source file defining the contract
+    pub file: String,
+    pub abi: Abi,
+    /// The creation code, in hex without `0x`
+    pub bytecode: String,
+    /// The runtime code, in hex without `0x`, empty when the compiler didn't output it
+    pub deployed_bytecode: String,
+    /// The source map of the runtime code
+    pub source_map: Option<String>,
+    /// Where the state variables are stored, only output by solc
+    pub storage_layout: Option<StorageLayout>,
+    /// The selectors of the functions, in hex, by signature
+    pub method_identifiers: BTreeMap<String, String>,
+    /// The libraries to link in the creation code
+    #[serde(default)]
+    pub link_references: LinkReferences,
+    /// The libraries to link in the runtime code, part of the creation code as well
+    #[serde(default)]
+    pub deployed_link_references: LinkReferences,
+}
+
+/// The layouts of the artifact files
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ArtifactFormat {
+    /// `out/Vault.sol/Vault.json`, as written by forge
+    #[default]
+    Foundry,
+    /// `out/src/Vault.sol/Vault.json`, as written by hardhat
+    Hardhat,
+}
+
+#[derive(Deserialize)]
+struct FoundryBytecode {
+    #[serde(default)]
+    object: String,
+    #[serde(rename = "sourceMap")]
+    source_map: Option<String>,
+    #[serde(rename = "linkReferences", default)]
+    link_references: LinkReferences,
+}
+
+#[derive(Deserialize)]
+struct FoundryArtifact {
+    abi: Abi,
+    #[serde(default)]
+    bytecode: Option<FoundryBytecode>,
+    #[serde(rename = "deployedBytecode", default)]
+    deployed_bytecode: Option<FoundryBytecode>,
+    #[serde(rename = "methodIdentifiers", default)]
+    method_identifiers: BTreeMap<String, String>,
+    #[serde(rename = "storageLayout", default)]
+    storage_layout: Option<StorageLayout>,
+    /// The metadata of solc, naming the contract in `settings.compilationTarget`
+    #[serde(default)]
+    metadata: Value,
+}
+
+#[derive(Deserialize)]
+struct HardhatArtifact {
+    #[serde(rename = "contractName")]
+    contract_name: String,
+    #[serde(rename = "sourceName")]
+    source_name: String,
+    abi: Abi,
+    bytecode: String,
+    #[serde(rename = "deployedBytecode")]
+    deployed_bytecode: String,
+    #[serde(rename = "linkReferences", default)]
+    link_references: LinkReferences,
+    #[serde(rename = "deployedLinkReferences", default)]
+    deployed_link_references: LinkReferences,
+}
+
+impl Artifact {
+    /// The selectors of the functions of an ABI, as output by solc
+    pub fn method_identifiers(abi: &Abi) -> BTreeMap<String, String> {
+        abi.iter()
+            .filter(|entry| entry.entry_type == "function")
+            .map(|func| {
+                let signature = func.signature();
+                let selector = get_sig(&signature)
+                    .iter()
+                    .map(|byte| format!("{byte:02x}"))
+                    .collect();
+                (signature, selector)
+            })
+            .collect()
+    }
+
+    /// The libraries to link in the creation code, by fully qualified name
+    /// (`src/Math.sol:Math`), with the offsets of their addresses
+    pub fn link_offsets(&self) -> Vec<(String, Vec<usize>)> {
+        self.link_references
+            .iter()
+            .flat_map(|(file, libraries)| {
+                libraries.iter().map(move |(name, ranges)| {
+                    let offsets = ranges.iter().map(|range| range.start).collect();
+                    (format!("{file}:{name}"), offsets)
+                })
+            })
+            .collect()
+    }
+
+    /// The creation code with zeros in place of the placeholders of the library addresses
+    pub fn unlinked_bytecode(&self) -> String {
+        let mut code = self.bytecode.clone();
+        for range in self
+            .link_references
+            .values()
+            .flat_map(BTreeMap::values)
+            .flatten()
+        {
+            let (start, end) = (2 * range.start, 2 * (range.start + range.length));
+            if end <= code.len() && code.is_char_boundary(start) && code.is_char_boundary(end) {
+                code.replace_range(start..end, &"0".repeat(end - start));
+            }
+        }
+        code
+    }
+
+    /// The JSON artifact file of the contract
+    pub fn to_json(&self, format: ArtifactFormat) -> Value {
+        match format {
+            ArtifactFormat::Foundry => {
+                let mut artifact = json!({
+                    "abi": self.abi,
+                    "bytecode": {
+                        "object": format!("0x{}", self.bytecode),
+                        "linkReferences": self.link_references,
+                    },
+                    "deployedBytecode": {
+                        "object": format!("0x{}", self.deployed_bytecode),
+                        "sourceMap": self.source_map,
+                        "linkReferences": self.deployed_link_references,
+                    },
+                    "methodIdentifiers": self.method_identifiers,
+                    "metadata": {
+                        "settings": { "compilationTarget": { &self.file: self.name } },
+                    },
+                });
+                if let Some(layout) = &self.storage_layout {
+                    artifact["storageLayout"] = json!(layout);
+                }
+                artifact
+            }
+            ArtifactFormat::Hardhat => json!({
+                "_format": "hh-sol-artifact-1",
+                "contractName": self.name,
+                "sourceName": self.file,
+                "abi": self.abi,
+                "bytecode": format!("0x{}", self.bytecode),
+                "deployedBytecode": format!("0x{}", self.deployed_bytecode),
+                "linkReferences": self.link_references,
+                "deployedLinkReferences": self.deployed_link_references,
+            }),
+        }
+    }
+
+    /// Reads a Foundry or Hardhat artifact, or a bare ABI, from the file at `path`. The contract
+    /// is named after the file when the artifact doesn't name it.
+    pub fn from_json(content: &str, path: &Path) -> Result<Self, serde_json::Error> {
+        let stem = |path: &Path| {
+            path.file_stem()
+                .map_or_else(String::new, |stem| stem.to_string_lossy().into_owned())
+        };
+        let value: Value = serde_json::from_str(content)?;
+
+        if value.get("contractName").is_some() {
+            let artifact: HardhatArtifact = serde_json::from_value(value)?;
+            return Ok(Artifact {
+                method_identifiers: Artifact::method_identifiers(&artifact.abi),
+                name: artifact.contract_name,
+                file: artifact.source_name,
+                abi: artifact.abi,
+                bytecode: unprefixed(&artifact.bytecode),
+                deployed_bytecode: unprefixed(&artifact.deployed_bytecode),
+                source_map: None,
+                storage_layout: None,
+                link_references: artifact.link_references,
+                deployed_link_references: artifact.deployed_link_references,
+            });
+        }
+        if value.is_array() {
+            let abi: Abi = serde_json::from_value(value)?;
+            return Ok(Artifact {
+                name: stem(path),
+                file: String::new(),
+                method_identifiers: Artifact::method_identifiers(&abi),
+                abi,
+                bytecode: String::new(),
+                deployed_bytecode: String::new(),
+                source_map: None,
+                storage_layout: None,
+                link_references: LinkReferences::new(),
+                deployed_link_references: LinkReferences::new(),
+            });
+        }
+
+        let artifact: FoundryArtifact = serde_json::from_value(value)?;
+        let (file, name) = artifact.metadata["settings"]["compilationTarget"]
+            .as_object()
+            .and_then(|target| target.iter().next())
+            .and_then(|(file, name)| Some((file.clone(), name.as_str()?.to_string())))
+            .unwrap_or_else(|| {
+                // `out/Vault.sol/Vault.json`
+                let file = path.parent().and_then(Path::file_name).unwrap_or_default();
+                (file.to_string_lossy().into_owned(), stem(path))
+            });
+        let method_identifiers = if artifact.method_identifiers.is_empty() {
+            Artifact::method_identifiers(&artifact.abi)
+        } else {
+            artifact.method_identifiers
+        };
+        let (bytecode, link_references) =
+            artifact.bytecode.map_or_else(Default::default, |bytecode| {
+                (unprefixed(&bytecode.object), bytecode.link_references)
+            });
+        let (deployed_bytecode, source_map, deployed_link_references) = artifact
+            .deployed_bytecode
+            .map_or_else(Default::default, |bytecode| {
+                (
+                    unprefixed(&bytecode.object),
+                    bytecode.source_map,
+                    bytecode.link_references,
+                )
+            });
+        Ok(Artifact {
+            name,
+            file,
+            abi: artifact.abi,
+            bytecode,
+            source_map,
+            deployed_bytecode,
+            storage_layout: artifact.storage_layout,
+            method_identifiers,
+            link_references,
+            deployed_link_references,
+        })
+    }
+}
+
+/// Strips the `0x` prefix of a hex string
+fn unprefixed(code: &str) -> String {
+    code.trim_start_matches("0x").to_string()
+}
+
+/// The contracts of a compilation
+pub trait Artifacts {
+    fn artifacts(&self) -> Vec<Artifact>;
+
+    /// The contract `name` defined in `file`
+    fn artifact(&self, file: &str, name: &str) -> Option<Artifact> {
+        self.artifacts()
+            .into_iter()
+            .find(|artifact| artifact.file == file && artifact.name == name)
+    }
+}
+
+/// What solc and vyper share in the standard JSON output of a contract
+struct StandardJsonContract<'a> {
+    abi: Option<&'a Abi>,
+    bytecode: Option<&'a str>,
+    deployed_bytecode: Option<&'a str>,
+    source_map: Option<&'a str>,
+    method_identifiers: Option<&'a HashMap<String, String>>,
+}
+
+impl StandardJsonContract<'_> {
+    /// The artifact without storage layout nor libraries
+    fn artifact(self, file: &str, name: &str) -> Artifact {
+        let abi = self.abi.cloned().unwrap_or_default();
+        Artifact {
+            name: name.to_string(),
+            file: file.to_string(),
+            bytecode: self.bytecode.map_or_else(String::new, unprefixed),
+            deployed_bytecode: self.deployed_bytecode.map_or_else(String::new, unprefixed),
+            source_map: self.source_map.map(str::to_string),
+            storage_layout: None,
+            method_identifiers: self
+                .method_identifiers
+                .map(|ids| ids.clone().into_iter().collect())
+                .unwrap_or_else(|| Artifact::method_identifiers(&abi)),
+            link_references: LinkReferences::new(),
+            deployed_link_references: LinkReferences::new(),
+            abi,
+        }
+    }
+}
+
+/// The artifacts of the contracts by file and name of the standard JSON output, sorted
+fn standard_json_artifacts<C>(
+    contracts: Option<&HashMap<String, HashMap<String, C>>>,
+    artifact: impl Fn(&str, &str, &C) -> Artifact,
+) -> Vec<Artifact> {
+    let mut artifacts: Vec<_> = contracts
+        .into_iter()
+        .flatten()
+        .flat_map(|(file, contracts)| {
+            contracts
+                .iter()
+                .map(|(name, contract)| artifact(file, name, contract))
+        })
+        .collect();
+    artifacts.sort_by(|a, b| (&a.file, &a.name).cmp(&(&b.file, &b.name)));
+    artifacts
+}
+
+impl Artifacts for SolcOut {
+    fn artifacts(&self) -> Vec<Artifact> {
+        standard_json_artifacts(self.contracts.as_ref(), |file, name, contract| {
+            let evm = contract.evm.as_ref();
+            let bytecode = evm.and_then(|evm| evm.bytecode.as_ref());
+            let deployed = evm
+                .and_then(|evm| evm.deployed_bytecode.as_ref())
+                .map(|deployed| &deployed.bytecode_data);
+            let standard = StandardJsonContract {
+                abi: contract.abi.as_ref(),
+                bytecode: bytecode.map(|bytecode| bytecode.object.as_str()),
+                deployed_bytecode: deployed.map(|bytecode| bytecode.object.as_str()),
+                source_map: deployed.map(|bytecode| bytecode.source_map.as_str()),
+                method_identifiers: evm.and_then(|evm| evm.method_identifiers.as_ref()),
+            };
+            let link_references = |bytecode: Option<&BytecodeData>| {
+                bytecode
+                    .map(|bytecode| {
+                        bytecode
+                            .link_references
+                            .iter()
+                            .map(|(file, libraries)| {
+                                (file.clone(), libraries.clone().into_iter().collect())
+                            })
+                            .collect()
+                    })
+                    .unwrap_or_default()
+            };
+            Artifact {
+                storage_layout: contract.storage_layout.clone(),
+                link_references: link_references(bytecode),
+                deployed_link_references: link_references(deployed),
+                ..standard.artifact(file, name)
+            }
+        })
+    }
+}
+
+impl Artifacts for VypercOut {
+    fn artifacts(&self) -> Vec<Artifact> {
+        standard_json_artifacts(self.contracts.as_ref(), |file, name, contract| {
+            let evm = contract.evm.as_ref();
+            let deployed = evm
+                .and_then(|evm| evm.deployed_bytecode.as_ref())
+                .map(|deployed| &deployed.bytecode_data);
+            StandardJsonContract {
+                abi: contract.abi.as_ref(),
+                bytecode: evm
+                    .and_then(|evm| evm.bytecode.as_ref())
+                    .map(|bytecode| bytecode.object.as_str()),
+                deployed_bytecode: deployed.map(|bytecode| bytecode.object.as_str()),
+                source_map: deployed.and_then(|bytecode| bytecode.source_map.as_deref()),
+                method_identifiers: evm.and_then(|evm| evm.method_identifiers.as_ref()),
+            }
+            .artifact(file, name)
+        })
+    }
+}
+
+/// huffc compiles a single contract, named after its file
+impl Artifacts for HuffcOut {
+    fn artifacts(&self) -> Vec<Artifact> {
+        let abi = self.abi.entries();
+        vec![Artifact {
+            name: self.name(),
+            file: self.file.path.clone(),
+            bytecode: unprefixed(&self.bytecode),
+            deployed_bytecode: unprefixed(&self.runtime),
+            source_map: None,
+            storage_layout: None,
+            link_references: LinkReferences::new(),
+            deployed_link_references: LinkReferences::new(),
+            method_identifiers: Artifact::method_identifiers(&abi),
+            abi,
+        }]
+    }
+}
+
+impl Artifacts for CompilerOutput {
+    fn artifacts(&self) -> Vec<Artifact> {
+        match self {
+            CompilerOutput::Solc(out) => out.artifacts(),
+            CompilerOutput::Vyper(out) => out.artifacts(),
+            CompilerOutput::Huff(out) => out.artifacts(),
+            CompilerOutput::Backend(out) => out.artifacts.clone(),
+        }
+    }
+}
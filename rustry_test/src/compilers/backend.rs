@@ -0,0 +1,432 @@
+use crate::compilers::{
+    artifact::{Abi, Artifact, Artifacts},
+    builder::{execute, BuilderError, CompilerError, CompilerKinds, CompilerOutput, Diagnostics},
+    solidity::solc::{
+        ErrType, OutError, OutputOption, Settings, Severity, Solc, SolcBuilder, SolcError, SolcOut,
+        SolcSettings, Source,
+    },
+    sources::SourceError,
+    version,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, LazyLock, RwLock},
+};
+use tempfile::tempdir;
+
+/// What a backend compiles
+#[derive(Debug, Clone, Serialize)]
+pub struct BackendInput {
+    pub sources: HashMap<String, String>,
+    /// The source to compile, the others being the files it imports
+    pub entry: String,
+    pub settings: SolcSettings,
+    /// Requirement on the compiler version
+    pub version: Option<String>,
+}
+
+/// The contracts of a compilation and its diagnostics
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct BackendOutput {
+    pub artifacts: Vec<Artifact>,
+    /// Diagnostics in the format of solc, the compilation failing when one is an error
+    #[serde(default)]
+    pub errors: Vec<SolcError>,
+}
+
+impl BackendOutput {
+    /// Deserializes an output cached by `Compiler::run`
+    pub fn parse(raw_out: &str) -> Result<CompilerOutput, CompilerError> {
+        let out = serde_json::from_str::<BackendOutput>(raw_out)
+            .map_err(|err| CompilerError::deserialize("backend", raw_out, err))?;
+
+        if out.errors.iter().any(OutError::is_error) {
+            return Err(Diagnostics::Solc(out.errors).into());
+        }
+
+        Ok(out.into())
+    }
+}
+
+impl From<BackendOutput> for CompilerOutput {
+    fn from(val: BackendOutput) -> Self {
+        CompilerOutput::Backend(val)
+    }
+}
+
+impl TryFrom<CompilerOutput> for BackendOutput {
+    type Error = CompilerError;
+
+    fn try_from(value: CompilerOutput) -> Result<BackendOutput, CompilerError> {
+        match value {
+            CompilerOutput::Backend(out) => Ok(out),
+            _ => Err(CompilerError::UnexpectedOutput("a backend")),
+        }
+    }
+}
+
+/// An error diagnostic without location, for the backends reporting their own failures
+pub fn error(message: impl Into<String>) -> SolcError {
+    OutError {
+        source_location: None,
+        secondary_source_locations: None,
+        err_type: ErrType::CompilerError,
+        component: String::from("backend"),
+        severity: Severity::Error,
+        error_code: None,
+        message: message.into(),
+        formatted_message: None,
+    }
+}
+
+/// A compiler of an EVM language, run by `Compiler` with `CompilerKinds::Backend(name)`
+///
+/// Backends are looked up by name in a registry holding the built-in ones and those given to
+/// [`register`], for the code running the compilers itself such as tests and build scripts. The
+/// macros expand in the process of rustc, which never runs the code of the crate using them, so
+/// they only see the built-in backends and the commands of the `rustry.toml` of the crate (see
+/// [`load_config`]).
+/// Imports are not followed: a backend compiling several files reads them itself.
+pub trait CompilerBackend: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// The command line run, hashed with the input so that changing its arguments recompiles
+    fn command(&self) -> Vec<String> {
+        vec![]
+    }
+
+    /// Extensions of the source files, the first naming the inline sources
+    fn extensions(&self) -> Vec<String>;
+
+    /// The executable compiling `input`, whose changes invalidate the cached outputs
+    fn binary(&self, input: &BackendInput) -> Result<PathBuf, CompilerError>;
+
+    fn compile(&self, input: &BackendInput) -> Result<BackendOutput, CompilerError>;
+}
+
+static BACKENDS: LazyLock<RwLock<BTreeMap<String, Arc<dyn CompilerBackend>>>> =
+    LazyLock::new(|| {
+        let builtins: [Arc<dyn CompilerBackend>; 3] = [
+            Arc::new(Yul),
+            Arc::new(CommandBackend::fe()),
+            Arc::new(CommandBackend::etk()),
+        ];
+        RwLock::new(
+            builtins
+                .into_iter()
+                .map(|backend| (backend.name().to_string(), backend))
+                .collect(),
+        )
+    });
+
+/// Adds a backend to the registry of the running process, replacing the one of the same name.
+/// The macros can't see it, see [`CompilerBackend`].
+pub fn register(backend: impl CompilerBackend + 'static) {
+    BACKENDS
+        .write()
+        .expect("the registry is not poisoned")
+        .insert(backend.name().to_string(), Arc::new(backend));
+}
+
+/// The backend registered as `name`
+pub fn get(name: &str) -> Option<Arc<dyn CompilerBackend>> {
+    BACKENDS
+        .read()
+        .expect("the registry is not poisoned")
+        .get(name)
+        .cloned()
+}
+
+/// Names of the registered backends, sorted
+pub fn names() -> Vec<String> {
+    BACKENDS
+        .read()
+        .expect("the registry is not poisoned")
+        .keys()
+        .cloned()
+        .collect()
+}
+
+/// Yul objects, compiled by solc with `language: "Yul"`
+#[derive(Debug)]
+pub struct Yul;
+
+impl CompilerBackend for Yul {
+    fn name(&self) -> &str {
+        "yul"
+    }
+
+    fn extensions(&self) -> Vec<String> {
+        vec![String::from("yul")]
+    }
+
+    fn binary(&self, input: &BackendInput) -> Result<PathBuf, CompilerError> {
+        Ok(version::select(
+            &CompilerKinds::Solc,
+            input.version.as_deref(),
+            &HashMap::new(),
+        )?)
+    }
+
+    fn compile(&self, input: &BackendInput) -> Result<BackendOutput, CompilerError> {
+        let mut solc = SolcBuilder::default()
+            .language(String::from("Yul"))
+            .bin(true)
+            .binary(self.binary(input)?)
+            .settings(Some(Settings {
+                remappings: None,
+                output_selection: HashMap::from([(
+                    String::from("*"),
+                    HashMap::from([(
+                        String::from("*"),
+                        vec![OutputOption::EvmBytecode, OutputOption::EvmDeployedBytecode],
+                    )]),
+                )]),
+                compilation: input.settings.clone(),
+            }))
+            .build()
+            .map_err(BuilderError::Solc)?;
+        solc.sources = input
+            .sources
+            .clone()
+            .into_iter()
+            .map(|(file, content)| (file, Source { content }))
+            .collect();
+
+        let out =
+            SolcOut::try_from(Solc::parse(&solc.raw_output()?)?).expect("solc outputs a SolcOut");
+        Ok(BackendOutput {
+            artifacts: out.artifacts(),
+            errors: out.errors.unwrap_or_default(),
+        })
+    }
+}
+
+/// A compiler run as a command, such as `fe` or `eas` (etk)
+///
+/// In the arguments, `{input}` is replaced by the path of the entry file and `{out}` by an
+/// output directory, the sources being written to a temporary directory. The command either
+/// writes `Name.bin` files of hex bytecode to `{out}`, next to optional `Name_abi.json` ABIs,
+/// or prints the hex bytecode of the entry file, which names the contract.
+#[derive(Debug, Clone, Deserialize)]
+pub struct CommandBackend {
+    #[serde(skip)]
+    pub name: String,
+    #[serde(default)]
+    pub extensions: Vec<String>,
+    /// The program and its arguments
+    pub command: Vec<String>,
+}
+
+impl CommandBackend {
+    /// Fe, built with `fe build`
+    pub fn fe() -> Self {
+        Self {
+            name: String::from("fe"),
+            extensions: vec![String::from("fe")],
+            command: [
+                "fe",
+                "build",
+                "{input}",
+                "--emit",
+                "abi,bytecode",
+                "--output-dir",
+                "{out}",
+                "--overwrite",
+            ]
+            .map(String::from)
+            .to_vec(),
+        }
+    }
+
+    /// etk assembly, assembled by `eas`
+    pub fn etk() -> Self {
+        Self {
+            name: String::from("etk"),
+            extensions: vec![String::from("etk")],
+            command: ["eas", "{input}"].map(String::from).to_vec(),
+        }
+    }
+}
+
+impl CompilerBackend for CommandBackend {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn command(&self) -> Vec<String> {
+        self.command.clone()
+    }
+
+    fn extensions(&self) -> Vec<String> {
+        self.extensions.clone()
+    }
+
+    fn binary(&self, _: &BackendInput) -> Result<PathBuf, CompilerError> {
+        Ok(PathBuf::from(
+            self.command.first().map_or("", String::as_str),
+        ))
+    }
+
+    fn compile(&self, input: &BackendInput) -> Result<BackendOutput, CompilerError> {
+        let dir = tempdir()?;
+        let sources = dir.path().join("sources");
+        for (name, content) in &input.sources {
+            let path = sources.join(name);
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(path, content)?;
+        }
+        let out = dir.path().join("out");
+        fs::create_dir_all(&out)?;
+
+        let entry = sources.join(&input.entry);
+        let args: Vec<String> = self
+            .command
+            .iter()
+            .skip(1)
+            .map(|arg| {
+                arg.replace("{input}", &entry.to_string_lossy())
+                    .replace("{out}", &out.to_string_lossy())
+            })
+            .collect();
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        let stdout = execute(&self.binary(input)?, &args, None)?;
+
+        let mut bins = vec![];
+        bin_files(&out, &mut bins)?;
+        bins.sort();
+        let mut output = BackendOutput::default();
+        for bin in bins {
+            let name = bin
+                .file_stem()
+                .map_or_else(String::new, |stem| stem.to_string_lossy().into_owned());
+            let abi_path = bin.with_file_name(format!("{name}_abi.json"));
+            let abi = if abi_path.is_file() {
+                let raw = fs::read_to_string(&abi_path)?;
+                serde_json::from_str::<Abi>(&raw)
+                    .map_err(|err| CompilerError::deserialize("backend", &raw, err))?
+            } else {
+                vec![]
+            };
+            let bytecode = fs::read_to_string(&bin)?;
+            output
+                .artifacts
+                .push(artifact(name, &input.entry, abi, &bytecode));
+        }
+
+        if output.artifacts.is_empty() {
+            let name = Path::new(&input.entry)
+                .file_stem()
+                .map_or_else(String::new, |stem| stem.to_string_lossy().into_owned());
+            output
+                .artifacts
+                .push(artifact(name, &input.entry, vec![], &stdout));
+        }
+        for artifact in &output.artifacts {
+            if !is_hex(&artifact.bytecode) {
+                output.errors.push(error(format!(
+                    "`{}` output for {} is not hex bytecode",
+                    self.command[0], artifact.name
+                )));
+            }
+        }
+        Ok(output)
+    }
+}
+
+fn artifact(name: String, file: &str, abi: Abi, bytecode: &str) -> Artifact {
+    Artifact {
+        name,
+        file: file.to_string(),
+        bytecode: bytecode.trim().trim_start_matches("0x").to_string(),
+        deployed_bytecode: String::new(),
+        source_map: None,
+        storage_layout: None,
+        link_references: Default::default(),
+        deployed_link_references: Default::default(),
+        method_identifiers: Artifact::method_identifiers(&abi),
+        abi,
+    }
+}
+
+fn is_hex(code: &str) -> bool {
+    !code.is_empty() && code.len().is_multiple_of(2) && code.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// The `.bin` files of `dir` and its subdirectories
+fn bin_files(dir: &Path, bins: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            bin_files(&path, bins)?;
+        } else if path.extension().is_some_and(|ext| ext == "bin") {
+            bins.push(path);
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+struct RustryToml {
+    #[serde(default)]
+    backends: HashMap<String, CommandBackend>,
+}
+
+/// The command backends declared in the `rustry.toml` of a crate, kept apart from the registry
+/// as each crate declares its own
+#[derive(Debug, Default)]
+pub struct BackendConfig {
+    /// The `rustry.toml`, if any
+    pub path: Option<PathBuf>,
+    pub backends: BTreeMap<String, CommandBackend>,
+}
+
+impl BackendConfig {
+    /// The backend `name` of the config, else of the registry
+    pub fn get(&self, name: &str) -> Option<Arc<dyn CompilerBackend>> {
+        match self.backends.get(name) {
+            Some(backend) => Some(Arc::new(backend.clone())),
+            None => get(name),
+        }
+    }
+}
+
+/// Reads the command backends declared in the `rustry.toml` of `root`, if any
+///
+/// ```toml
+/// [backends.mydsl]
+/// command = ["mydslc", "{input}", "--out-dir", "{out}"]
+/// extensions = ["mydsl"]
+/// ```
+pub fn load_config(root: &Path) -> Result<BackendConfig, SourceError> {
+    let path = root.join("rustry.toml");
+    if !path.is_file() {
+        return Ok(BackendConfig::default());
+    }
+    let invalid = |reason: String| SourceError::Config {
+        path: path.clone(),
+        reason,
+    };
+    let content = fs::read_to_string(&path).map_err(|err| SourceError::Io {
+        path: path.clone(),
+        err,
+    })?;
+    let config: RustryToml = toml::from_str(&content).map_err(|err| invalid(err.to_string()))?;
+    let mut backends = BTreeMap::new();
+    for (name, mut backend) in config.backends {
+        if backend.command.is_empty() {
+            return Err(invalid(format!("the command of backend `{name}` is empty")));
+        }
+        backend.name = name.clone();
+        backends.insert(name, backend);
+    }
+    Ok(BackendConfig {
+        path: Some(path),
+        backends,
+    })
+}
@@ -1,29 +1,55 @@
 use crate::compilers::{
-    huff::huffc::HuffcBuilder,
-    solidity::solc::{
-        JsonError, OutputOption, Settings, SolcBuilder, SolcBuilderError, SolcOut, Source,
+    backend::{self, BackendInput, BackendOutput, CompilerBackend},
+    cache::cached,
+    huff::huffc::{HuffSettings, Huffc, HuffcBuilder, HuffcBuilderError},
+    solidity::{
+        foundry::Remapping,
+        solc::{
+            OutputOption, Settings, Solc, SolcBuilder, SolcBuilderError, SolcError, SolcOut,
+            SolcSettings, Source,
+        },
     },
 };
 use core::fmt;
-use std::{collections::HashMap, fs::File, io::Write};
+use std::{
+    collections::HashMap,
+    error::Error,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process::{Command, ExitStatus, Stdio},
+};
 use tempfile::tempdir;
 
 use super::{
     huff::huffc::HuffcOut,
-    vyper::vyperc::{self, VypercBuilder, VypercOut},
+    version::{self, VersionError},
+    vyper::vyperc::{self, VyperError, Vyperc, VypercBuilder, VypercBuilderError, VypercOut},
 };
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum CompilerKinds {
     Solc,
     Vyper,
     Huff,
+    /// A compiler registered in `backend`, by name
+    Backend(String),
 }
 
 #[derive(Debug)]
 pub struct Compiler {
     pub sources: HashMap<String, String>,
+    /// The source to compile, the others being the files it imports
+    pub entry: String,
     pub kind: CompilerKinds,
+    /// Solidity import remappings
+    pub remappings: Vec<Remapping>,
+    /// Settings of solc, of which Vyper only uses the EVM version
+    pub settings: SolcSettings,
+    /// Requirement on the compiler version, instead of the pragmas
+    pub version: Option<String>,
+    /// Settings of huffc
+    pub huff: HuffSettings,
 }
 
 #[derive(Debug)]
@@ -31,43 +57,130 @@ pub enum CompilerOutput {
     Solc(SolcOut),
     Vyper(VypercOut),
     Huff(HuffcOut),
+    Backend(BackendOutput),
 }
 
 #[derive(Debug)]
 pub enum BuilderError {
     Solc(SolcBuilderError),
+    Vyper(VypercBuilderError),
+    Huff(HuffcBuilderError),
 }
 
 impl fmt::Display for BuilderError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Self::Solc(e) => write!(f, "solc builder error: {e}"),
+            Self::Vyper(e) => write!(f, "vyper builder error: {e}"),
+            Self::Huff(e) => write!(f, "huffc builder error: {e}"),
         }
     }
 }
 
-impl fmt::Display for BinError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+impl Error for BuilderError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
         match self {
-            Self::Json(e) => write!(f, "compiler bin error: {e}"),
+            Self::Solc(e) => Some(e),
+            Self::Vyper(e) => Some(e),
+            Self::Huff(e) => Some(e),
         }
     }
 }
 
+/// Every diagnostic of a failed compilation, warnings included
 #[derive(Debug)]
-pub enum BinError {
-    Json(JsonError),
+pub enum Diagnostics {
+    Solc(Vec<SolcError>),
+    Vyper(Vec<VyperError>),
+}
+
+impl Diagnostics {
+    /// Formatted messages of the errors, in the compiler's order
+    pub fn errors(&self) -> Vec<&str> {
+        match self {
+            Self::Solc(errs) => errs
+                .iter()
+                .filter(|e| e.is_error())
+                .map(|e| e.formatted())
+                .collect(),
+            Self::Vyper(errs) => errs
+                .iter()
+                .filter(|e| e.is_error())
+                .map(|e| e.formatted())
+                .collect(),
+        }
+    }
+}
+
+impl fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.errors().join("\n"))
+    }
 }
 
 #[derive(Debug)]
 pub enum CompilerError {
     BuilderError(BuilderError),
-    BinError(BinError),
+    /// The compiler could not be started
+    MissingBinary {
+        binary: PathBuf,
+        err: io::Error,
+    },
+    /// The compiler exited with a failure
+    ExitError {
+        binary: PathBuf,
+        status: ExitStatus,
+        stderr: String,
+    },
+    /// The output of the compiler is not the expected JSON
+    DeserializeError {
+        compiler: &'static str,
+        err: serde_json::Error,
+        /// The output around the error
+        snippet: String,
+    },
+    /// The files of the compilation could not be written or read
+    IoError(io::Error),
+    VersionError(VersionError),
+    Diagnostics(Diagnostics),
+    /// No backend is registered under that name
+    UnknownBackend(String),
+    /// The output is not the one of the compiler named
+    UnexpectedOutput(&'static str),
+}
+
+impl CompilerError {
+    pub fn deserialize(compiler: &'static str, output: &str, err: serde_json::Error) -> Self {
+        const CONTEXT: usize = 60;
+        let line = output
+            .lines()
+            .nth(err.line().saturating_sub(1))
+            .unwrap_or_default();
+        let column = err.column().saturating_sub(1);
+        let start = (0..=column.saturating_sub(CONTEXT).min(line.len()))
+            .rev()
+            .find(|i| line.is_char_boundary(*i))
+            .unwrap_or(0);
+        let end = ((column + CONTEXT).min(line.len())..=line.len())
+            .find(|i| line.is_char_boundary(*i))
+            .unwrap_or(line.len());
+        Self::DeserializeError {
+            compiler,
+            err,
+            snippet: line[start..end].to_string(),
+        }
+    }
 }
 
-impl From<BinError> for CompilerError {
-    fn from(e: BinError) -> Self {
-        Self::BinError(e)
+impl From<Diagnostics> for CompilerError {
+    fn from(e: Diagnostics) -> Self {
+        Self::Diagnostics(e)
+    }
+}
+
+impl From<VersionError> for CompilerError {
+    fn from(e: VersionError) -> Self {
+        Self::VersionError(e)
     }
 }
 
@@ -77,43 +190,155 @@ impl From<BuilderError> for CompilerError {
     }
 }
 
-impl std::error::Error for CompilerError {}
+impl From<io::Error> for CompilerError {
+    fn from(e: io::Error) -> Self {
+        Self::IoError(e)
+    }
+}
+
+impl Error for CompilerError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::BuilderError(e) => Some(e),
+            Self::MissingBinary { err, .. } => Some(err),
+            Self::ExitError { .. } => None,
+            Self::DeserializeError { err, .. } => Some(err),
+            Self::IoError(e) => Some(e),
+            Self::VersionError(e) => Some(e),
+            Self::Diagnostics(_) | Self::UnknownBackend(_) | Self::UnexpectedOutput(_) => None,
+        }
+    }
+}
 
 impl std::fmt::Display for CompilerError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
         match self {
             Self::BuilderError(e) => write!(f, "builder error: {e}"),
-            Self::BinError(e) => write!(f, "bin error: {e}"),
+            Self::MissingBinary { binary, err } if err.kind() == io::ErrorKind::NotFound => write!(
+                f,
+                "`{}` was not found, install it or add it to PATH",
+                binary.display()
+            ),
+            Self::MissingBinary { binary, err } => {
+                write!(f, "failed to run `{}`: {err}", binary.display())
+            }
+            Self::ExitError {
+                binary,
+                status,
+                stderr,
+            } => write!(
+                f,
+                "`{}` failed ({status}):\n{}",
+                binary.display(),
+                stderr.trim_end()
+            ),
+            Self::DeserializeError {
+                compiler,
+                err,
+                snippet,
+            } => write!(
+                f,
+                "failed to deserialize the {compiler} output: {err}, near `{snippet}`"
+            ),
+            Self::IoError(e) => write!(f, "io error: {e}"),
+            Self::VersionError(e) => write!(f, "version error: {e}"),
+            Self::Diagnostics(e) => write!(f, "compilation failed:\n{e}"),
+            Self::UnknownBackend(name) => write!(
+                f,
+                "unknown compiler backend `{name}`, expected one of: {}",
+                backend::names().join(", ")
+            ),
+            Self::UnexpectedOutput(compiler) => {
+                write!(f, "the output is not the one of {compiler}")
+            }
         }
     }
 }
 
+/// Runs a compiler, writing `input` to its standard input, and returns its standard output
+pub(crate) fn execute(
+    binary: &Path,
+    args: &[&str],
+    input: Option<&str>,
+) -> Result<String, CompilerError> {
+    let missing = |err| CompilerError::MissingBinary {
+        binary: binary.to_path_buf(),
+        err,
+    };
+    let mut child = Command::new(binary)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(missing)?;
+
+    if let Some(input) = input {
+        child
+            .stdin
+            .take()
+            .expect("stdin is piped")
+            .write_all(input.as_bytes())?;
+    }
+    let output = child.wait_with_output()?;
+    let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+
+    if !output.status.success() {
+        // some compilers report their errors on the standard output
+        let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+        return Err(CompilerError::ExitError {
+            binary: binary.to_path_buf(),
+            status: output.status,
+            stderr: if stderr.trim().is_empty() {
+                stdout
+            } else {
+                stderr
+            },
+        });
+    }
+    Ok(stdout)
+}
+
 pub trait RunCompiler {
     fn run(&self) -> Result<CompilerOutput, CompilerError>;
 }
 
 impl Compiler {
+    /// Compiles the sources, reusing the output of a previous compilation of the same input
+    /// from the on-disk cache
     pub fn run(&self) -> Result<CompilerOutput, CompilerError> {
-        match self.kind {
+        match &self.kind {
             CompilerKinds::Solc => {
                 let mut solc = SolcBuilder::default()
                     .bin(true)
+                    .binary(version::select(
+                        &self.kind,
+                        self.version.as_deref(),
+                        &self.sources,
+                    )?)
                     .settings(Some(Settings {
-                        remappings: None,
+                        remappings: (!self.remappings.is_empty())
+                            .then(|| self.remappings.iter().map(ToString::to_string).collect()),
                         output_selection: HashMap::from([(
                             String::from("*"),
-                            HashMap::from([(
-                                String::from("*"),
-                                vec![
-                                    OutputOption::EvmBytecode,
-                                    OutputOption::Metadata,
-                                    OutputOption::Abi,
-                                ],
-                            )]),
+                            HashMap::from([
+                                (String::from(""), vec![OutputOption::Ast]),
+                                (
+                                    String::from("*"),
+                                    vec![
+                                        OutputOption::EvmBytecode,
+                                        OutputOption::EvmDeployedBytecode,
+                                        OutputOption::Metadata,
+                                        OutputOption::Abi,
+                                        OutputOption::StorageLayout,
+                                    ],
+                                ),
+                            ]),
                         )]),
+                        compilation: self.settings.clone(),
                     }))
                     .build()
-                    .unwrap();
+                    .map_err(BuilderError::Solc)?;
                 solc.sources = self
                     .sources
                     .clone()
@@ -121,43 +346,81 @@ impl Compiler {
                     .map(|(file, content)| (file, Source { content }))
                     .collect();
 
-                solc.run()
+                cached(
+                    &self.kind,
+                    &solc.binary,
+                    &serde_json::to_value(&solc)
+                        .expect("the input serializes")
+                        .to_string(),
+                    || solc.raw_output(),
+                    Solc::parse,
+                )
             }
             CompilerKinds::Huff => {
-                let dir = tempdir().unwrap(); // deleted when the destructor is ran
-                let input = dir.path().join("input.huff");
-                let input = input.into_os_string().into_string().unwrap();
-                let mut file = File::create(input.clone()).unwrap();
-                let content = self.sources.iter().next().unwrap().1;
-                writeln!(file, "{}", content).unwrap();
-                let output = dir.path().join("output.json");
-                File::create(output.clone()).unwrap();
-                let output = output.into_os_string().into_string().unwrap();
-
-                let mut huffc = HuffcBuilder::default()
-                    .artifacts(true)
-                    .input(input)
-                    .output(output)
-                    .build()
-                    .unwrap();
-                huffc.attach_dir(dir);
+                let input = serde_json::json!({
+                    "sources": self.sources,
+                    "entry": self.entry,
+                    "settings": self.huff,
+                });
+                cached(
+                    &self.kind,
+                    Path::new("huffc"),
+                    &input.to_string(),
+                    || {
+                        // huffc reads the files, which are written with their includes so that
+                        // the relative paths resolve
+                        let dir = tempdir()?; // deleted when the destructor is ran
+                        let sources = dir.path().join("sources");
+                        for (name, content) in &self.sources {
+                            let path = sources.join(name);
+                            if let Some(parent) = path.parent() {
+                                fs::create_dir_all(parent)?;
+                            }
+                            fs::write(path, content)?;
+                        }
+                        let output = dir.path().join("output.json");
+
+                        let mut huffc = HuffcBuilder::default()
+                            .artifacts(true)
+                            .input(sources.join(&self.entry).to_string_lossy().into_owned())
+                            .output(output.to_string_lossy().into_owned())
+                            .settings(self.huff.clone())
+                            .build()
+                            .map_err(BuilderError::Huff)?;
+                        huffc.attach_dir(dir);
 
-                huffc.run()
+                        huffc.raw_output()
+                    },
+                    Huffc::parse,
+                )
             }
             CompilerKinds::Vyper => {
                 let mut vyc = VypercBuilder::default()
+                    .binary(version::select(
+                        &self.kind,
+                        self.version.as_deref(),
+                        &self.sources,
+                    )?)
                     .settings(Some(super::vyper::vyperc::Settings {
-                        evm_version: String::from("paris"),
+                        evm_version: self
+                            .settings
+                            .evm_version
+                            .clone()
+                            .unwrap_or_else(|| String::from("paris")),
                         output_selection: HashMap::from([(
                             String::from("*"),
                             HashMap::from([(
                                 String::from("*"),
-                                vec![vyperc::OutputOption::EvmBytecode, vyperc::OutputOption::Abi],
+                                vec![
+                                    vyperc::OutputOption::EvmBytecode,
+                                    vyperc::OutputOption::EvmDeployedBytecode,
+                                    vyperc::OutputOption::Abi,
+                                ],
                             )]),
                         )]),
                     }))
                     .build()
-                    .unwrap();
+                    .map_err(BuilderError::Vyper)?;
                 vyc.sources = self
                     .sources
                     .clone()
@@ -165,8 +428,46 @@ impl Compiler {
                     .map(|(file, content)| (file, Source { content }))
                     .collect();
 
-                vyc.run()
+                cached(
+                    &self.kind,
+                    &vyc.binary,
+                    &serde_json::to_value(&vyc)
+                        .expect("the input serializes")
+                        .to_string(),
+                    || vyc.raw_output(),
+                    Vyperc::parse,
+                )
+            }
+            CompilerKinds::Backend(name) => {
+                let backend = backend::get(name)
+                    .ok_or_else(|| CompilerError::UnknownBackend(name.clone()))?;
+                self.run_backend(&*backend)
             }
         }
     }
+
+    /// Compiles the sources with `backend`, such as one of a `BackendConfig` missing from the
+    /// registry, caching the output as `run` does
+    pub fn run_backend(
+        &self,
+        backend: &dyn CompilerBackend,
+    ) -> Result<CompilerOutput, CompilerError> {
+        let input = BackendInput {
+            sources: self.sources.clone(),
+            entry: self.entry.clone(),
+            settings: self.settings.clone(),
+            version: self.version.clone(),
+        };
+
+        cached(
+            &self.kind,
+            &backend.binary(&input)?,
+            &serde_json::json!({ "command": backend.command(), "input": input }).to_string(),
+            || {
+                let out = backend.compile(&input)?;
+                Ok(serde_json::to_string(&out).expect("the output serializes"))
+            },
+            BackendOutput::parse,
+        )
+    }
 }
@@ -0,0 +1,158 @@
+use crate::compilers::builder::{CompilerError, CompilerKinds, CompilerOutput};
+use std::{
+    env, fs,
+    io::Write,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+use tempfile::NamedTempFile;
+use tiny_keccak::{Hasher, Keccak};
+
+/// On-disk cache of the raw compiler outputs, shared by the macro invocations and the builds
+///
+/// It lives in `target/rustry-cache` unless `RUSTRY_CACHE_DIR` is set, and is disabled by
+/// setting `RUSTRY_NO_CACHE`.
+#[derive(Debug)]
+pub struct Cache {
+    pub dir: PathBuf,
+}
+
+impl Cache {
+    pub fn open() -> Option<Self> {
+        if env::var_os("RUSTRY_NO_CACHE").is_some() {
+            return None;
+        }
+        let dir = match env::var_os("RUSTRY_CACHE_DIR") {
+            Some(dir) => PathBuf::from(dir),
+            None => target_dir()?.join("rustry-cache"),
+        };
+        Some(Self { dir })
+    }
+
+    /// Hash of everything the output depends on: the compiler, its binary and its input,
+    /// which holds the settings and the sources. The input must be serialized with sorted
+    /// keys, as the maps of the input are `HashMap`s.
+    pub fn key(kind: &CompilerKinds, binary: &Path, input: &str) -> String {
+        let mut keccak = Keccak::v256();
+        keccak.update(format!("{kind:?}").as_bytes());
+        keccak.update(&[0]);
+        keccak.update(fingerprint(binary).as_bytes());
+        keccak.update(&[0]);
+        keccak.update(input.as_bytes());
+        let mut hash = [0u8; 32];
+        keccak.finalize(&mut hash);
+        hash.iter().map(|byte| format!("{byte:02x}")).collect()
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(key).with_extension("json")
+    }
+
+    pub fn get(&self, key: &str) -> Option<String> {
+        fs::read_to_string(self.path(key)).ok()
+    }
+
+    /// Stores an output, through a rename so that concurrent macro expansions never read a
+    /// partial file. Failing to write only loses the cache entry.
+    pub fn put(&self, key: &str, raw: &str) {
+        let _ = fs::create_dir_all(&self.dir)
+            .and_then(|_| NamedTempFile::new_in(&self.dir))
+            .and_then(|mut file| {
+                file.write_all(raw.as_bytes())?;
+                file.persist(self.path(key)).map_err(|e| e.error)?;
+                Ok(())
+            });
+    }
+}
+
+/// Returns the cached output for `input`, or compiles it and caches the output if it parses
+/// without errors
+pub fn cached(
+    kind: &CompilerKinds,
+    binary: &Path,
+    input: &str,
+    compile: impl FnOnce() -> Result<String, CompilerError>,
+    parse: impl Fn(&str) -> Result<CompilerOutput, CompilerError>,
+) -> Result<CompilerOutput, CompilerError> {
+    let Some(cache) = Cache::open() else {
+        return parse(&compile()?);
+    };
+    let key = Cache::key(kind, binary, input);
+    if let Some(raw) = cache.get(&key) {
+        if let Ok(output) = parse(&raw) {
+            return Ok(output);
+        }
+    }
+
+    let raw = compile()?;
+    let output = parse(&raw)?;
+    cache.put(&key, &raw);
+    Ok(output)
+}
+
+/// The target directory of the crate being built: `CARGO_TARGET_DIR`, or `target` next to the
+/// `Cargo.lock` of its workspace
+fn target_dir() -> Option<PathBuf> {
+    if let Some(dir) = env::var_os("CARGO_TARGET_DIR") {
+        return Some(PathBuf::from(dir));
+    }
+    let manifest_dir = PathBuf::from(env::var_os("CARGO_MANIFEST_DIR")?);
+    let root = manifest_dir
+        .ancestors()
+        .find(|dir| dir.join("Cargo.lock").is_file())
+        .unwrap_or(&manifest_dir);
+    Some(root.join("target"))
+}
+
+/// Identifies the binary by its resolved path, size and modification time, so that replacing
+/// the compiler invalidates its outputs
+fn fingerprint(binary: &Path) -> String {
+    let resolved = if binary.components().count() > 1 {
+        Some(binary.to_path_buf())
+    } else {
+        env::var_os("PATH").and_then(|paths| {
+            env::split_paths(&paths)
+                .map(|dir| dir.join(binary))
+                .find(|path| path.is_file())
+        })
+    };
+    let Some(path) = resolved else {
+        return binary.display().to_string();
+    };
+    let (len, modified) = fs::metadata(&path)
+        .map(|metadata| {
+            let modified = metadata
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |time| time.as_nanos());
+            (metadata.len(), modified)
+        })
+        .unwrap_or_default();
+    format!("{}:{len}:{modified}", path.display())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Cache, CompilerKinds};
+    use std::path::Path;
+    use tempfile::tempdir;
+
+    #[test]
+    fn keys_and_entries() {
+        let binary = Path::new("/nonexistent/solc");
+        let key = Cache::key(&CompilerKinds::Solc, binary, "{}");
+        assert_eq!(key.len(), 64);
+        assert_eq!(key, Cache::key(&CompilerKinds::Solc, binary, "{}"));
+        assert_ne!(key, Cache::key(&CompilerKinds::Vyper, binary, "{}"));
+        assert_ne!(key, Cache::key(&CompilerKinds::Solc, binary, "{ }"));
+
+        let dir = tempdir().unwrap();
+        let cache = Cache {
+            dir: dir.path().join("cache"),
+        };
+        assert_eq!(cache.get(&key), None);
+        cache.put(&key, "{\"contracts\":{}}");
+        assert_eq!(cache.get(&key).as_deref(), Some("{\"contracts\":{}}"));
+    }
+}
@@ -1,15 +1,35 @@
 use crate::{
     common::utils::opt_false,
-    compilers::builder::{CompilerError, CompilerOutput, RunCompiler},
+    compilers::{
+        builder::{execute, CompilerError, CompilerOutput, RunCompiler},
+        solidity::solc::{AbiEntry, TypeType},
+    },
 };
 use derive_builder::Builder;
 use serde::{Deserialize, Serialize};
 use std::{
-    fs::{self},
-    process::{Command, Stdio},
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
 };
 use tempfile::TempDir;
 
+/// Options of huffc, passed as flags by `Huffc::args`. They serialize into the key of the
+/// project builds.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct HuffSettings {
+    /// Arguments of the constructor, encoded by huffc from their declared types
+    pub constructor_args: Vec<String>,
+    /// Values of `#define constant`s, overriding the ones of the code
+    pub constants: BTreeMap<String, String>,
+    /// Macro to use instead of `MAIN`
+    pub alt_main: Option<String>,
+    /// Macro to use instead of `CONSTRUCTOR`
+    pub alt_constructor: Option<String>,
+    /// EVM version of the bytecode, like `shanghai`, left to huffc's default when `None`
+    pub evm_version: Option<String>,
+}
+
 #[derive(Builder, Serialize)]
 pub struct Huffc {
     #[serde(skip_serializing_if = "opt_false")]
@@ -19,6 +39,13 @@ pub struct Huffc {
     #[serde(rename(serialize = "-o"))]
     pub output: String,
     pub input: String,
+    #[builder(default)]
+    #[serde(flatten)]
+    pub settings: HuffSettings,
+    /// The huffc binary to run
+    #[serde(skip_serializing)]
+    #[builder(default = "PathBuf::from(\"huffc\")")]
+    pub binary: PathBuf,
     #[builder(setter(skip))]
     #[serde(skip)]
     pub dir: Option<TempDir>,
@@ -28,6 +55,41 @@ impl Huffc {
     pub fn attach_dir(&mut self, dir: TempDir) {
         self.dir = Some(dir);
     }
+
+    /// Command line arguments, the input coming first as the constants and the constructor
+    /// arguments take several values
+    pub fn args(&self) -> Vec<String> {
+        let mut args = vec![self.input.clone()];
+        if self.artifacts {
+            args.push(String::from("-a"));
+        }
+        args.extend([String::from("-o"), self.output.clone()]);
+
+        let settings = &self.settings;
+        for (flag, value) in [
+            ("-m", &settings.alt_main),
+            ("-t", &settings.alt_constructor),
+            ("-e", &settings.evm_version),
+        ] {
+            if let Some(value) = value {
+                args.extend([flag.to_string(), value.clone()]);
+            }
+        }
+        if !settings.constants.is_empty() {
+            args.push(String::from("-c"));
+            args.extend(
+                settings
+                    .constants
+                    .iter()
+                    .map(|(name, value)| format!("{name}={value}")),
+            );
+        }
+        if !settings.constructor_args.is_empty() {
+            args.push(String::from("-i"));
+            args.extend(settings.constructor_args.iter().cloned());
+        }
+        args
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -38,28 +100,194 @@ pub struct HuffFile {
     pub dependencies: Vec<String>, // TODO
 }
 
+/// Type of a parameter, as declared with `#define function`
+#[derive(Debug, Deserialize)]
+pub enum HuffParamType {
+    Address,
+    Bytes,
+    Int(usize),
+    Uint(usize),
+    Bool,
+    String,
+    /// The element type and the sizes of the dimensions, 0 for a dynamic one
+    Array(Box<HuffParamType>, Vec<usize>),
+    FixedBytes(usize),
+    Tuple(Vec<HuffParamType>),
+}
+
+impl HuffParamType {
+    /// The parameter in the Solidity ABI model, tuples having unnamed components
+    pub fn to_param(&self, name: &str, internal_type: Option<String>) -> TypeType {
+        let (base, dims) = self.split_dims();
+        let (type_type, components) = match base {
+            Self::Tuple(types) => (
+                String::from("tuple"),
+                Some(types.iter().map(|ty| ty.to_param("", None)).collect()),
+            ),
+            Self::Address => (String::from("address"), None),
+            Self::Bytes => (String::from("bytes"), None),
+            Self::Int(size) => (format!("int{size}"), None),
+            Self::Uint(size) => (format!("uint{size}"), None),
+            Self::Bool => (String::from("bool"), None),
+            Self::String => (String::from("string"), None),
+            Self::FixedBytes(size) => (format!("bytes{size}"), None),
+            Self::Array(..) => unreachable!("the dimensions are split"),
+        };
+        TypeType {
+            internal_type,
+            name: name.to_string(),
+            type_type: format!("{type_type}{dims}"),
+            components,
+            indexed: false,
+        }
+    }
+
+    /// The element type and the `[2][]` suffix of an array
+    fn split_dims(&self) -> (&Self, String) {
+        match self {
+            Self::Array(inner, sizes) => {
+                let (base, mut dims) = inner.split_dims();
+                for size in sizes {
+                    match size {
+                        0 => dims.push_str("[]"),
+                        size => dims.push_str(&format!("[{size}]")),
+                    }
+                }
+                (base, dims)
+            }
+            ty => (ty, String::new()),
+        }
+    }
+}
+
 #[derive(Debug, Deserialize)]
-pub struct Functions {}
+pub struct HuffParam {
+    pub name: String,
+    pub kind: HuffParamType,
+    pub internal_type: Option<String>,
+}
+
+impl HuffParam {
+    fn to_param(&self) -> TypeType {
+        self.kind.to_param(&self.name, self.internal_type.clone())
+    }
+}
 
 #[derive(Debug, Deserialize)]
-pub struct Events {}
+pub struct HuffEventParam {
+    pub name: String,
+    pub kind: HuffParamType,
+    pub indexed: bool,
+}
 
 #[derive(Debug, Deserialize)]
-pub struct Errors {}
+pub struct HuffConstructor {
+    pub inputs: Vec<HuffParam>,
+}
 
+#[derive(Debug, Deserialize)]
+pub struct HuffFunction {
+    pub name: String,
+    pub inputs: Vec<HuffParam>,
+    pub outputs: Vec<HuffParam>,
+    pub constant: bool,
+    /// `View`, `Pure`, `NonPayable` or `Payable`
+    pub state_mutability: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HuffEvent {
+    pub name: String,
+    pub inputs: Vec<HuffEventParam>,
+    pub anonymous: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HuffError {
+    pub name: String,
+    pub inputs: Vec<HuffParam>,
+}
+
+/// The ABI of the `#define function`, `event`, `error` and `constructor` declarations, keyed by
+/// name
 #[derive(Debug, Deserialize)]
 pub struct HuffAbi {
-    pub constructor: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub functions: Option<Functions>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub events: Option<Events>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub errors: Option<Errors>,
+    pub constructor: Option<HuffConstructor>,
+    #[serde(default)]
+    pub functions: BTreeMap<String, HuffFunction>,
+    #[serde(default)]
+    pub events: BTreeMap<String, HuffEvent>,
+    #[serde(default)]
+    pub errors: BTreeMap<String, HuffError>,
     pub receive: bool,
     pub fallback: bool,
 }
 
+impl HuffAbi {
+    /// The entries of the ABI in the Solidity model, shared by the bindings
+    pub fn entries(&self) -> Vec<AbiEntry> {
+        let entry =
+            |entry_type: &str, name: &str, inputs, outputs, state_mutability: &str| AbiEntry {
+                inputs,
+                name: name.to_string(),
+                outputs,
+                state_mutability: state_mutability.to_string(),
+                entry_type: entry_type.to_string(),
+                anonymous: false,
+            };
+        let params = |params: &[HuffParam]| params.iter().map(HuffParam::to_param).collect();
+
+        let mut entries: Vec<_> = self
+            .constructor
+            .iter()
+            .map(|constructor| {
+                entry(
+                    "constructor",
+                    "",
+                    params(&constructor.inputs),
+                    vec![],
+                    "nonpayable",
+                )
+            })
+            .collect();
+        entries.extend(self.functions.values().map(|func| {
+            entry(
+                "function",
+                &func.name,
+                params(&func.inputs),
+                params(&func.outputs),
+                &func.state_mutability.to_lowercase(),
+            )
+        }));
+        entries.extend(self.events.values().map(|event| {
+            let inputs = event
+                .inputs
+                .iter()
+                .map(|input| TypeType {
+                    indexed: input.indexed,
+                    ..input.kind.to_param(&input.name, None)
+                })
+                .collect();
+            AbiEntry {
+                anonymous: event.anonymous,
+                ..entry("event", &event.name, inputs, vec![], "")
+            }
+        }));
+        entries.extend(
+            self.errors
+                .values()
+                .map(|error| entry("error", &error.name, params(&error.inputs), vec![], "")),
+        );
+        if self.receive {
+            entries.push(entry("receive", "", vec![], vec![], "payable"));
+        }
+        if self.fallback {
+            entries.push(entry("fallback", "", vec![], vec![], "nonpayable"));
+        }
+        entries
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct HuffcOut {
     pub file: HuffFile,
@@ -68,6 +296,15 @@ pub struct HuffcOut {
     pub abi: HuffAbi,
 }
 
+impl HuffcOut {
+    /// The contract is named after its file
+    pub fn name(&self) -> String {
+        Path::new(&self.file.path)
+            .file_stem()
+            .map_or_else(String::new, |stem| stem.to_string_lossy().into_owned())
+    }
+}
+
 impl From<HuffcOut> for CompilerOutput {
     fn from(val: HuffcOut) -> Self {
         CompilerOutput::Huff(val)
@@ -85,30 +322,29 @@ impl TryFrom<CompilerOutput> for HuffcOut {
     }
 }
 
-impl RunCompiler for Huffc {
-    fn run(&self) -> Result<CompilerOutput, CompilerError> {
-        // TODO pipe stderr and return it
-        let child = Command::new("huffc")
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .arg("-a")
-            .args(["-o", &self.output])
-            .arg(&self.input)
-            .spawn()
-            .unwrap();
-
-        let output = child.wait_with_output().unwrap();
-        if !output.status.success() {
-            panic!("{} ", String::from_utf8(output.stderr).unwrap());
-        }
+impl Huffc {
+    /// Runs the compiler, returning the raw artifact it writes
+    pub fn raw_output(&self) -> Result<String, CompilerError> {
+        let args = self.args();
+        execute(
+            &self.binary,
+            &args.iter().map(String::as_str).collect::<Vec<_>>(),
+            None,
+        )?;
+        Ok(fs::read_to_string(&self.output)?)
+    }
 
-        let out_content = fs::read_to_string(&self.output).unwrap();
-        let huffc_out = if let Ok(huffc_out) = serde_json::from_str::<HuffcOut>(&out_content) {
-            huffc_out
-        } else {
-            panic!("failed to deserialize huffc output: {}", &out_content);
-        };
+    /// Deserializes the artifact of the compiler
+    pub fn parse(out_content: &str) -> Result<CompilerOutput, CompilerError> {
+        let huffc_out = serde_json::from_str::<HuffcOut>(out_content)
+            .map_err(|err| CompilerError::deserialize("huffc", out_content, err))?;
 
         Ok(huffc_out.into())
     }
 }
+
+impl RunCompiler for Huffc {
+    fn run(&self) -> Result<CompilerOutput, CompilerError> {
+        Self::parse(&self.raw_output()?)
+    }
+}
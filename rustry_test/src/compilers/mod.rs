@@ -1,6 +1,11 @@
+pub mod artifact;
+pub mod backend;
 pub mod builder;
+pub mod cache;
 pub mod huff;
 pub mod solidity;
+pub mod sources;
+pub mod version;
 pub mod vyper;
 
 #[cfg(test)]
@@ -0,0 +1,254 @@
+use crate::compilers::sources::SourceError;
+use core::fmt;
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// `context:prefix=path`, the context restricting the remapping to the files under it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Remapping {
+    pub context: Option<String>,
+    pub prefix: String,
+    pub path: String,
+}
+
+impl FromStr for Remapping {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (context, remapping) = match s.split_once(':') {
+            Some((context, remapping)) if !context.contains('=') => (Some(context), remapping),
+            _ => (None, s),
+        };
+        match remapping.split_once('=') {
+            Some((prefix, path)) if !prefix.is_empty() => Ok(Remapping {
+                context: context
+                    .filter(|context| !context.is_empty())
+                    .map(String::from),
+                prefix: prefix.to_string(),
+                path: path.to_string(),
+            }),
+            _ => Err(format!("invalid remapping `{s}`, expected `prefix=path`")),
+        }
+    }
+}
+
+impl fmt::Display for Remapping {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(context) = &self.context {
+            write!(f, "{context}:")?;
+        }
+        write!(f, "{}={}", self.prefix, self.path)
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Profile {
+    src: Option<String>,
+    out: Option<String>,
+    libs: Option<Vec<String>>,
+    remappings: Option<Vec<String>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FoundryToml {
+    #[serde(default)]
+    profile: HashMap<String, Profile>,
+}
+
+/// The layout of a Foundry project, from its `foundry.toml` and `remappings.txt`
+#[derive(Debug)]
+pub struct FoundryConfig {
+    /// Directory of the contracts
+    pub src: String,
+    /// Directory of the artifacts
+    pub out: String,
+    /// Directories searched for the imports not found from the root
+    pub libs: Vec<String>,
+    pub remappings: Vec<Remapping>,
+    /// The configuration files read
+    pub paths: Vec<PathBuf>,
+}
+
+impl Default for FoundryConfig {
+    fn default() -> Self {
+        Self {
+            src: String::from("src"),
+            out: String::from("out"),
+            libs: vec![String::from("lib")],
+            remappings: vec![],
+            paths: vec![],
+        }
+    }
+}
+
+fn read(path: &Path) -> Result<Option<String>, SourceError> {
+    if !path.is_file() {
+        return Ok(None);
+    }
+    std::fs::read_to_string(path)
+        .map(Some)
+        .map_err(|err| SourceError::Io {
+            path: path.to_path_buf(),
+            err,
+        })
+}
+
+impl FoundryConfig {
+    /// Reads the configuration files in `root`, if any. The profile is selected with
+    /// `FOUNDRY_PROFILE`, falling back on the default profile for the missing keys.
+    pub fn load(root: &Path) -> Result<Self, SourceError> {
+        let mut config = Self::default();
+
+        let path = root.join("foundry.toml");
+        if let Some(content) = read(&path)? {
+            let invalid = |reason: String| SourceError::Config {
+                path: path.clone(),
+                reason,
+            };
+            let mut toml: FoundryToml =
+                toml::from_str(&content).map_err(|err| invalid(err.to_string()))?;
+            let name = std::env::var("FOUNDRY_PROFILE").unwrap_or_else(|_| String::from("default"));
+            let default = toml.profile.remove("default").unwrap_or_default();
+            let profile = toml.profile.remove(&name).unwrap_or_default();
+
+            if let Some(src) = profile.src.or(default.src) {
+                config.src = src;
+            }
+            if let Some(out) = profile.out.or(default.out) {
+                config.out = out;
+            }
+            if let Some(libs) = profile.libs.or(default.libs) {
+                config.libs = libs;
+            }
+            for remapping in profile
+                .remappings
+                .or(default.remappings)
+                .unwrap_or_default()
+            {
+                config.remappings.push(remapping.parse().map_err(invalid)?);
+            }
+            config.paths.push(path.clone());
+        }
+
+        let path = root.join("remappings.txt");
+        if let Some(content) = read(&path)? {
+            for line in content.lines().map(str::trim) {
+                if line.is_empty() || line.starts_with('#') {
+                    continue;
+                }
+                config
+                    .remappings
+                    .push(line.parse().map_err(|reason| SourceError::Config {
+                        path: path.clone(),
+                        reason,
+                    })?);
+            }
+            config.paths.push(path);
+        }
+
+        Ok(config)
+    }
+
+    /// Applies the longest matching remapping to the source unit `name` imported by `from`,
+    /// as solc does
+    pub fn remap(&self, from: &str, name: &str) -> String {
+        self.remappings
+            .iter()
+            .enumerate()
+            .filter(|(_, remapping)| {
+                name.starts_with(&remapping.prefix)
+                    && remapping
+                        .context
+                        .iter()
+                        .all(|context| from.starts_with(context))
+            })
+            .max_by_key(|(i, remapping)| {
+                let context = remapping.context.as_ref().map_or(0, String::len);
+                (context, remapping.prefix.len(), *i)
+            })
+            .map(|(_, remapping)| format!("{}{}", remapping.path, &name[remapping.prefix.len()..]))
+            .unwrap_or_else(|| name.to_string())
+    }
+
+    /// The file of a source unit, from the root or one of the libs
+    pub fn locate(&self, root: &Path, name: &str) -> Option<PathBuf> {
+        std::iter::once(root.to_path_buf())
+            .chain(self.libs.iter().map(|lib| root.join(lib)))
+            .map(|dir| dir.join(name))
+            .find(|path| path.is_file())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FoundryConfig, Remapping};
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn remappings() {
+        let remapping: Remapping = "src/:@oz/=lib/openzeppelin-contracts/contracts/"
+            .parse()
+            .unwrap();
+        assert_eq!(remapping.context.as_deref(), Some("src/"));
+        assert_eq!(remapping.prefix, "@oz/");
+        assert_eq!(
+            remapping.to_string(),
+            "src/:@oz/=lib/openzeppelin-contracts/contracts/"
+        );
+        assert!("no-equal-sign".parse::<Remapping>().is_err());
+
+        let config = FoundryConfig {
+            remappings: vec![
+                "@oz/=lib/oz/".parse().unwrap(),
+                "@oz/token/=lib/oz-token/".parse().unwrap(),
+                "test/:@oz/=lib/oz-mock/".parse().unwrap(),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(config.remap("src/A.sol", "@oz/A.sol"), "lib/oz/A.sol");
+        assert_eq!(
+            config.remap("src/A.sol", "@oz/token/B.sol"),
+            "lib/oz-token/B.sol"
+        );
+        assert_eq!(config.remap("test/A.sol", "@oz/A.sol"), "lib/oz-mock/A.sol");
+        assert_eq!(config.remap("src/A.sol", "src/B.sol"), "src/B.sol");
+    }
+
+    #[test]
+    fn load() {
+        let root = tempdir().unwrap();
+        fs::write(
+            root.path().join("foundry.toml"),
+            r#"
+                [profile.default]
+                src = "contracts"
+                out = "build"
+                libs = ["lib", "node_modules"]
+                remappings = ["@oz/=lib/openzeppelin-contracts/contracts/"]
+            "#,
+        )
+        .unwrap();
+        fs::write(
+            root.path().join("remappings.txt"),
+            "# comment\nds-test/=lib/forge-std/lib/ds-test/src/\n",
+        )
+        .unwrap();
+
+        let config = FoundryConfig::load(root.path()).unwrap();
+        assert_eq!(config.src, "contracts");
+        assert_eq!(config.out, "build");
+        assert_eq!(config.libs, ["lib", "node_modules"]);
+        assert_eq!(config.remappings.len(), 2);
+        assert_eq!(config.paths.len(), 2);
+
+        let config = FoundryConfig::load(&root.path().join("missing")).unwrap();
+        assert_eq!(config.src, "src");
+        assert_eq!(config.out, "out");
+        assert!(config.remappings.is_empty());
+    }
+}
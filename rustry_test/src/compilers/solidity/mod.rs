@@ -1,2 +1,3 @@
+pub mod foundry;
 pub mod solc;
 pub mod types;
@@ -1,15 +1,14 @@
+pub use crate::compilers::artifact::{AbiEntry, EntryUtils, TypeType};
 use crate::{
     common::utils::opt_false,
-    compilers::builder::{BinError, CompilerError, CompilerOutput, RunCompiler},
+    compilers::builder::{execute, CompilerError, CompilerOutput, Diagnostics, RunCompiler},
 };
-use core::fmt;
 use derive_builder::Builder;
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::HashMap,
-    error::Error,
-    io::Write,
-    process::{Command, Stdio},
+    collections::{BTreeMap, HashMap},
+    path::PathBuf,
+    str::FromStr,
 };
 
 // TODO we should move that in a common place
@@ -25,8 +24,14 @@ pub enum OutputOption {
     Metadata,
     #[serde(rename = "evm.bytecode")]
     EvmBytecode,
+    #[serde(rename = "evm.deployedBytecode")]
+    EvmDeployedBytecode,
     #[serde(rename = "abi")]
     Abi,
+    #[serde(rename = "ast")]
+    Ast,
+    #[serde(rename = "storageLayout")]
+    StorageLayout,
 }
 
 #[derive(Clone, Serialize)]
@@ -35,6 +40,128 @@ pub struct Settings {
     pub remappings: Option<Vec<String>>,
     #[serde(rename = "outputSelection")]
     pub output_selection: HashMap<String, HashMap<String, Vec<OutputOption>>>,
+    #[serde(flatten)]
+    pub compilation: SolcSettings,
+}
+
+/// The settings changing the bytecode, left to solc's defaults when `None`
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct SolcSettings {
+    #[serde(skip_serializing_if = "opt_none")]
+    pub optimizer: Option<Optimizer>,
+    #[serde(rename = "viaIR", skip_serializing_if = "opt_none")]
+    pub via_ir: Option<bool>,
+    #[serde(rename = "evmVersion", skip_serializing_if = "opt_none")]
+    pub evm_version: Option<String>,
+    #[serde(skip_serializing_if = "opt_none")]
+    pub metadata: Option<MetadataSettings>,
+    #[serde(skip_serializing_if = "opt_none")]
+    pub debug: Option<DebugSettings>,
+    /// Addresses of the deployed libraries, by source file and library name
+    #[serde(skip_serializing_if = "opt_none")]
+    pub libraries: Option<HashMap<String, HashMap<String, String>>>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Optimizer {
+    pub enabled: bool,
+    pub runs: u32,
+    #[serde(skip_serializing_if = "opt_none")]
+    pub details: Option<OptimizerDetails>,
+}
+
+impl Default for Optimizer {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            runs: 200,
+            details: None,
+        }
+    }
+}
+
+/// Switches for the optimizer steps
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OptimizerDetails {
+    #[serde(skip_serializing_if = "opt_none")]
+    pub peephole: Option<bool>,
+    #[serde(skip_serializing_if = "opt_none")]
+    pub inliner: Option<bool>,
+    #[serde(skip_serializing_if = "opt_none")]
+    pub jumpdest_remover: Option<bool>,
+    #[serde(skip_serializing_if = "opt_none")]
+    pub order_literals: Option<bool>,
+    #[serde(skip_serializing_if = "opt_none")]
+    pub deduplicate: Option<bool>,
+    #[serde(skip_serializing_if = "opt_none")]
+    pub cse: Option<bool>,
+    #[serde(skip_serializing_if = "opt_none")]
+    pub constant_optimizer: Option<bool>,
+    #[serde(skip_serializing_if = "opt_none")]
+    pub yul: Option<bool>,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct MetadataSettings {
+    #[serde(rename = "bytecodeHash", skip_serializing_if = "opt_none")]
+    pub bytecode_hash: Option<BytecodeHash>,
+    #[serde(rename = "appendCBOR", skip_serializing_if = "opt_none")]
+    pub append_cbor: Option<bool>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BytecodeHash {
+    Ipfs,
+    Bzzr1,
+    None,
+}
+
+impl FromStr for BytecodeHash {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ipfs" => Ok(Self::Ipfs),
+            "bzzr1" => Ok(Self::Bzzr1),
+            "none" => Ok(Self::None),
+            _ => Err(format!(
+                "unknown bytecode hash `{s}`, expected one of: ipfs, bzzr1, none"
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct DebugSettings {
+    #[serde(rename = "revertStrings", skip_serializing_if = "opt_none")]
+    pub revert_strings: Option<RevertStrings>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RevertStrings {
+    Default,
+    Strip,
+    Debug,
+    VerboseDebug,
+}
+
+impl FromStr for RevertStrings {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "default" => Ok(Self::Default),
+            "strip" => Ok(Self::Strip),
+            "debug" => Ok(Self::Debug),
+            "verboseDebug" => Ok(Self::VerboseDebug),
+            _ => Err(format!(
+                "unknown revert strings `{s}`, expected one of: default, strip, debug, verboseDebug"
+            )),
+        }
+    }
 }
 
 // https://docs.soliditylang.org/en/latest/using-the-compiler.html#input-description
@@ -48,6 +175,10 @@ pub struct Solc {
     #[serde(skip_serializing)]
     #[builder(default = "false")]
     pub bin: bool,
+    /// The solc binary to run
+    #[serde(skip_serializing)]
+    #[builder(default = "PathBuf::from(\"solc\")")]
+    pub binary: PathBuf,
     // #[serde(rename(serialize = "--ast-compact-json"))]
     #[serde(skip_serializing_if = "opt_false")]
     #[builder(default = "false")]
@@ -62,9 +193,10 @@ pub struct Solc {
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SourceLocation {
-    file: String,
-    start: i32,
-    end: i32,
+    pub file: String,
+    pub start: i32,
+    pub end: i32,
+    pub message: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -73,10 +205,24 @@ pub enum ErrType {
     InternalCompilerError,
     Exception,
     JSONError,
+    IOError,
     ParserError,
+    DocstringParsingError,
+    SyntaxError,
+    DeclarationError,
+    UnimplementedFeatureError,
+    CodeGenerationError,
+    CompilerError,
+    FatalError,
+    YulException,
+    SMTLogicException,
+    Warning,
+    Info,
+    #[serde(other)]
+    Other,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum Severity {
     Error,
@@ -88,7 +234,7 @@ pub enum Severity {
 pub struct OutError<S, E> {
     #[serde(rename = "sourceLocation")]
     pub source_location: Option<S>,
-    #[serde(rename = "secondarySourceLocation")]
+    #[serde(rename = "secondarySourceLocations")]
     pub secondary_source_locations: Option<Vec<SourceLocation>>,
     #[serde(rename = "type")]
     pub err_type: E,
@@ -101,6 +247,20 @@ pub struct OutError<S, E> {
     pub formatted_message: Option<String>,
 }
 
+impl<S, E> OutError<S, E> {
+    /// Whether the diagnostic fails the compilation, warnings and infos do not
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    /// The message with its location and the code snippet, when the compiler formats it
+    pub fn formatted(&self) -> &str {
+        self.formatted_message.as_deref().unwrap_or(&self.message)
+    }
+}
+
+pub type SolcError = OutError<SourceLocation, ErrType>;
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct DebugData {
     #[serde(rename = "entryPoint")]
@@ -113,7 +273,7 @@ pub struct DebugData {
 }
 
 // TODO
-type Ast = HashMap<String, String>;
+type Ast = serde_json::Value;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct GeneratedSource {
@@ -134,16 +294,24 @@ pub struct BytecodeData {
     pub source_map: String,
     #[serde(rename = "generatedSources")]
     pub generated_sources: Vec<GeneratedSource>,
-    #[serde(rename = "linkReferences")]
-    pub link_references: HashMap<String, HashMap<String, Vec<SourceLocation>>>,
+    /// Placeholders of the library addresses, by source file and library name
+    #[serde(rename = "linkReferences", default)]
+    pub link_references: HashMap<String, HashMap<String, Vec<CodeRange>>>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct DeployedBytecodeData {
     #[serde(flatten)]
     pub bytecode_data: BytecodeData,
-    #[serde(rename = "immutableReferences")]
-    pub immutable_references: HashMap<String, String>, // TODO
+    #[serde(rename = "immutableReferences", default)]
+    pub immutable_references: HashMap<String, Vec<CodeRange>>,
+}
+
+/// A range of the bytecode
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CodeRange {
+    pub start: usize,
+    pub length: usize,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -160,43 +328,74 @@ pub struct EvmOutput {
     pub gas_estimates: Option<HashMap<String, HashMap<String, String>>>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct StorageLayout {}
+/// Where the state variables of a contract are stored
+// https://docs.soliditylang.org/en/latest/internals/layout_in_storage.html#json-output
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct StorageLayout {
+    pub storage: Vec<StorageEntry>,
+    /// The types of the variables by identifier, `null` without state variables
+    pub types: Option<BTreeMap<String, StorageType>>,
+}
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct TypeType {
-    #[serde(rename = "internalType")]
-    pub internal_type: Option<String>,
-    pub name: String,
+/// A state variable, or a member of a struct
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct StorageEntry {
+    #[serde(rename = "astId")]
+    pub ast_id: usize,
+    /// The contract declaring the variable, as `src/Vault.sol:Vault`
+    pub contract: String,
+    pub label: String,
+    /// The first byte of the variable in its slot, from the lowest-order byte
+    pub offset: usize,
+    /// The slot in decimal, relative to the struct for members
+    pub slot: String,
+    /// The identifier of the type in `types`
     #[serde(rename = "type")]
-    pub type_type: String,
+    pub ty: String,
 }
 
-// TODO move elsewhere
-pub trait EntryUtils {
-    fn signature(&self) -> String;
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct StorageType {
+    pub encoding: StorageEncoding,
+    /// The canonical name, e.g. `mapping(address => uint256)`
+    pub label: String,
+    /// The size in decimal, 32 for mappings, dynamic arrays, bytes and strings
+    #[serde(rename = "numberOfBytes")]
+    pub number_of_bytes: String,
+    /// The type of the keys of a mapping
+    #[serde(skip_serializing_if = "opt_none", default)]
+    pub key: Option<String>,
+    /// The type of the values of a mapping
+    #[serde(skip_serializing_if = "opt_none", default)]
+    pub value: Option<String>,
+    /// The type of the elements of an array
+    #[serde(skip_serializing_if = "opt_none", default)]
+    pub base: Option<String>,
+    /// The members of a struct
+    #[serde(skip_serializing_if = "opt_none", default)]
+    pub members: Option<Vec<StorageEntry>>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct AbiEntry {
-    pub inputs: Vec<TypeType>,
-    pub name: String,
-    pub outputs: Vec<TypeType>,
-    #[serde(rename = "stateMutability")]
-    pub state_mutability: String,
-    #[serde(rename = "type")]
-    pub entry_type: String,
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StorageEncoding {
+    /// Value types, structs and static arrays, laid out from their slot
+    Inplace,
+    /// The value of a key is at `keccak256(key . slot)`
+    Mapping,
+    /// The length is in the slot, the elements from `keccak256(slot)`
+    DynamicArray,
+    /// In the slot up to 31 bytes, from `keccak256(slot)` with the length in the slot otherwise
+    Bytes,
 }
 
-impl EntryUtils for AbiEntry {
-    fn signature(&self) -> String {
-        let mut inner_types: String = self
-            .inputs
-            .iter()
-            .map(|tt| format!("{},", tt.type_type.clone()))
-            .collect();
-        inner_types.pop();
-        format!("{0}({inner_types})", self.name)
+impl StorageLayout {
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
     }
 }
 
@@ -220,15 +419,90 @@ pub struct SolcContract {
     pub evm: Option<EvmOutput>,
 }
 
+// only the top-level nodes that we need, the others fields are ignored
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AstNode {
+    #[serde(rename = "nodeType")]
+    pub node_type: String,
+    pub name: Option<String>,
+    // "start:length:source_index"
+    pub src: String,
+    #[serde(rename = "contractKind")]
+    pub contract_kind: Option<String>,
+    #[serde(rename = "abstract")]
+    pub is_abstract: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SourceUnit {
+    pub nodes: Vec<AstNode>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SourceOut {
+    pub id: i32,
+    pub ast: Option<SourceUnit>,
+}
+
 // https://docs.soliditylang.org/en/latest/using-the-compiler.html#output-description
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SolcOut {
-    pub errors: Option<Vec<OutError<SourceLocation, ErrType>>>,
-    pub sources: HashMap<String, HashMap<String, i32>>,
+    /// Diagnostics of the compilation, holding only warnings and infos when it succeeds
+    pub errors: Option<Vec<SolcError>>,
+    // missing from the output of Yul
+    #[serde(default)]
+    pub sources: HashMap<String, SourceOut>,
     // "sourceFile.sol" { "ContractName" { ... } }
     pub contracts: Option<HashMap<String, HashMap<String, SolcContract>>>,
 }
 
+impl SolcOut {
+    /// Contracts of a source file, in definition order when the AST is available
+    pub fn contract_names(&self, file: &str) -> Vec<String> {
+        let ast = self
+            .sources
+            .get(file)
+            .and_then(|source| source.ast.as_ref());
+        match ast {
+            Some(ast) => ast
+                .nodes
+                .iter()
+                .filter(|node| node.node_type == "ContractDefinition")
+                .filter_map(|node| node.name.clone())
+                .collect(),
+            None => {
+                let mut names: Vec<_> = self
+                    .contracts
+                    .iter()
+                    .flat_map(|contracts| contracts.get(file))
+                    .flat_map(|contracts| contracts.keys().cloned())
+                    .collect();
+                names.sort();
+                names
+            }
+        }
+    }
+
+    /// The sole contract of a source file, or the last deployable one
+    pub fn default_contract(&self, file: &str) -> Option<String> {
+        let names = self.contract_names(file);
+        if names.len() == 1 {
+            return names.into_iter().next();
+        }
+
+        let nodes = &self.sources.get(file)?.ast.as_ref()?.nodes;
+        nodes
+            .iter()
+            .rev()
+            .find(|node| {
+                node.node_type == "ContractDefinition"
+                    && node.contract_kind.as_deref() == Some("contract")
+                    && node.is_abstract != Some(true)
+            })
+            .and_then(|node| node.name.clone())
+    }
+}
+
 impl From<SolcOut> for CompilerOutput {
     fn from(val: SolcOut) -> Self {
         CompilerOutput::Solc(val)
@@ -246,76 +520,105 @@ impl TryFrom<CompilerOutput> for SolcOut {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct JsonError {
-    pub message: String,
+fn opt_none<T>(val: &Option<T>) -> bool {
+    val.is_none()
 }
 
-impl From<JsonError> for CompilerError {
-    fn from(val: JsonError) -> Self {
-        CompilerError::BinError(BinError::Json(val))
+impl Solc {
+    /// Runs the compiler on the standard JSON input, returning its raw output
+    pub fn raw_output(&self) -> Result<String, CompilerError> {
+        let input = serde_json::to_string(&self).expect("the input serializes");
+        execute(&self.binary, &["--standard-json"], Some(&input))
     }
-}
 
-impl Error for JsonError {}
+    /// Deserializes the raw output of the compiler
+    pub fn parse(raw_out: &str) -> Result<CompilerOutput, CompilerError> {
+        let solc_out = serde_json::from_str::<SolcOut>(raw_out)
+            .map_err(|err| CompilerError::deserialize("solc", raw_out, err))?;
 
-impl fmt::Display for JsonError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.message)
-    }
-}
+        if solc_out.errors.iter().flatten().any(OutError::is_error) {
+            return Err(Diagnostics::Solc(solc_out.errors.unwrap_or_default()).into());
+        }
 
-fn opt_none<T>(val: &Option<T>) -> bool {
-    val.is_none()
+        Ok(solc_out.into())
+    }
 }
 
 impl RunCompiler for Solc {
     fn run(&self) -> Result<CompilerOutput, CompilerError> {
-        let input = serde_json::to_string(&self).unwrap();
-
-        let mut child = Command::new("solc")
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .arg("--standard-json")
-            .spawn()
-            .unwrap();
-
-        let child_stdin = child.stdin.as_mut().unwrap();
-        child_stdin.write_all(input.as_bytes()).unwrap();
-        let output = child.wait_with_output().unwrap();
-
-        let stdout = output.stdout;
-        let raw_out = String::from_utf8(stdout).unwrap();
-
-        if !output.status.success() {
-            return Err(JsonError {
-                message: String::from_utf8(output.stderr).unwrap(),
-            }
-            .into());
-        }
+        Self::parse(&self.raw_output()?)
+    }
+}
 
-        let solc_out = if let Ok(solc_out) = serde_json::from_str::<SolcOut>(&raw_out) {
-            solc_out
-        } else {
-            panic!("failed to deserialize solc output: {}", &raw_out);
+#[cfg(test)]
+mod tests {
+    use super::{
+        BytecodeHash, MetadataSettings, Optimizer, OptimizerDetails, Settings, SolcOut,
+        SolcSettings,
+    };
+    use std::collections::HashMap;
+
+    #[test]
+    fn serialize_settings() {
+        let settings = Settings {
+            remappings: None,
+            output_selection: HashMap::new(),
+            compilation: SolcSettings {
+                optimizer: Some(Optimizer {
+                    runs: 1000,
+                    details: Some(OptimizerDetails {
+                        jumpdest_remover: Some(false),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                }),
+                via_ir: Some(true),
+                metadata: Some(MetadataSettings {
+                    bytecode_hash: Some(BytecodeHash::None),
+                    append_cbor: None,
+                }),
+                ..Default::default()
+            },
         };
+        assert_eq!(
+            serde_json::to_value(settings).unwrap(),
+            serde_json::json!({
+                "outputSelection": {},
+                "optimizer": {
+                    "enabled": true,
+                    "runs": 1000,
+                    "details": { "jumpdestRemover": false }
+                },
+                "viaIR": true,
+                "metadata": { "bytecodeHash": "none" }
+            })
+        );
+    }
 
-        if let Some(errs) = &solc_out.errors {
-            if !errs.is_empty() {
-                let first_err = errs.first().unwrap();
-                let message = first_err
-                    .formatted_message
-                    .as_ref()
-                    .unwrap_or(&first_err.message);
-                Err(JsonError {
-                    message: message.to_string(),
+    #[test]
+    fn default_contract() {
+        let out: SolcOut = serde_json::from_str(
+            r#"{
+                "sources": {
+                    "a.sol": {
+                        "id": 0,
+                        "ast": {
+                            "nodeType": "SourceUnit",
+                            "nodes": [
+                                { "nodeType": "PragmaDirective", "src": "0:23:0" },
+                                { "nodeType": "ContractDefinition", "name": "Vault", "src": "24:10:0", "contractKind": "contract", "abstract": false },
+                                { "nodeType": "ContractDefinition", "name": "Base", "src": "35:10:0", "contractKind": "contract", "abstract": true },
+                                { "nodeType": "ContractDefinition", "name": "IVault", "src": "46:10:0", "contractKind": "interface", "abstract": false }
+                            ]
+                        }
+                    }
                 }
-                .into())
-            } else {
-                Ok(solc_out.into())
-            }
-        } else {
-            Ok(solc_out.into())
-        }
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(out.contract_names("a.sol"), ["Vault", "Base", "IVault"]);
+        assert_eq!(out.default_contract("a.sol").as_deref(), Some("Vault"));
+        assert_eq!(out.default_contract("b.sol"), None);
     }
 }
@@ -1,15 +1,362 @@
-pub fn internal_to_type(_type: &str) -> String {
-    if _type.ends_with(']') {
-        unimplemented!("{_type} is an invalid type for now");
-    }
-    if _type.starts_with("tuple") {
-        unimplemented!("{_type} variants are not yet unpacked");
-    }
-    if _type.starts_with("uint") {
-        // TODO clamp types
-        // String::from("U256")
-        String::from("u128")
-    } else {
-        todo!("{_type} missing !");
+use crate::compilers::artifact::{AbiEntry, TypeType};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// A Solidity struct, to be generated as a Rust struct deriving the ABI traits
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructDef {
+    pub name: String,
+    /// Solidity field name and Rust type
+    pub fields: Vec<(String, String)>,
+}
+
+/// Structs met while mapping types, by Rust name
+#[derive(Debug, Default)]
+pub struct Structs {
+    defs: BTreeMap<String, StructDef>,
+    /// Names of different structs, which are all qualified with their contract
+    clashes: BTreeSet<String>,
+}
+
+impl Structs {
+    /// Finds the structs of the `abi` sharing a name up front, so that their Rust names don't
+    /// depend on the order of the entries
+    pub fn new<'a>(abi: impl IntoIterator<Item = &'a AbiEntry>) -> Self {
+        let mut layouts = BTreeMap::<String, BTreeSet<Vec<(&str, String)>>>::new();
+        let mut params: Vec<_> = abi
+            .into_iter()
+            .flat_map(|entry| entry.inputs.iter().chain(&entry.outputs))
+            .collect();
+        while let Some(param) = params.pop() {
+            let components = param.components.iter().flatten();
+            if let Some(name) = param.internal_type.as_deref().and_then(struct_name) {
+                let layout = components
+                    .clone()
+                    .map(|component| (component.name.as_str(), canonical_type(component)))
+                    .collect();
+                layouts.entry(name).or_default().insert(layout);
+            }
+            params.extend(components);
+        }
+        let clashes = layouts
+            .into_iter()
+            .filter(|(_, layouts)| layouts.len() > 1)
+            .map(|(name, _)| name)
+            .collect();
+        Structs {
+            defs: BTreeMap::new(),
+            clashes,
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&StructDef> {
+        self.defs.get(name)
+    }
+
+    pub fn insert(&mut self, def: StructDef) {
+        self.defs.insert(def.name.clone(), def);
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &StructDef> {
+        self.defs.values()
+    }
+}
+
+/// Splits `uint256[2][]` into `uint256` and the array dimensions, innermost first
+fn split_dims(ty: &str) -> Result<(&str, Vec<Option<usize>>), String> {
+    let Some(start) = ty.find('[') else {
+        return Ok((ty, vec![]));
+    };
+    let dims = ty[start..]
+        .trim_end_matches(']')
+        .split("][")
+        .map(|dim| {
+            let dim = dim.trim_start_matches('[');
+            if dim.is_empty() {
+                Ok(None)
+            } else {
+                dim.parse()
+                    .map(Some)
+                    .map_err(|_| format!("{ty} has an invalid array size"))
+            }
+        })
+        .collect::<Result<_, _>>()?;
+    Ok((&ty[..start], dims))
+}
+
+fn wrap_dims(inner: String, dims: &[Option<usize>]) -> String {
+    dims.iter().fold(inner, |inner, dim| match dim {
+        Some(size) => format!("[{inner}; {size}]"),
+        None => format!("Vec<{inner}>"),
+    })
+}
+
+fn bits(ty: &str, prefix: &str, max: usize) -> Result<usize, String> {
+    let size = &ty[prefix.len()..];
+    if size.is_empty() {
+        return Ok(max);
+    }
+    match size.parse() {
+        Ok(size) if size > 0 && size <= max && (prefix == "bytes" || size % 8 == 0) => Ok(size),
+        _ => Err(format!("{ty} is not a valid Solidity type")),
+    }
+}
+
+fn uint(bits: usize, signed: bool) -> String {
+    match (bits, signed) {
+        (8 | 16 | 32 | 64 | 128, false) => format!("u{bits}"),
+        (8 | 16 | 32 | 64 | 128, true) => format!("i{bits}"),
+        (256, false) => String::from("revm::primitives::U256"),
+        (256, true) => String::from("revm::primitives::alloy_primitives::I256"),
+        (_, false) => format!(
+            "revm::primitives::alloy_primitives::Uint<{bits}, {}>",
+            bits.div_ceil(64)
+        ),
+        (_, true) => format!(
+            "revm::primitives::alloy_primitives::Signed<{bits}, {}>",
+            bits.div_ceil(64)
+        ),
+    }
+}
+
+/// `struct Counter.Point[]` -> `Counter.Point`
+fn qualified_struct_name(internal_type: &str) -> Option<&str> {
+    let name = internal_type.strip_prefix("struct ")?;
+    Some(name.split('[').next().unwrap())
+}
+
+/// `struct Counter.Point[]` -> `Point`
+fn struct_name(internal_type: &str) -> Option<String> {
+    let name = qualified_struct_name(internal_type)?;
+    Some(name.rsplit('.').next().unwrap().to_string())
+}
+
+/// Maps a parameter of the ABI to the Rust type used in the bindings.
+/// Named structs are collected in `structs`, anonymous tuples become Rust tuples.
+pub fn internal_to_type(param: &TypeType, structs: &mut Structs) -> Result<String, String> {
+    let (base, dims) = split_dims(&param.type_type)?;
+    let inner = match base {
+        "bool" => String::from("bool"),
+        "address" => String::from("revm::primitives::Address"),
+        "string" => String::from("String"),
+        "bytes" => String::from("revm::primitives::Bytes"),
+        // an address followed by a function selector
+        "function" => String::from("revm::primitives::FixedBytes<24>"),
+        "tuple" => {
+            let components = param
+                .components
+                .as_ref()
+                .ok_or_else(|| format!("{} is missing its components", param.name))?;
+            let fields = components
+                .iter()
+                .map(|component| {
+                    internal_to_type(component, structs).map(|ty| (component.name.clone(), ty))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+
+            match param
+                .internal_type
+                .as_deref()
+                .and_then(qualified_struct_name)
+            {
+                Some(qualified) => {
+                    let mut name = qualified.rsplit('.').next().unwrap().to_string();
+                    // `Geometry.Point` is `GeometryPoint` when another contract has a `Point`
+                    if structs.clashes.contains(&name) {
+                        name = qualified.replace('.', "");
+                    }
+                    if let Some(def) = structs.get(&name) {
+                        if def.fields != fields {
+                            return Err(format!(
+                                "struct {qualified} is bound as {name}, the name of another struct"
+                            ));
+                        }
+                    }
+                    structs.insert(StructDef {
+                        name: name.clone(),
+                        fields,
+                    });
+                    name
+                }
+                None => {
+                    let types: Vec<_> = fields.into_iter().map(|(_, ty)| ty).collect();
+                    format!("({},)", types.join(", "))
+                }
+            }
+        }
+        ty if ty.starts_with("uint") => uint(bits(ty, "uint", 256)?, false),
+        ty if ty.starts_with("int") => uint(bits(ty, "int", 256)?, true),
+        ty if ty.starts_with("bytes") => {
+            format!("revm::primitives::FixedBytes<{}>", bits(ty, "bytes", 32)?)
+        }
+        ty => return Err(format!("{ty} is not supported")),
+    };
+
+    Ok(wrap_dims(inner, &dims))
+}
+
+/// The type as written in a function signature, with the tuples expanded
+pub fn canonical_type(param: &TypeType) -> String {
+    match param.type_type.strip_prefix("tuple") {
+        Some(dims) => {
+            let components: Vec<_> = param
+                .components
+                .iter()
+                .flatten()
+                .map(canonical_type)
+                .collect();
+            format!("({}){dims}", components.join(","))
+        }
+        None => param.type_type.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{canonical_type, internal_to_type, Structs};
+    use crate::compilers::solidity::solc::{AbiEntry, TypeType};
+
+    fn param(type_type: &str) -> TypeType {
+        TypeType {
+            internal_type: None,
+            name: String::new(),
+            type_type: type_type.to_string(),
+            components: None,
+            indexed: false,
+        }
+    }
+
+    fn map(type_type: &str) -> String {
+        internal_to_type(&param(type_type), &mut Structs::default()).unwrap()
+    }
+
+    #[test]
+    fn elementary_types() {
+        assert_eq!(map("uint8"), "u8");
+        assert_eq!(map("uint256"), "revm::primitives::U256");
+        assert_eq!(
+            map("uint24"),
+            "revm::primitives::alloy_primitives::Uint<24, 1>"
+        );
+        assert_eq!(map("int128"), "i128");
+        assert_eq!(
+            map("int72"),
+            "revm::primitives::alloy_primitives::Signed<72, 2>"
+        );
+        assert_eq!(map("bytes4"), "revm::primitives::FixedBytes<4>");
+        assert_eq!(map("bytes"), "revm::primitives::Bytes");
+        assert_eq!(map("address"), "revm::primitives::Address");
+        assert_eq!(map("string"), "String");
+        assert!(internal_to_type(&param("uint7"), &mut Structs::default()).is_err());
+        assert!(internal_to_type(&param("bytes33"), &mut Structs::default()).is_err());
+    }
+
+    #[test]
+    fn arrays() {
+        assert_eq!(map("bool[]"), "Vec<bool>");
+        assert_eq!(map("uint8[2][]"), "Vec<[u8; 2]>");
+        assert_eq!(map("string[][3]"), "[Vec<String>; 3]");
+    }
+
+    #[test]
+    fn tuples() {
+        let point = TypeType {
+            internal_type: Some(String::from("struct Geometry.Point[]")),
+            name: String::from("points"),
+            type_type: String::from("tuple[]"),
+            components: Some(vec![
+                TypeType {
+                    name: String::from("x"),
+                    ..param("uint256")
+                },
+                TypeType {
+                    name: String::from("tags"),
+                    ..param("string[]")
+                },
+            ]),
+            indexed: false,
+        };
+        let mut structs = Structs::default();
+        assert_eq!(
+            internal_to_type(&point, &mut structs).unwrap(),
+            "Vec<Point>"
+        );
+        assert_eq!(
+            structs.get("Point").unwrap().fields,
+            vec![
+                (String::from("x"), String::from("revm::primitives::U256")),
+                (String::from("tags"), String::from("Vec<String>")),
+            ]
+        );
+        assert_eq!(canonical_type(&point), "(uint256,string[])[]");
+
+        let anonymous = TypeType {
+            internal_type: None,
+            ..point
+        };
+        assert_eq!(
+            internal_to_type(&anonymous, &mut Structs::default()).unwrap(),
+            "Vec<(revm::primitives::U256, Vec<String>,)>"
+        );
+    }
+
+    fn point(contract: &str, fields: &[&str]) -> TypeType {
+        TypeType {
+            internal_type: Some(format!("struct {contract}.Point")),
+            name: String::from("point"),
+            type_type: String::from("tuple"),
+            components: Some(
+                fields
+                    .iter()
+                    .map(|name| TypeType {
+                        name: name.to_string(),
+                        ..param("uint256")
+                    })
+                    .collect(),
+            ),
+            indexed: false,
+        }
+    }
+
+    fn entry(inputs: Vec<TypeType>) -> AbiEntry {
+        AbiEntry {
+            inputs,
+            name: String::from("f"),
+            outputs: vec![],
+            state_mutability: String::from("nonpayable"),
+            entry_type: String::from("function"),
+            anonymous: false,
+        }
+    }
+
+    #[test]
+    fn clashing_structs() {
+        let x = point("X", &["x", "y"]);
+        let y = point("Y", &["x", "y", "z"]);
+        for abi in [
+            vec![entry(vec![x.clone()]), entry(vec![y.clone()])],
+            vec![entry(vec![y.clone()]), entry(vec![x.clone()])],
+        ] {
+            let mut structs = Structs::new(&abi);
+            for entry in &abi {
+                internal_to_type(&entry.inputs[0], &mut structs).unwrap();
+            }
+            let names: Vec<_> = structs.values().map(|def| def.name.as_str()).collect();
+            assert_eq!(names, ["XPoint", "YPoint"]);
+        }
+
+        // the qualified name is the one of another struct
+        let xpoint = TypeType {
+            internal_type: Some(String::from("struct XPoint")),
+            ..point("", &["z"])
+        };
+        let abi = [entry(vec![xpoint.clone(), x.clone(), y])];
+        let mut structs = Structs::new(&abi);
+        internal_to_type(&xpoint, &mut structs).unwrap();
+        assert_eq!(
+            internal_to_type(&x, &mut structs),
+            Err(String::from(
+                "struct X.Point is bound as XPoint, the name of another struct"
+            ))
+        );
     }
 }
@@ -0,0 +1,491 @@
+use crate::compilers::{
+    builder::CompilerKinds,
+    solidity::foundry::{FoundryConfig, Remapping},
+};
+use core::fmt;
+use std::{
+    collections::HashMap,
+    path::{Component, Path, PathBuf},
+};
+
+/// The source files of a compilation, named as in the standard-JSON input: relative to the root
+/// directory, with `/` separators
+#[derive(Debug, Default)]
+pub struct SourceFiles {
+    /// Name of the file holding the contracts to bind
+    pub entry: String,
+    pub sources: HashMap<String, String>,
+    /// Absolute paths of the files read, for cargo to rebuild when they change
+    pub paths: Vec<PathBuf>,
+    /// Solidity import remappings of the project, to be passed to solc
+    pub remappings: Vec<Remapping>,
+}
+
+#[derive(Debug)]
+pub enum SourceError {
+    Io { path: PathBuf, err: std::io::Error },
+    NotFound { import: String, from: String },
+    Config { path: PathBuf, reason: String },
+}
+
+impl fmt::Display for SourceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io { path, err } => write!(f, "unable to read {}: {err}", path.display()),
+            Self::NotFound { import, from } => write!(f, "{import} imported by {from} not found"),
+            Self::Config { path, reason } => write!(f, "invalid {}: {reason}", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for SourceError {}
+
+impl SourceFiles {
+    /// Reads the file at `path`, relative to `root`, and the files it imports.
+    /// Solidity files may also be given relative to the `src` directory of a Foundry project.
+    pub fn read(root: &Path, path: &str, kind: &CompilerKinds) -> Result<Self, SourceError> {
+        let config = Self::config(root, kind)?;
+
+        let mut entry = normalize(Path::new(path));
+        if matches!(kind, CompilerKinds::Solc) && !root.join(&entry).is_file() {
+            let in_src = normalize(&Path::new(&config.src).join(&entry));
+            if root.join(&in_src).is_file() {
+                entry = in_src;
+            }
+        }
+        let full_path = root.join(&entry);
+        let content = std::fs::read_to_string(&full_path).map_err(|err| SourceError::Io {
+            path: full_path.clone(),
+            err,
+        })?;
+
+        let mut files = Self::default();
+        files.paths.push(full_path);
+        files.collect(root, &config, entry, content, kind)?;
+        Ok(files)
+    }
+
+    /// Source code named `entry`, and the files it imports from `root`
+    pub fn inline(
+        root: &Path,
+        entry: &str,
+        content: String,
+        kind: &CompilerKinds,
+    ) -> Result<Self, SourceError> {
+        let config = Self::config(root, kind)?;
+        let mut files = Self::default();
+        files.collect(root, &config, entry.to_string(), content, kind)?;
+        Ok(files)
+    }
+
+    /// Solidity imports are resolved as in a Foundry project
+    fn config(root: &Path, kind: &CompilerKinds) -> Result<FoundryConfig, SourceError> {
+        match kind {
+            CompilerKinds::Solc => FoundryConfig::load(root),
+            _ => Ok(FoundryConfig::default()),
+        }
+    }
+
+    fn collect(
+        &mut self,
+        root: &Path,
+        config: &FoundryConfig,
+        entry: String,
+        content: String,
+        kind: &CompilerKinds,
+    ) -> Result<(), SourceError> {
+        self.paths.extend(config.paths.iter().cloned());
+        self.remappings = config.remappings.clone();
+        self.entry = entry.clone();
+        let mut queue = vec![(entry, content)];
+        while let Some((name, content)) = queue.pop() {
+            let imports = match kind {
+                CompilerKinds::Solc => solidity_imports(&content)
+                    .into_iter()
+                    .map(|import| resolve_solidity(root, config, &name, import))
+                    .collect::<Result<Vec<_>, _>>()?,
+                CompilerKinds::Vyper => vyper_imports(&content)
+                    .into_iter()
+                    .filter_map(|import| resolve_vyper(root, &name, import).transpose())
+                    .collect::<Result<Vec<_>, _>>()?,
+                CompilerKinds::Huff => huff_includes(&content)
+                    .into_iter()
+                    .map(|include| resolve_huff(root, &name, include))
+                    .collect::<Result<Vec<_>, _>>()?,
+                CompilerKinds::Backend(_) => vec![],
+            };
+            self.sources.insert(name, content);
+
+            for (import, path) in imports {
+                if self.sources.contains_key(&import)
+                    || queue.iter().any(|(name, _)| *name == import)
+                {
+                    continue;
+                }
+                let content = std::fs::read_to_string(&path).map_err(|err| SourceError::Io {
+                    path: path.clone(),
+                    err,
+                })?;
+                self.paths.push(path);
+                queue.push((import, content));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Resolves the `.` and `..` components, and joins with `/`
+fn normalize(path: &Path) -> String {
+    let mut parts: Vec<String> = vec![];
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                parts.pop();
+            }
+            component => parts.push(component.as_os_str().to_string_lossy().into_owned()),
+        }
+    }
+    parts.join("/")
+}
+
+/// The directory of a source name, as a path to join imports to
+fn parent(name: &str) -> &Path {
+    Path::new(name).parent().unwrap_or(Path::new(""))
+}
+
+/// Replaces the comments with spaces, leaving the string literals untouched
+pub(crate) fn strip_comments(content: &str, line_comment: &str, block_comment: bool) -> String {
+    let mut code = String::with_capacity(content.len());
+    let mut rest = content;
+    let mut quote = None;
+    while let Some(c) = rest.chars().next() {
+        match quote {
+            Some(q) => {
+                if c == '\\' {
+                    let escaped: String = rest.chars().take(2).collect();
+                    code.push_str(&escaped);
+                    rest = &rest[escaped.len()..];
+                    continue;
+                }
+                if c == q || c == '\n' {
+                    quote = None;
+                }
+            }
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if rest.starts_with(line_comment) => {
+                let end = rest.find('\n').unwrap_or(rest.len());
+                code.push_str(&" ".repeat(end));
+                rest = &rest[end..];
+                continue;
+            }
+            None if block_comment && rest.starts_with("/*") => {
+                let end = rest.find("*/").map_or(rest.len(), |end| end + 2);
+                code.extend(
+                    rest[..end]
+                        .chars()
+                        .map(|c| if c == '\n' { '\n' } else { ' ' }),
+                );
+                rest = &rest[end..];
+                continue;
+            }
+            None => {}
+        }
+        code.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+    code
+}
+
+/// The paths of the `import` directives, in all their forms:
+/// `import "a.sol";`, `import "a.sol" as a;`, `import * as a from "a.sol";` and
+/// `import {A, B as C} from "a.sol";`
+fn solidity_imports(content: &str) -> Vec<String> {
+    let code = strip_comments(content, "//", true);
+    code.match_indices("import")
+        .filter(|(i, _)| {
+            // the keyword starts a statement
+            let before = code[..*i].trim_end();
+            before.is_empty() || before.ends_with([';', '{', '}'])
+        })
+        .map(|(i, keyword)| &code[i + keyword.len()..])
+        .filter(|rest| rest.starts_with(|c: char| c.is_whitespace() || "\"'{*".contains(c)))
+        .filter_map(|rest| {
+            let statement = &rest[..rest.find(';')?];
+            let start = statement.find(['"', '\''])?;
+            let quote = statement[start..].chars().next()?;
+            let len = statement[start + 1..].find(quote)?;
+            Some(statement[start + 1..start + 1 + len].to_string())
+        })
+        .collect()
+}
+
+/// The source unit name of an import, after the remappings, and its file
+fn resolve_solidity(
+    root: &Path,
+    config: &FoundryConfig,
+    from: &str,
+    import: String,
+) -> Result<(String, PathBuf), SourceError> {
+    let name = if import.starts_with("./") || import.starts_with("../") {
+        normalize(&parent(from).join(&import))
+    } else {
+        normalize(Path::new(&import))
+    };
+    let name = config.remap(from, &name);
+
+    match config.locate(root, &name) {
+        Some(path) => Ok((name, path)),
+        None => Err(SourceError::NotFound {
+            import,
+            from: from.to_string(),
+        }),
+    }
+}
+
+/// The dotted paths of the `import a.b as c` and `from a.b import c` statements
+fn vyper_imports(content: &str) -> Vec<String> {
+    let code = strip_comments(content, "#", false);
+    code.lines()
+        .filter_map(|line| {
+            let words: Vec<_> = line.split_whitespace().collect();
+            match words.as_slice() {
+                ["import", module, ..] => Some(module.to_string()),
+                ["from", package, "import", module, ..] if package.ends_with('.') => {
+                    Some(format!("{package}{module}"))
+                }
+                ["from", package, "import", module, ..] => Some(format!("{package}.{module}")),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Vyper modules are files relative to the root or, when the path starts with dots, to the
+/// importing file. The built-in interfaces (`vyper.interfaces`, `ethereum.ercs`) have no file.
+fn resolve_vyper(
+    root: &Path,
+    from: &str,
+    import: String,
+) -> Result<Option<(String, PathBuf)>, SourceError> {
+    let module = import.trim_start_matches('.');
+    let levels = import.len() - module.len();
+    if levels == 0 && (module.starts_with("vyper.") || module.starts_with("ethereum.")) {
+        return Ok(None);
+    }
+
+    let mut dir = if levels == 0 {
+        PathBuf::new()
+    } else {
+        parent(from).to_path_buf()
+    };
+    for _ in 1..levels {
+        dir.push("..");
+    }
+    let path = dir.join(module.replace('.', "/"));
+
+    ["vy", "vyi", "json"]
+        .iter()
+        .map(|extension| normalize(&path.with_extension(extension)))
+        .find(|name| root.join(name).is_file())
+        .map(|name| Some((name.clone(), root.join(name))))
+        .ok_or(SourceError::NotFound {
+            import,
+            from: from.to_string(),
+        })
+}
+
+/// The paths of the `#include "a.huff"` directives
+fn huff_includes(content: &str) -> Vec<String> {
+    let code = strip_comments(content, "//", true);
+    code.lines()
+        .filter_map(|line| {
+            let rest = line.trim_start().strip_prefix("#include")?.trim();
+            let path = rest.strip_prefix('"')?;
+            Some(path[..path.find('"')?].to_string())
+        })
+        .collect()
+}
+
+/// Huff includes are relative to the including file
+fn resolve_huff(
+    root: &Path,
+    from: &str,
+    include: String,
+) -> Result<(String, PathBuf), SourceError> {
+    let name = normalize(&parent(from).join(&include));
+    let path = root.join(&name);
+    if path.is_file() {
+        Ok((name, path))
+    } else {
+        Err(SourceError::NotFound {
+            import: include,
+            from: from.to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{huff_includes, solidity_imports, vyper_imports, SourceFiles};
+    use crate::compilers::builder::CompilerKinds;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn parse_imports() {
+        let solidity = r#"
+            import "./A.sol";
+            // import "./Commented.sol";
+            import {B, C as D} from "lib/B.sol";
+            /* import "./Commented.sol"; */
+            import * as E from '../E.sol';
+            contract F { string s = "import \"./String.sol\";"; }
+        "#;
+        assert_eq!(
+            solidity_imports(solidity),
+            ["./A.sol", "lib/B.sol", "../E.sol"]
+        );
+
+        let vyper = "
+from vyper.interfaces import ERC20
+import interfaces.Token as Token
+from . import sibling
+from .. import parent # import nothing
+";
+        assert_eq!(
+            vyper_imports(vyper),
+            [
+                "vyper.interfaces.ERC20",
+                "interfaces.Token",
+                ".sibling",
+                "..parent"
+            ]
+        );
+
+        let huff = r#"
+            #include "./utils/Ownable.huff"
+            // #include "./Commented.huff"
+            #include "../lib/Math.huff"
+            #define macro MAIN() = takes(0) returns(0) {}
+        "#;
+        assert_eq!(
+            huff_includes(huff),
+            ["./utils/Ownable.huff", "../lib/Math.huff"]
+        );
+    }
+
+    #[test]
+    fn read_transitive_imports() {
+        let root = tempdir().unwrap();
+        fs::create_dir_all(root.path().join("src/utils")).unwrap();
+        fs::write(
+            root.path().join("src/Vault.sol"),
+            r#"import "./utils/Math.sol"; contract Vault {}"#,
+        )
+        .unwrap();
+        fs::write(
+            root.path().join("src/utils/Math.sol"),
+            r#"import "../Vault.sol"; import "src/Token.sol"; library Math {}"#,
+        )
+        .unwrap();
+        fs::write(root.path().join("src/Token.sol"), "contract Token {}").unwrap();
+
+        let files =
+            SourceFiles::read(root.path(), "./src/Vault.sol", &CompilerKinds::Solc).unwrap();
+        assert_eq!(files.entry, "src/Vault.sol");
+        let mut names: Vec<_> = files.sources.keys().collect();
+        names.sort();
+        assert_eq!(
+            names,
+            ["src/Token.sol", "src/Vault.sol", "src/utils/Math.sol"]
+        );
+        assert_eq!(files.paths.len(), 3);
+
+        let err = SourceFiles::inline(
+            root.path(),
+            "source_code.sol",
+            String::from(r#"import "./Missing.sol";"#),
+            &CompilerKinds::Solc,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "./Missing.sol imported by source_code.sol not found"
+        );
+
+        fs::create_dir_all(root.path().join("contracts/interfaces")).unwrap();
+        fs::write(
+            root.path().join("contracts/Crowdfund.vy"),
+            "from vyper.interfaces import ERC20\nfrom .interfaces import Token\n",
+        )
+        .unwrap();
+        fs::write(root.path().join("contracts/interfaces/Token.vyi"), "").unwrap();
+
+        let files = SourceFiles::read(root.path(), "contracts/Crowdfund.vy", &CompilerKinds::Vyper)
+            .unwrap();
+        assert!(files.sources.contains_key("contracts/interfaces/Token.vyi"));
+
+        fs::create_dir_all(root.path().join("contracts/utils")).unwrap();
+        fs::write(
+            root.path().join("contracts/Store.huff"),
+            "#include \"./utils/Ownable.huff\"\n",
+        )
+        .unwrap();
+        fs::write(
+            root.path().join("contracts/utils/Ownable.huff"),
+            "#include \"../Store.huff\"\n",
+        )
+        .unwrap();
+
+        let files =
+            SourceFiles::read(root.path(), "contracts/Store.huff", &CompilerKinds::Huff).unwrap();
+        let mut names: Vec<_> = files.sources.keys().collect();
+        names.sort();
+        assert_eq!(names, ["contracts/Store.huff", "contracts/utils/Ownable.huff"]);
+    }
+
+    #[test]
+    fn remapped_imports() {
+        let root = tempdir().unwrap();
+        let oz = root
+            .path()
+            .join("lib/openzeppelin-contracts/contracts/token");
+        fs::create_dir_all(&oz).unwrap();
+        fs::create_dir_all(root.path().join("src")).unwrap();
+        fs::write(
+            root.path().join("remappings.txt"),
+            "@openzeppelin/contracts/=lib/openzeppelin-contracts/contracts/\n",
+        )
+        .unwrap();
+        fs::write(
+            root.path().join("src/Vault.sol"),
+            r#"
+                import {ERC20} from "@openzeppelin/contracts/token/ERC20.sol";
+                import "forge-std/Test.sol";
+            "#,
+        )
+        .unwrap();
+        fs::write(oz.join("ERC20.sol"), r#"import "./IERC20.sol";"#).unwrap();
+        fs::write(oz.join("IERC20.sol"), "").unwrap();
+        fs::create_dir_all(root.path().join("lib/forge-std")).unwrap();
+        fs::write(root.path().join("lib/forge-std/Test.sol"), "").unwrap();
+
+        // relative to `src`
+        let files = SourceFiles::read(root.path(), "Vault.sol", &CompilerKinds::Solc).unwrap();
+        assert_eq!(files.entry, "src/Vault.sol");
+        let mut names: Vec<_> = files.sources.keys().collect();
+        names.sort();
+        assert_eq!(
+            names,
+            [
+                "forge-std/Test.sol",
+                "lib/openzeppelin-contracts/contracts/token/ERC20.sol",
+                "lib/openzeppelin-contracts/contracts/token/IERC20.sol",
+                "src/Vault.sol",
+            ]
+        );
+        assert_eq!(files.remappings.len(), 1);
+        assert!(files.paths.contains(&root.path().join("remappings.txt")));
+    }
+}
@@ -1,6 +1,22 @@
-use super::{huff::huffc::HuffcOut, solidity::solc::SolcOut, vyper::vyperc::VypercOut};
+use super::{
+    artifact::{AbiEntry, Artifact, ArtifactFormat, Artifacts},
+    backend::{self, BackendInput, CommandBackend, CompilerBackend},
+    builder::{execute, Compiler, CompilerError, CompilerKinds, CompilerOutput, Diagnostics},
+    huff::huffc::{HuffSettings, Huffc, HuffcBuilder, HuffcOut},
+    solidity::{
+        solc::{CodeRange, EntryUtils, Solc, SolcOut},
+        types::canonical_type,
+    },
+    vyper::vyperc::{Vyperc, VypercOut},
+};
 use serde::Deserialize;
-use std::{fs, path::PathBuf};
+use serde_json::Value;
+use std::{
+    collections::{BTreeMap, HashMap},
+    error::Error,
+    fs, io,
+    path::{Path, PathBuf},
+};
 
 #[test]
 fn deserialize_solc_output() {
@@ -17,6 +33,368 @@ fn deserialize_vyper_output() {
     test_compiler_output::<VypercOut>("vyperc");
 }
 
+#[test]
+fn huffc_abi() {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("src/test-data/huffc/output/simple_store.json");
+    let out: HuffcOut = serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap();
+
+    let entries: Vec<_> = out
+        .abi
+        .entries()
+        .iter()
+        .map(|entry| {
+            format!(
+                "{} {} {}",
+                entry.entry_type,
+                entry.signature(),
+                entry.state_mutability
+            )
+        })
+        .collect();
+    assert_eq!(
+        entries,
+        [
+            "constructor (address) nonpayable",
+            "function getValue() view",
+            "function setPairs((address,uint256)[]) payable",
+            "function setValue(uint256) nonpayable",
+            "event ValueSet(uint256) ",
+            "error Unauthorized(address) ",
+        ]
+    );
+}
+
+#[test]
+fn artifacts() {
+    let output = |compiler: &str, file: &str| {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join(format!("src/test-data/{compiler}/output/{file}"))
+    };
+
+    let content = fs::read_to_string(output("solc", "abi.json")).unwrap();
+    let artifacts = Solc::parse(&content).unwrap().artifacts();
+    let counter = &artifacts[0];
+    assert_eq!(counter.name, "Counter");
+    assert!(!counter.bytecode.starts_with("0x") && !counter.bytecode.is_empty());
+    assert_eq!(
+        counter
+            .method_identifiers
+            .get("setNumber(uint256)")
+            .map(String::as_str),
+        Some("3fb5c1cb")
+    );
+
+    let content = fs::read_to_string(output("vyperc", "crowdfund.json")).unwrap();
+    let crowdfund = Vyperc::parse(&content)
+        .unwrap()
+        .artifact("source_code.vy", "source_code")
+        .unwrap();
+    assert_eq!(crowdfund.abi.len(), 7);
+    assert!(crowdfund.method_identifiers.contains_key("participate()"));
+
+    let content = fs::read_to_string(output("huffc", "simple_store.json")).unwrap();
+    let artifacts = Huffc::parse(&content).unwrap().artifacts();
+    assert_eq!(artifacts.len(), 1);
+    assert_eq!(artifacts[0].name, "simple_store");
+    assert_eq!(artifacts[0].deployed_bytecode, "5f3560e01c");
+    assert_eq!(
+        artifacts[0]
+            .method_identifiers
+            .get("getValue()")
+            .map(String::as_str),
+        Some("20965255")
+    );
+}
+
+#[test]
+fn huffc_args() {
+    let huffc = HuffcBuilder::default()
+        .artifacts(true)
+        .input(String::from("src/Store.huff"))
+        .output(String::from("out.json"))
+        .settings(HuffSettings {
+            constructor_args: vec![String::from("0x01"), String::from("42")],
+            constants: [("FEE", "0x64"), ("OWNER", "0x02")]
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .into(),
+            alt_main: Some(String::from("MAIN_V2")),
+            alt_constructor: None,
+            evm_version: Some(String::from("paris")),
+        })
+        .build()
+        .unwrap();
+    assert_eq!(
+        huffc.args().join(" "),
+        "src/Store.huff -a -o out.json -m MAIN_V2 -e paris -c FEE=0x64 OWNER=0x02 -i 0x01 42"
+    );
+}
+
+#[test]
+fn solc_diagnostics() {
+    let path =
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/test-data/solc/output/errors.json");
+    let content = fs::read_to_string(path).unwrap();
+
+    match Solc::parse(&content) {
+        Err(CompilerError::Diagnostics(Diagnostics::Solc(errs))) => assert_eq!(errs.len(), 3),
+        res => panic!("expected the diagnostics, got {res:?}"),
+    }
+    let err = Solc::parse(&content).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "compilation failed:\nDeclarationError: Undeclared identifier.\n --> source_code.sol:10:17:\n\nDeclarationError: Undeclared identifier \"y\".\n --> source_code.sol:11:17:\n"
+    );
+
+    // warnings alone do not fail
+    let mut output: Value = serde_json::from_str(&content).unwrap();
+    output["errors"].as_array_mut().unwrap().truncate(1);
+    match Solc::parse(&output.to_string()) {
+        Ok(CompilerOutput::Solc(out)) => assert_eq!(out.errors.unwrap().len(), 1),
+        res => panic!("expected the output, got {res:?}"),
+    }
+}
+
+#[test]
+fn compiler_errors() {
+    let output = r#"{"contracts": {"source_code.sol": 42}}"#;
+    let err = Solc::parse(output).unwrap_err();
+    assert!(matches!(
+        &err,
+        CompilerError::DeserializeError { compiler: "solc", snippet, .. } if snippet == output
+    ));
+    assert!(err.source().is_some());
+
+    let err = execute(Path::new("/nonexistent/solc"), &["--version"], None).unwrap_err();
+    assert!(
+        matches!(&err, CompilerError::MissingBinary { err, .. } if err.kind() == io::ErrorKind::NotFound)
+    );
+    assert_eq!(
+        err.to_string(),
+        "`/nonexistent/solc` was not found, install it or add it to PATH"
+    );
+
+    if cfg!(unix) {
+        let err = execute(Path::new("sh"), &["-c", "echo oops >&2; exit 3"], Some("")).unwrap_err();
+        assert!(matches!(&err, CompilerError::ExitError { stderr, .. } if stderr == "oops\n"));
+    }
+}
+
+#[test]
+fn human_readable_abi() {
+    let entry: AbiEntry =
+        "function transfer(address to, uint amount) external returns (bool success);"
+            .parse()
+            .unwrap();
+    assert_eq!(entry.signature(), "transfer(address,uint256)");
+    assert_eq!(entry.inputs[0].name, "to");
+    assert_eq!(entry.outputs[0].name, "success");
+    assert_eq!(entry.state_mutability, "nonpayable");
+
+    let entry: AbiEntry =
+        "function settle((address owner, uint256[] amounts)[2] calldata orders, bytes memory) payable"
+            .parse()
+            .unwrap();
+    assert_eq!(entry.signature(), "settle((address,uint256[])[2],bytes)");
+    assert_eq!(entry.inputs[0].type_type, "tuple[2]");
+    assert_eq!(entry.state_mutability, "payable");
+
+    let entry: AbiEntry = "function get() view returns (tuple(uint8,int)[])"
+        .parse()
+        .unwrap();
+    assert_eq!(entry.state_mutability, "view");
+    assert_eq!(canonical_type(&entry.outputs[0]), "(uint8,int256)[]");
+
+    let entry: AbiEntry = "event Transfer(address indexed from, address indexed to, uint256)"
+        .parse()
+        .unwrap();
+    assert_eq!(
+        (entry.entry_type.as_str(), entry.signature()),
+        ("event", String::from("Transfer(address,address,uint256)"))
+    );
+    assert!(entry.inputs[0].indexed && !entry.inputs[2].indexed);
+    let entry: AbiEntry = "receive() external payable".parse().unwrap();
+    assert_eq!(
+        (entry.entry_type.as_str(), entry.state_mutability.as_str()),
+        ("receive", "payable")
+    );
+    let entry: AbiEntry = "constructor(address owner)".parse().unwrap();
+    assert_eq!(entry.entry_type, "constructor");
+    assert_eq!(entry.inputs.len(), 1);
+
+    for invalid in [
+        "transfer(address)",
+        "function (uint256)",
+        "function f(uint256",
+        "function f(uint256 a b)",
+        "function f() returns",
+        "function f() constant",
+    ] {
+        assert!(invalid.parse::<AbiEntry>().is_err(), "{invalid}");
+    }
+}
+
+#[test]
+fn artifact_files() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/test-data/solc/output/abi.json");
+    let raw = fs::read_to_string(path).unwrap();
+    let out: SolcOut = serde_json::from_str(&raw).unwrap();
+    let mut artifact = out.artifacts().remove(0);
+    let math = |start| {
+        BTreeMap::from([(
+            String::from("src/Math.sol"),
+            BTreeMap::from([(String::from("Math"), vec![CodeRange { start, length: 20 }])]),
+        )])
+    };
+    artifact.link_references = math(40);
+    artifact.deployed_link_references = math(8);
+
+    for format in [ArtifactFormat::Foundry, ArtifactFormat::Hardhat] {
+        let json = artifact.to_json(format).to_string();
+        let read = Artifact::from_json(&json, Path::new("out/Other.json")).unwrap();
+        assert_eq!((&read.name, &read.file), (&artifact.name, &artifact.file));
+        assert_eq!(read.bytecode, artifact.bytecode);
+        assert_eq!(read.deployed_bytecode, artifact.deployed_bytecode);
+        assert_eq!(read.method_identifiers, artifact.method_identifiers);
+        assert_eq!(read.link_references, artifact.link_references);
+        assert_eq!(
+            read.deployed_link_references,
+            artifact.deployed_link_references
+        );
+    }
+
+    // a bare ABI, named after the file
+    let abi = serde_json::to_string(&artifact.abi).unwrap();
+    let read = Artifact::from_json(&abi, Path::new("abis/Counter.json")).unwrap();
+    assert_eq!(read.name, "Counter");
+    assert!(read.bytecode.is_empty());
+    assert_eq!(read.method_identifiers, artifact.method_identifiers);
+}
+
+#[test]
+fn command_backends() {
+    if !cfg!(unix) {
+        return;
+    }
+    let input = BackendInput {
+        sources: [(
+            String::from("src/Answer.dsl"),
+            String::from("0x602a60005260206000f3\n"),
+        )]
+        .into(),
+        entry: String::from("src/Answer.dsl"),
+        settings: Default::default(),
+        version: None,
+    };
+    let backend = |command: &[&str]| CommandBackend {
+        name: String::from("dsl"),
+        extensions: vec![String::from("dsl")],
+        command: command.iter().map(|arg| arg.to_string()).collect(),
+    };
+
+    // hex bytecode printed for the entry file
+    let out = backend(&["cat", "{input}"]).compile(&input).unwrap();
+    assert!(out.errors.is_empty());
+    assert_eq!(out.artifacts.len(), 1);
+    assert_eq!(out.artifacts[0].name, "Answer");
+    assert_eq!(out.artifacts[0].file, "src/Answer.dsl");
+    assert_eq!(out.artifacts[0].bytecode, "602a60005260206000f3");
+
+    // `.bin` files and their ABI written to the output directory
+    let script = r#"mkdir "$1/Answer" && cp "$0" "$1/Answer/Answer.bin" && echo '[{"type":"function","name":"answer","inputs":[],"outputs":[{"name":"","type":"uint256"}],"stateMutability":"view"}]' > "$1/Answer/Answer_abi.json""#;
+    let out = backend(&["sh", "-c", script, "{input}", "{out}"])
+        .compile(&input)
+        .unwrap();
+    assert_eq!(out.artifacts.len(), 1);
+    assert_eq!(out.artifacts[0].bytecode, "602a60005260206000f3");
+    assert_eq!(out.artifacts[0].abi.len(), 1);
+    assert_eq!(out.artifacts[0].method_identifiers["answer()"], "85bb7d69");
+
+    let out = backend(&["echo", "not bytecode"]).compile(&input).unwrap();
+    assert_eq!(
+        out.errors[0].message,
+        "`echo` output for Answer is not hex bytecode"
+    );
+    let raw = serde_json::to_string(&out).unwrap();
+    assert!(matches!(
+        backend::BackendOutput::parse(&raw),
+        Err(CompilerError::Diagnostics(Diagnostics::Solc(errs))) if errs.len() == 1
+    ));
+}
+
+#[test]
+fn backend_registry() {
+    assert!(backend::get("yul").is_some());
+    assert_eq!(backend::get("fe").unwrap().extensions(), ["fe"]);
+    assert!(backend::get("mydsl").is_none());
+
+    backend::register(CommandBackend {
+        name: String::from("registered"),
+        extensions: vec![],
+        command: vec![String::from("registeredc")],
+    });
+    assert_eq!(
+        backend::get("registered").unwrap().command(),
+        ["registeredc"]
+    );
+
+    let root = tempfile::tempdir().unwrap();
+    assert!(backend::load_config(root.path()).unwrap().path.is_none());
+
+    fs::write(
+        root.path().join("rustry.toml"),
+        "[backends.mydsl]\ncommand = [\"mydslc\", \"{input}\"]\nextensions = [\"mydsl\"]\n",
+    )
+    .unwrap();
+    let config = backend::load_config(root.path()).unwrap();
+    assert_eq!(config.path, Some(root.path().join("rustry.toml")));
+    let mydsl = config.get("mydsl").unwrap();
+    assert_eq!(mydsl.name(), "mydsl");
+    assert_eq!(mydsl.extensions(), ["mydsl"]);
+    assert_eq!(mydsl.command(), ["mydslc", "{input}"]);
+    assert!(config.get("yul").is_some());
+    // not seen by the other crates
+    assert!(backend::get("mydsl").is_none());
+
+    fs::write(
+        root.path().join("rustry.toml"),
+        "[backends.empty]\ncommand = []\n",
+    )
+    .unwrap();
+    assert!(backend::load_config(root.path()).is_err());
+}
+
+#[test]
+fn backend_arguments_invalidate_the_cache() {
+    let root = tempfile::tempdir().unwrap();
+    let compile = |code: &str| {
+        fs::write(
+            root.path().join("rustry.toml"),
+            format!("[backends.echo_args]\ncommand = [\"echo\", \"{code}\"]\n"),
+        )
+        .unwrap();
+        let config = backend::load_config(root.path()).unwrap();
+        let out = Compiler {
+            sources: HashMap::from([(String::from("Answer.dsl"), String::new())]),
+            entry: String::from("Answer.dsl"),
+            kind: CompilerKinds::Backend(String::from("echo_args")),
+            remappings: vec![],
+            settings: Default::default(),
+            version: None,
+            huff: HuffSettings::default(),
+        }
+        .run_backend(&*config.get("echo_args").unwrap())
+        .unwrap();
+        match out {
+            CompilerOutput::Backend(out) => out.artifacts[0].bytecode.clone(),
+            _ => unreachable!(),
+        }
+    };
+    assert_eq!(compile("6001"), "6001");
+    // same input, the command changed
+    assert_eq!(compile("6002"), "6002");
+}
+
 fn test_compiler_output<T: for<'a> Deserialize<'a>>(compiler: &str) {
     let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
     d.push(format!("src/test-data/{compiler}/output/"));
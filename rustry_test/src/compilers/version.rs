@@ -0,0 +1,485 @@
+use crate::compilers::{builder::CompilerKinds, sources::strip_comments};
+use core::fmt;
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    path::{Path, PathBuf},
+    process::Command,
+    str::FromStr,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Version {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+}
+
+impl Version {
+    pub fn new(major: u64, minor: u64, patch: u64) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+        }
+    }
+}
+
+impl FromStr for Version {
+    type Err = String;
+
+    /// `0.8.20`, ignoring a `v` prefix and the `+commit` or `-pre` suffixes
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let version = s.trim().trim_start_matches('v');
+        let version = version.split(['+', '-']).next().unwrap();
+        let parts = version
+            .split('.')
+            .map(|part| part.parse::<u64>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| format!("invalid version `{s}`"))?;
+        match parts.as_slice() {
+            [major, minor, patch] => Ok(Self::new(*major, *minor, *patch)),
+            _ => Err(format!(
+                "invalid version `{s}`, expected `major.minor.patch`"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Eq,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Comparator {
+    op: Op,
+    version: Version,
+}
+
+impl Comparator {
+    fn matches(&self, version: &Version) -> bool {
+        let ordering = version.cmp(&self.version);
+        match self.op {
+            Op::Eq => ordering == Ordering::Equal,
+            Op::Lt => ordering == Ordering::Less,
+            Op::Le => ordering != Ordering::Greater,
+            Op::Gt => ordering == Ordering::Greater,
+            Op::Ge => ordering != Ordering::Less,
+        }
+    }
+}
+
+/// A version requirement, as in the pragmas: `^0.8.0`, `>=0.8.4 <0.9.0`, `0.8.20 || 0.8.21`,
+/// `0.8.0 - 0.8.10`, or Vyper's `==0.3.10` and `~=0.4.0`
+#[derive(Debug, Clone, PartialEq)]
+pub struct VersionReq {
+    /// Alternatives of comparators that must all match
+    alternatives: Vec<Vec<Comparator>>,
+    raw: String,
+}
+
+/// `0.8` -> `0.8.0` and the number of parts given
+fn partial(s: &str) -> Result<(Version, usize), String> {
+    let parts = s
+        .split('.')
+        .filter(|part| !matches!(*part, "x" | "X" | "*"))
+        .map(|part| part.parse::<u64>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| format!("invalid version `{s}`"))?;
+    match parts.as_slice() {
+        [major] => Ok((Version::new(*major, 0, 0), 1)),
+        [major, minor] => Ok((Version::new(*major, *minor, 0), 2)),
+        [major, minor, patch] => Ok((Version::new(*major, *minor, *patch), 3)),
+        _ => Err(format!("invalid version `{s}`")),
+    }
+}
+
+/// The first version above the given parts: `0.8` -> `0.9.0`
+fn bump((version, parts): (Version, usize)) -> Version {
+    match parts {
+        1 => Version::new(version.major + 1, 0, 0),
+        2 => Version::new(version.major, version.minor + 1, 0),
+        _ => Version::new(version.major, version.minor, version.patch + 1),
+    }
+}
+
+fn comparators(term: &str) -> Result<Vec<Comparator>, String> {
+    let cmp = |op, version| Comparator { op, version };
+
+    let (op, version) = term
+        .find(|c: char| c.is_ascii_digit())
+        .map(|i| term.split_at(i))
+        .ok_or_else(|| format!("invalid version requirement `{term}`"))?;
+    let parsed = partial(version)?;
+    let (base, parts) = parsed;
+
+    Ok(match op.trim() {
+        "^" => {
+            let upper = match (base.major, base.minor, parts) {
+                (0, 0, 3) => Version::new(0, 0, base.patch + 1),
+                (0, _, 2 | 3) => Version::new(0, base.minor + 1, 0),
+                _ => Version::new(base.major + 1, 0, 0),
+            };
+            vec![cmp(Op::Ge, base), cmp(Op::Lt, upper)]
+        }
+        "~" => vec![cmp(Op::Ge, base), cmp(Op::Lt, bump((base, parts.min(2))))],
+        "~=" => vec![
+            cmp(Op::Ge, base),
+            cmp(Op::Lt, bump((base, parts.saturating_sub(1).max(1)))),
+        ],
+        "" | "=" | "==" if parts < 3 => vec![cmp(Op::Ge, base), cmp(Op::Lt, bump(parsed))],
+        "" | "=" | "==" => vec![cmp(Op::Eq, base)],
+        "<" => vec![cmp(Op::Lt, base)],
+        "<=" if parts < 3 => vec![cmp(Op::Lt, bump(parsed))],
+        "<=" => vec![cmp(Op::Le, base)],
+        ">" if parts < 3 => vec![cmp(Op::Ge, bump(parsed))],
+        ">" => vec![cmp(Op::Gt, base)],
+        ">=" => vec![cmp(Op::Ge, base)],
+        op => return Err(format!("unknown operator `{op}` in `{term}`")),
+    })
+}
+
+impl FromStr for VersionReq {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let alternatives = s
+            .split("||")
+            .map(|alternative| {
+                let mut words: Vec<String> = vec![];
+                // `>= 0.8.0` is `>=0.8.0`
+                for word in alternative
+                    .split([' ', ','])
+                    .filter(|word| !word.is_empty())
+                {
+                    match words.last_mut() {
+                        Some(last) if !last_has_version(last) => *last = format!("{last}{word}"),
+                        _ => words.push(word.to_string()),
+                    }
+                }
+
+                match words.as_slice() {
+                    [from, hyphen, to] if hyphen == "-" => {
+                        let to = partial(to)?;
+                        let upper = if to.1 < 3 {
+                            Comparator {
+                                op: Op::Lt,
+                                version: bump(to),
+                            }
+                        } else {
+                            Comparator {
+                                op: Op::Le,
+                                version: to.0,
+                            }
+                        };
+                        Ok(vec![
+                            Comparator {
+                                op: Op::Ge,
+                                version: partial(from)?.0,
+                            },
+                            upper,
+                        ])
+                    }
+                    [] => Err(format!("empty version requirement `{s}`")),
+                    words => Ok(words
+                        .iter()
+                        .map(|word| comparators(word))
+                        .collect::<Result<Vec<_>, _>>()?
+                        .concat()),
+                }
+            })
+            .collect::<Result<_, String>>()?;
+
+        Ok(Self {
+            alternatives,
+            raw: s.trim().to_string(),
+        })
+    }
+}
+
+fn last_has_version(word: &str) -> bool {
+    word == "-" || word.contains(|c: char| c.is_ascii_digit())
+}
+
+impl VersionReq {
+    pub fn matches(&self, version: &Version) -> bool {
+        self.alternatives
+            .iter()
+            .any(|comparators| comparators.iter().all(|cmp| cmp.matches(version)))
+    }
+}
+
+impl fmt::Display for VersionReq {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.raw)
+    }
+}
+
+/// The requirement of `pragma solidity ^0.8.0;`
+pub fn solidity_pragma(content: &str) -> Option<String> {
+    let code = strip_comments(content, "//", true);
+    code.split(';').find_map(|statement| {
+        let mut words = statement.split_whitespace();
+        match (words.next(), words.next()) {
+            (Some("pragma"), Some("solidity")) => Some(words.collect::<Vec<_>>().join(" ")),
+            _ => None,
+        }
+    })
+}
+
+/// The requirement of `# @version ^0.3.0` or `#pragma version ~=0.4.0`
+pub fn vyper_pragma(content: &str) -> Option<String> {
+    content.lines().find_map(|line| {
+        let comment = line.trim().strip_prefix('#')?.trim();
+        let req = comment
+            .strip_prefix("@version")
+            .or_else(|| comment.strip_prefix("pragma version"))?;
+        Some(req.trim().to_string())
+    })
+}
+
+#[derive(Debug)]
+pub enum VersionError {
+    InvalidRequirement {
+        source: String,
+        reason: String,
+    },
+    /// No compiler satisfies the requirements
+    NotInstalled {
+        compiler: &'static str,
+        requirements: Vec<(String, VersionReq)>,
+        installed: Vec<Version>,
+        dir: Option<PathBuf>,
+        /// Version of the compiler on `PATH`, used when none is installed
+        on_path: Option<Version>,
+    },
+}
+
+impl fmt::Display for VersionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidRequirement { source, reason } => write!(f, "{source}: {reason}"),
+            Self::NotInstalled {
+                compiler,
+                requirements,
+                installed,
+                dir,
+                on_path,
+            } => {
+                let requirements: Vec<_> = requirements
+                    .iter()
+                    .map(|(source, req)| format!("`{req}` ({source})"))
+                    .collect();
+                write!(
+                    f,
+                    "no installed {compiler} satisfies {}",
+                    requirements.join(" and ")
+                )?;
+                if let Some(version) = on_path {
+                    write!(f, ", the {compiler} of PATH is {version}")?;
+                } else if installed.is_empty() {
+                    write!(f, ", none is installed")?;
+                } else {
+                    let installed: Vec<_> = installed.iter().map(ToString::to_string).collect();
+                    write!(f, ", installed: {}", installed.join(", "))?;
+                }
+                match dir {
+                    Some(dir) => {
+                        write!(f, ". Install it as {}/{compiler}-<version>", dir.display())
+                    }
+                    None => write!(
+                        f,
+                        ". Install it in ~/.rustry/compilers/{compiler}-<version>"
+                    ),
+                }
+            }
+        }
+    }
+}
+
+impl std::error::Error for VersionError {}
+
+/// `RUSTRY_COMPILERS`, or `~/.rustry/compilers`
+pub fn compilers_dir() -> Option<PathBuf> {
+    if let Ok(dir) = std::env::var("RUSTRY_COMPILERS") {
+        return Some(PathBuf::from(dir));
+    }
+    let home = std::env::var("HOME")
+        .or_else(|_| std::env::var("USERPROFILE"))
+        .ok()?;
+    Some(Path::new(&home).join(".rustry").join("compilers"))
+}
+
+/// The `<compiler>-<version>` binaries of a directory, the latest first
+pub fn installed(dir: &Path, compiler: &str) -> Vec<(Version, PathBuf)> {
+    let prefix = format!("{compiler}-");
+    let mut binaries: Vec<_> = std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name();
+            let version = name.to_str()?.strip_prefix(&prefix)?;
+            let version = version.strip_suffix(".exe").unwrap_or(version);
+            Some((version.parse::<Version>().ok()?, entry.path()))
+        })
+        .collect();
+    binaries.sort_by_key(|(version, _)| std::cmp::Reverse(*version));
+    binaries
+}
+
+/// The version printed by `<bin> --version`
+pub fn binary_version(bin: &Path) -> Option<Version> {
+    let output = Command::new(bin).arg("--version").output().ok()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    stdout.split_whitespace().find_map(|word| word.parse().ok())
+}
+
+/// Picks the compiler binary satisfying the explicit `version` and the pragmas of the sources:
+/// the latest one of the compilers directory, or the one on `PATH` when none is installed there
+pub fn select(
+    kind: &CompilerKinds,
+    version: Option<&str>,
+    sources: &HashMap<String, String>,
+) -> Result<PathBuf, VersionError> {
+    let (compiler, pragma): (_, fn(&str) -> Option<String>) = match kind {
+        CompilerKinds::Solc => ("solc", solidity_pragma),
+        CompilerKinds::Vyper => ("vyper", vyper_pragma),
+        CompilerKinds::Huff => return Ok(PathBuf::from("huffc")),
+        // the backends select their compiler
+        CompilerKinds::Backend(name) => return Ok(PathBuf::from(name)),
+    };
+
+    let requirements = match version {
+        Some(version) => vec![(String::from("version option"), version.to_string())],
+        None => {
+            let mut pragmas: Vec<_> = sources
+                .iter()
+                .filter_map(|(name, content)| Some((name.clone(), pragma(content)?)))
+                .collect();
+            pragmas.sort();
+            pragmas
+        }
+    };
+    let requirements = requirements
+        .into_iter()
+        .map(|(source, req)| match req.parse::<VersionReq>() {
+            Ok(req) => Ok((source, req)),
+            Err(reason) => Err(VersionError::InvalidRequirement { source, reason }),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let satisfies = |version: &Version| requirements.iter().all(|(_, req)| req.matches(version));
+
+    let dir = compilers_dir();
+    let installed = dir
+        .as_deref()
+        .map(|dir| installed(dir, compiler))
+        .unwrap_or_default();
+    if let Some((_, bin)) = installed.iter().find(|(version, _)| satisfies(version)) {
+        return Ok(bin.clone());
+    }
+
+    let on_path = PathBuf::from(compiler);
+    if installed.is_empty() {
+        if requirements.is_empty() {
+            return Ok(on_path);
+        }
+        if let Some(version) = binary_version(&on_path) {
+            if satisfies(&version) {
+                return Ok(on_path);
+            }
+            return Err(VersionError::NotInstalled {
+                compiler,
+                requirements,
+                installed: vec![],
+                dir,
+                on_path: Some(version),
+            });
+        }
+    }
+
+    Err(VersionError::NotInstalled {
+        compiler,
+        requirements,
+        installed: installed.into_iter().map(|(version, _)| version).collect(),
+        dir,
+        on_path: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{installed, solidity_pragma, vyper_pragma, Version, VersionReq};
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn matches(req: &str, version: &str) -> bool {
+        req.parse::<VersionReq>()
+            .unwrap()
+            .matches(&version.parse::<Version>().unwrap())
+    }
+
+    #[test]
+    fn requirements() {
+        assert!(matches("0.8.20", "0.8.20"));
+        assert!(!matches("=0.8.20", "0.8.21"));
+        assert!(matches("^0.8.0", "0.8.24"));
+        assert!(!matches("^0.8.0", "0.9.0"));
+        assert!(!matches("^0.8.4", "0.8.3"));
+        assert!(matches(">=0.8.4 <0.9.0", "0.8.4"));
+        assert!(matches(">= 0.8.4 < 0.9.0", "0.8.19"));
+        assert!(!matches(">=0.8.4 <0.9.0", "0.9.0"));
+        assert!(matches("~0.8.1", "0.8.9"));
+        assert!(matches("0.7.6 || ^0.8.0", "0.7.6"));
+        assert!(matches("0.8.0 - 0.8.10", "0.8.10"));
+        assert!(!matches("0.8.0 - 0.8.10", "0.8.11"));
+        assert!(matches("0.8", "0.8.3"));
+        assert!(matches("==0.3.10", "0.3.10"));
+        assert!(matches("~=0.4.0", "0.4.3"));
+        assert!(!matches("~=0.4.0", "0.5.0"));
+        assert!("^abc".parse::<VersionReq>().is_err());
+        assert_eq!(
+            "0.8.20+commit.a1b79de6".parse::<Version>(),
+            Ok(Version::new(0, 8, 20))
+        );
+    }
+
+    #[test]
+    fn pragmas() {
+        let solidity = "// pragma solidity 0.4.0;\npragma solidity >=0.8.4 <0.9.0;\ncontract A {}";
+        assert_eq!(solidity_pragma(solidity).as_deref(), Some(">=0.8.4 <0.9.0"));
+        assert_eq!(solidity_pragma("contract A {}"), None);
+        assert_eq!(
+            vyper_pragma("# @version ^0.3.9\n").as_deref(),
+            Some("^0.3.9")
+        );
+        assert_eq!(
+            vyper_pragma("#pragma version ~=0.4.0\n").as_deref(),
+            Some("~=0.4.0")
+        );
+    }
+
+    #[test]
+    fn installed_compilers() {
+        let dir = tempdir().unwrap();
+        for name in ["solc-0.8.19", "solc-0.8.21", "solc-nightly", "vyper-0.3.10"] {
+            fs::write(dir.path().join(name), "").unwrap();
+        }
+        let versions: Vec<_> = installed(dir.path(), "solc")
+            .into_iter()
+            .map(|(version, _)| version.to_string())
+            .collect();
+        assert_eq!(versions, ["0.8.21", "0.8.19"]);
+    }
+}
@@ -1,14 +1,10 @@
 use crate::compilers::{
-    builder::{CompilerError, CompilerOutput, RunCompiler},
-    solidity::solc::{JsonError, OutError, Source, StorageLayout},
+    builder::{execute, CompilerError, CompilerOutput, Diagnostics, RunCompiler},
+    solidity::solc::{AbiEntry, CodeRange, OutError, Source},
 };
 use derive_builder::Builder;
 use serde::{Deserialize, Serialize};
-use std::{
-    collections::HashMap,
-    io::Write,
-    process::{Command, Stdio},
-};
+use std::{collections::HashMap, path::PathBuf};
 
 #[derive(Clone, Serialize)]
 pub struct Settings {
@@ -23,6 +19,8 @@ pub struct Settings {
 pub enum OutputOption {
     #[serde(rename = "evm.bytecode")]
     EvmBytecode,
+    #[serde(rename = "evm.deployedBytecode")]
+    EvmDeployedBytecode,
     #[serde(rename = "abi")]
     Abi,
 }
@@ -38,6 +36,10 @@ pub struct Vyperc {
     #[builder(default = "None")]
     pub settings: Option<Settings>,
     // pub interfaces: ...
+    /// The vyper binary to run
+    #[serde(skip_serializing)]
+    #[builder(default = "PathBuf::from(\"vyper\")")]
+    pub binary: PathBuf,
 }
 
 #[derive(Debug, Deserialize)]
@@ -51,24 +53,40 @@ pub struct SourceLocation {
 pub enum ErrType {
     AttributeError,
     JSONError,
+    SyntaxException,
+    StructureException,
+    TypeMismatch,
+    InvalidType,
+    InvalidLiteral,
+    InvalidOperation,
+    UndeclaredDefinition,
+    NamespaceCollision,
+    ImmutableViolation,
+    StateAccessViolation,
+    CallViolation,
+    ArgumentException,
+    VersionException,
+    CompilerPanic,
+    #[serde(other)]
+    Other,
 }
 
-fn constructor() -> String {
-    String::from("constructor")
-}
+pub type VyperError = OutError<SourceLocation, ErrType>;
 
 #[derive(Debug, Deserialize)]
 pub struct BytecodeData {
     pub object: String,
     pub opcodes: String,
+    #[serde(rename = "sourceMap")]
+    pub source_map: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct DeployedBytecodeData {
     #[serde(flatten)]
     pub bytecode_data: BytecodeData,
-    #[serde(rename = "immutableReferences")]
-    pub immutable_references: HashMap<String, String>, // TODO
+    #[serde(rename = "immutableReferences", default)]
+    pub immutable_references: HashMap<String, Vec<CodeRange>>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -85,20 +103,9 @@ pub struct EvmOutput {
     pub gas_estimates: Option<HashMap<String, HashMap<String, String>>>,
 }
 
-#[derive(Debug, Deserialize)]
-pub struct AbiEntry {
-    #[serde(default = "constructor")]
-    pub name: String,
-    #[serde(rename = "type")]
-    pub entry_type: String,
-    pub inputs: Option<Vec<AbiEntry>>,
-    pub outputs: Option<Vec<AbiEntry>>,
-    #[serde(rename = "stateMutability")]
-    pub state_mutability: Option<String>,
-}
-
 #[derive(Debug, Deserialize)]
 pub struct VycContract {
+    /// Vyper outputs the ABI of Solidity
     pub abi: Option<Vec<AbiEntry>>,
     #[serde(rename = "userdoc")]
     pub user_doc: Option<HashMap<String, String>>,
@@ -111,8 +118,9 @@ pub struct VycContract {
     pub ir_optimized: Option<String>,
     #[serde(rename = "irOptimizedAst")]
     pub ir_optimized_ast: Option<HashMap<String, String>>,
+    /// Not requested, Vyper lays the storage out differently from Solidity
     #[serde(rename = "storageLayout")]
-    pub storage_layout: Option<StorageLayout>,
+    pub storage_layout: Option<serde_json::Value>,
     pub evm: Option<EvmOutput>,
 }
 
@@ -120,7 +128,7 @@ pub struct VycContract {
 #[derive(Debug, Deserialize)]
 pub struct VypercOut {
     pub compiler: String,
-    pub errors: Option<Vec<OutError<SourceLocation, ErrType>>>,
+    pub errors: Option<Vec<VyperError>>,
     pub sources: Option<HashMap<String, HashMap<String, i32>>>,
     pub contracts: Option<HashMap<String, HashMap<String, VycContract>>>,
 }
@@ -142,53 +150,28 @@ impl TryFrom<CompilerOutput> for VypercOut {
     }
 }
 
-impl RunCompiler for Vyperc {
-    fn run(&self) -> Result<CompilerOutput, CompilerError> {
-        let input = serde_json::to_string(&self).unwrap();
-
-        let mut child = Command::new("vyper")
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .arg("--standard-json")
-            .spawn()
-            .unwrap();
-
-        let child_stdin = child.stdin.as_mut().unwrap();
-        child_stdin.write_all(input.as_bytes()).unwrap();
-        let output = child.wait_with_output().unwrap();
-
-        let stdout = output.stdout;
-        let raw_out = String::from_utf8(stdout).unwrap();
-
-        if !output.status.success() {
-            return Err(JsonError {
-                message: String::from_utf8(output.stderr).unwrap(),
-            }
-            .into());
-        }
+impl Vyperc {
+    /// Runs the compiler on the standard JSON input, returning its raw output
+    pub fn raw_output(&self) -> Result<String, CompilerError> {
+        let input = serde_json::to_string(&self).expect("the input serializes");
+        execute(&self.binary, &["--standard-json"], Some(&input))
+    }
+
+    /// Deserializes the raw output of the compiler
+    pub fn parse(raw_out: &str) -> Result<CompilerOutput, CompilerError> {
+        let vyc_out = serde_json::from_str::<VypercOut>(raw_out)
+            .map_err(|err| CompilerError::deserialize("vyper", raw_out, err))?;
 
-        let vyc_out = if let Ok(vyc_out) = serde_json::from_str::<VypercOut>(&raw_out) {
-            vyc_out
-        } else {
-            panic!("failed to deserialize vyc output: {}", &raw_out);
-        };
-
-        if let Some(errs) = &vyc_out.errors {
-            if !errs.is_empty() {
-                let first_err = errs.first().unwrap();
-                let message = first_err
-                    .formatted_message
-                    .as_ref()
-                    .unwrap_or(&first_err.message);
-                Err(JsonError {
-                    message: message.to_string(),
-                }
-                .into())
-            } else {
-                Ok(vyc_out.into())
-            }
-        } else {
-            Ok(vyc_out.into())
+        if vyc_out.errors.iter().flatten().any(OutError::is_error) {
+            return Err(Diagnostics::Vyper(vyc_out.errors.unwrap_or_default()).into());
         }
+
+        Ok(vyc_out.into())
+    }
+}
+
+impl RunCompiler for Vyperc {
+    fn run(&self) -> Result<CompilerOutput, CompilerError> {
+        Self::parse(&self.raw_output()?)
     }
 }
@@ -3,5 +3,6 @@
 pub mod common;
 pub mod compilers;
 pub mod prelude;
+pub mod project;
 pub mod provider;
 pub mod utils;
@@ -1,4 +1,5 @@
 pub use super::provider::{
     db::{Cheats, Frontend},
+    storage::{Storage, StorageError, StorageValue},
     Provider,
 };
@@ -0,0 +1,793 @@
+use crate::compilers::{
+    artifact::{Artifact, ArtifactFormat, Artifacts},
+    builder::{Compiler, CompilerError, CompilerKinds},
+    cache::Cache,
+    huff::huffc::HuffSettings,
+    solidity::{foundry::FoundryConfig, solc::SolcSettings},
+    sources::{SourceError, SourceFiles},
+    version::{self, VersionError},
+};
+use core::fmt;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs, io,
+    path::{Path, PathBuf},
+    thread,
+};
+
+/// Name of the file of `out` recording the sources each artifact was compiled from
+const MANIFEST: &str = ".rustry-build.json";
+
+/// The contracts of a directory tree, compiled to artifact files
+///
+/// The `.sol`, `.vy` and `.huff` files of the source directories are compiled in one batch per
+/// compiler binary, the batches running in parallel, and only the files whose sources or
+/// settings changed since the last compilation are rebuilt.
+#[derive(Debug, Clone)]
+pub struct Project {
+    pub root: PathBuf,
+    /// Directories of the contracts, relative to the root
+    pub sources: Vec<String>,
+    /// Directory of the artifacts, relative to the root
+    pub out: String,
+    pub format: ArtifactFormat,
+    pub settings: SolcSettings,
+    pub huff: HuffSettings,
+}
+
+#[derive(Debug)]
+pub enum ProjectError {
+    Source(SourceError),
+    Version(VersionError),
+    Io {
+        path: PathBuf,
+        err: io::Error,
+    },
+    Compiler {
+        files: Vec<String>,
+        err: Box<CompilerError>,
+    },
+    NotFound {
+        name: String,
+        out: PathBuf,
+    },
+    Ambiguous {
+        name: String,
+        paths: Vec<PathBuf>,
+    },
+    InvalidArtifact {
+        path: PathBuf,
+        err: serde_json::Error,
+    },
+}
+
+impl fmt::Display for ProjectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Source(err) => write!(f, "{err}"),
+            Self::Version(err) => write!(f, "{err}"),
+            Self::Io { path, err } => write!(f, "{}: {err}", path.display()),
+            Self::Compiler { files, err } => {
+                write!(f, "compiling {}: {err}", files.join(", "))
+            }
+            Self::NotFound { name, out } => write!(
+                f,
+                "no artifact of {name} in {}, compile the project with `Project::compile` first",
+                out.display()
+            ),
+            Self::Ambiguous { name, paths } => {
+                let paths: Vec<_> = paths
+                    .iter()
+                    .map(|path| path.display().to_string())
+                    .collect();
+                write!(
+                    f,
+                    "several artifacts of {name}: {}, name the file as in `File.sol:{name}`",
+                    paths.join(", ")
+                )
+            }
+            Self::InvalidArtifact { path, err } => {
+                write!(f, "invalid artifact {}: {err}", path.display())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ProjectError {}
+
+impl From<SourceError> for ProjectError {
+    fn from(err: SourceError) -> Self {
+        Self::Source(err)
+    }
+}
+
+impl From<VersionError> for ProjectError {
+    fn from(err: VersionError) -> Self {
+        Self::Version(err)
+    }
+}
+
+/// What `Project::compile` did
+#[derive(Debug, Default)]
+pub struct ProjectOutput {
+    /// Files compiled, relative to the root
+    pub compiled: Vec<String>,
+    /// Files whose artifacts were kept
+    pub up_to_date: Vec<String>,
+    /// Paths of all the artifacts of the project
+    pub artifacts: Vec<PathBuf>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Manifest {
+    files: BTreeMap<String, ManifestEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct ManifestEntry {
+    /// Hash of the sources of the file and of the compilation settings
+    key: String,
+    /// Artifacts of the file, relative to `out`
+    artifacts: Vec<String>,
+}
+
+/// A file to compile, with the files it imports
+#[derive(Debug)]
+struct Unit {
+    file: String,
+    kind: CompilerKinds,
+    files: SourceFiles,
+    binary: PathBuf,
+    key: String,
+}
+
+impl Project {
+    /// The project at `root`, with the `src` and `out` directories of its `foundry.toml`
+    pub fn new(root: impl Into<PathBuf>) -> Result<Self, ProjectError> {
+        let root = root.into();
+        let config = FoundryConfig::load(&root)?;
+        Ok(Self {
+            root,
+            sources: vec![config.src],
+            out: config.out,
+            format: ArtifactFormat::default(),
+            settings: SolcSettings::default(),
+            huff: HuffSettings::default(),
+        })
+    }
+
+    /// The contracts of the source directories, relative to the root and sorted
+    pub fn files(&self) -> Result<Vec<String>, ProjectError> {
+        let mut files = vec![];
+        for dir in &self.sources {
+            let dir = self.root.join(dir);
+            if dir.is_dir() {
+                walk(&dir, &mut |path| {
+                    if kind(path).is_some() {
+                        files.push(relative(&self.root, path));
+                    }
+                })?;
+            }
+        }
+        files.sort();
+        files.dedup();
+        Ok(files)
+    }
+
+    /// Compiles the files changed since the last compilation and writes their artifacts,
+    /// removing those of the deleted files
+    ///
+    /// The batches that compiled are written even when others failed, the first error being
+    /// returned afterwards.
+    pub fn compile(&self) -> Result<ProjectOutput, ProjectError> {
+        let out = self.root.join(&self.out);
+        let previous = read_manifest(&out.join(MANIFEST));
+
+        let mut units = vec![];
+        // the binary picked for each set of pragmas, as picking one runs `--version` on them
+        let mut binaries: Vec<(CompilerKinds, BTreeSet<String>, PathBuf)> = vec![];
+        for file in self.files()? {
+            let kind = kind(Path::new(&file)).expect("files() filters the extensions");
+            let files = SourceFiles::read(&self.root, &file, &kind)?;
+            let binary = match kind {
+                CompilerKinds::Huff => PathBuf::from("huffc"),
+                _ => {
+                    let pragma = match kind {
+                        CompilerKinds::Vyper => version::vyper_pragma,
+                        _ => version::solidity_pragma,
+                    };
+                    let pragmas: BTreeSet<_> = files
+                        .sources
+                        .values()
+                        .filter_map(|content| pragma(content))
+                        .collect();
+                    let known = binaries.iter().find(|(known, known_pragmas, _)| {
+                        *known == kind && *known_pragmas == pragmas
+                    });
+                    match known {
+                        Some((_, _, binary)) => binary.clone(),
+                        None => {
+                            let binary = version::select(&kind, None, &files.sources)?;
+                            binaries.push((kind.clone(), pragmas, binary.clone()));
+                            binary
+                        }
+                    }
+                }
+            };
+            let key = self.key(&kind, &binary, &files);
+            units.push(Unit {
+                file,
+                kind,
+                files,
+                binary,
+                key,
+            });
+        }
+
+        let mut output = ProjectOutput::default();
+        let mut manifest = Manifest::default();
+        let mut batches: Vec<Vec<&Unit>> = vec![];
+        for unit in &units {
+            match previous.files.get(&unit.file) {
+                Some(entry)
+                    if entry.key == unit.key
+                        && entry.artifacts.iter().all(|path| out.join(path).is_file()) =>
+                {
+                    output.up_to_date.push(unit.file.clone());
+                    manifest.files.insert(unit.file.clone(), entry.clone());
+                }
+                _ => match batches.iter_mut().find(|batch| {
+                    let first = batch[0];
+                    !matches!(unit.kind, CompilerKinds::Huff)
+                        && first.kind == unit.kind
+                        && first.binary == unit.binary
+                }) {
+                    Some(batch) => batch.push(unit),
+                    None => batches.push(vec![unit]),
+                },
+            }
+        }
+
+        let results: Vec<_> = thread::scope(|scope| {
+            let handles: Vec<_> = batches
+                .iter()
+                .map(|batch| scope.spawn(|| self.compile_batch(batch)))
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().expect("compiling doesn't panic"))
+                .collect()
+        });
+
+        let mut error = None;
+        for (batch, result) in batches.iter().zip(results) {
+            let artifacts = match result {
+                Ok(artifacts) => artifacts,
+                Err(err) => {
+                    error.get_or_insert(err);
+                    continue;
+                }
+            };
+            'units: for unit in batch {
+                let mut paths = vec![];
+                for artifact in artifacts.iter().filter(|a| a.file == unit.file) {
+                    let path = self.artifact_path(artifact);
+                    if let Err(err) = write(&out.join(&path), &artifact.to_json(self.format)) {
+                        // kept as a failed file, the manifest being saved below
+                        error.get_or_insert(err);
+                        continue 'units;
+                    }
+                    paths.push(path);
+                }
+                output.compiled.push(unit.file.clone());
+                manifest.files.insert(
+                    unit.file.clone(),
+                    ManifestEntry {
+                        key: unit.key.clone(),
+                        artifacts: paths,
+                    },
+                );
+            }
+        }
+
+        for (file, entry) in &previous.files {
+            if !manifest.files.contains_key(file) && units.iter().any(|unit| &unit.file == file) {
+                // failed to compile, its artifacts are kept until it does
+                manifest.files.insert(file.clone(), entry.clone());
+                continue;
+            }
+            // artifacts of deleted files, or of contracts removed from a file
+            let live = |path: &String| {
+                manifest
+                    .files
+                    .values()
+                    .any(|entry| entry.artifacts.contains(path))
+            };
+            for path in entry.artifacts.iter().filter(|path| !live(path)) {
+                let path = out.join(path);
+                if path.is_file() {
+                    if let Err(err) = fs::remove_file(&path) {
+                        error.get_or_insert(ProjectError::Io {
+                            path: path.clone(),
+                            err,
+                        });
+                        continue;
+                    }
+                }
+                if let Some(parent) = path.parent() {
+                    // only removed once empty
+                    let _ = fs::remove_dir(parent);
+                }
+            }
+        }
+
+        let content = serde_json::to_string_pretty(&manifest).expect("the manifest serializes");
+        fs::create_dir_all(&out)
+            .and_then(|_| fs::write(out.join(MANIFEST), content))
+            .map_err(|err| ProjectError::Io {
+                path: out.join(MANIFEST),
+                err,
+            })?;
+
+        if let Some(err) = error {
+            return Err(err);
+        }
+        output.artifacts = manifest
+            .files
+            .values()
+            .flat_map(|entry| entry.artifacts.iter().map(|path| out.join(path)))
+            .collect();
+        Ok(output)
+    }
+
+    /// The artifact of the contract `name`, or of `File.sol:Name` (`v2/File.sol:Name`,
+    /// `src/v2/File.sol:Name`) when several files define a contract of that name, and its path
+    pub fn artifact(&self, name: &str) -> Result<(Artifact, PathBuf), ProjectError> {
+        let out = self.root.join(&self.out);
+        let (file, contract) = match name.rsplit_once(':') {
+            Some((file, contract)) => (Some(file), contract),
+            None => (None, name),
+        };
+
+        // by directory, relative to `out`
+        let mut candidates = vec![];
+        if out.is_dir() {
+            walk(&out, &mut |path| {
+                if path.extension().is_some_and(|ext| ext == "json")
+                    && path.file_stem().is_some_and(|stem| stem == contract)
+                {
+                    let dir = relative(&out, path.parent().unwrap_or(&out));
+                    candidates.push((dir, path.to_path_buf()));
+                }
+            })?;
+        }
+        // the file exactly, else a file of that name in a subdirectory
+        if let Some(file) = file {
+            let dir = self.artifact_dir(file);
+            let nested = format!("/{dir}");
+            candidates = if candidates.iter().any(|(d, _)| *d == dir) {
+                candidates.into_iter().filter(|(d, _)| *d == dir).collect()
+            } else {
+                candidates
+                    .into_iter()
+                    .filter(|(d, _)| d.ends_with(&nested))
+                    .collect()
+            };
+        }
+        let mut paths: Vec<_> = candidates.into_iter().map(|(_, path)| path).collect();
+        paths.sort();
+
+        let path = match paths.as_slice() {
+            [] => {
+                return Err(ProjectError::NotFound {
+                    name: name.to_string(),
+                    out,
+                })
+            }
+            [path] => path.clone(),
+            _ => {
+                return Err(ProjectError::Ambiguous {
+                    name: name.to_string(),
+                    paths,
+                })
+            }
+        };
+        let content = fs::read_to_string(&path).map_err(|err| ProjectError::Io {
+            path: path.clone(),
+            err,
+        })?;
+        let artifact =
+            Artifact::from_json(&content, &path).map_err(|err| ProjectError::InvalidArtifact {
+                path: path.clone(),
+                err,
+            })?;
+        Ok((artifact, path))
+    }
+
+    /// The artifacts of the libraries `artifact` calls, and of the libraries they call, with
+    /// their paths. The libraries without artifacts are left to link by hand.
+    pub fn libraries(&self, artifact: &Artifact) -> Result<Vec<(Artifact, PathBuf)>, ProjectError> {
+        let mut libraries: Vec<(Artifact, PathBuf)> = vec![];
+        let mut needed: Vec<_> = artifact
+            .link_offsets()
+            .into_iter()
+            .map(|(l, _)| l)
+            .collect();
+        while let Some(library) = needed.pop() {
+            if libraries
+                .iter()
+                .any(|(lib, _)| format!("{}:{}", lib.file, lib.name) == library)
+            {
+                continue;
+            }
+            match self.artifact(&library) {
+                Ok((lib, path)) => {
+                    needed.extend(lib.link_offsets().into_iter().map(|(l, _)| l));
+                    libraries.push((lib, path));
+                }
+                Err(ProjectError::NotFound { .. }) => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(libraries)
+    }
+
+    /// Hash of the sources of a file, its compiler and the settings
+    fn key(&self, kind: &CompilerKinds, binary: &Path, files: &SourceFiles) -> String {
+        let input = json!({
+            "sources": files.sources,
+            "remappings": files.remappings.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            "settings": self.settings,
+            "huff": self.huff,
+            "format": format!("{:?}", self.format),
+        });
+        Cache::key(kind, binary, &input.to_string())
+    }
+
+    fn compile_batch(&self, batch: &[&Unit]) -> Result<Vec<Artifact>, ProjectError> {
+        let first = batch[0];
+        let compiler = Compiler {
+            sources: batch
+                .iter()
+                .flat_map(|unit| unit.files.sources.clone())
+                .collect(),
+            entry: first.file.clone(),
+            kind: first.kind.clone(),
+            remappings: first.files.remappings.clone(),
+            settings: self.settings.clone(),
+            version: None,
+            huff: self.huff.clone(),
+        };
+        let mut artifacts = compiler
+            .run()
+            .map_err(|err| ProjectError::Compiler {
+                files: batch.iter().map(|unit| unit.file.clone()).collect(),
+                err: Box::new(err),
+            })?
+            .artifacts();
+        if matches!(first.kind, CompilerKinds::Huff) {
+            // huffc names the temporary copy of the file
+            for artifact in &mut artifacts {
+                artifact.file = first.file.clone();
+            }
+        }
+        Ok(artifacts)
+    }
+
+    /// Path of the artifact file, relative to `out`: `Vault.sol/Vault.json` as Foundry writes
+    /// it, or `src/Vault.sol/Vault.json` as Hardhat does
+    fn artifact_path(&self, artifact: &Artifact) -> String {
+        format!(
+            "{}/{}.json",
+            self.artifact_dir(&artifact.file),
+            artifact.name
+        )
+    }
+
+    /// The directory of the artifacts of `file`, relative to `out`: the file relative to its
+    /// source directory for Foundry, so that `src/v2/Vault.sol` gets `v2/Vault.sol`, or the file
+    /// itself for Hardhat
+    fn artifact_dir(&self, file: &str) -> String {
+        match self.format {
+            ArtifactFormat::Foundry => self
+                .sources
+                .iter()
+                .find_map(|dir| {
+                    let dir = dir.trim_start_matches("./").trim_end_matches('/');
+                    file.strip_prefix(dir)?.strip_prefix('/')
+                })
+                .unwrap_or(file)
+                .to_string(),
+            ArtifactFormat::Hardhat => file.to_string(),
+        }
+    }
+}
+
+/// The compiler of a contract, from its extension
+fn kind(path: &Path) -> Option<CompilerKinds> {
+    match path.extension()?.to_str()? {
+        "sol" => Some(CompilerKinds::Solc),
+        "vy" => Some(CompilerKinds::Vyper),
+        "huff" => Some(CompilerKinds::Huff),
+        _ => None,
+    }
+}
+
+/// `path` relative to `root`, separated by `/`
+fn relative(root: &Path, path: &Path) -> String {
+    let path = path.strip_prefix(root).unwrap_or(path);
+    path.components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Calls `f` on the files of `dir` and its subdirectories
+fn walk(dir: &Path, f: &mut impl FnMut(&Path)) -> Result<(), ProjectError> {
+    let io_err = |err| ProjectError::Io {
+        path: dir.to_path_buf(),
+        err,
+    };
+    for entry in fs::read_dir(dir).map_err(io_err)? {
+        let path = entry.map_err(io_err)?.path();
+        if path.is_dir() {
+            walk(&path, f)?;
+        } else {
+            f(&path);
+        }
+    }
+    Ok(())
+}
+
+/// The manifest of the previous compilation, empty when missing or unreadable
+fn read_manifest(path: &Path) -> Manifest {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn write(path: &Path, artifact: &serde_json::Value) -> Result<(), ProjectError> {
+    let content = serde_json::to_string_pretty(artifact).expect("artifacts serialize");
+    path.parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| fs::write(path, content))
+        .map_err(|err| ProjectError::Io {
+            path: path.to_path_buf(),
+            err,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compilers::{
+        artifact::{AbiEntry, Artifact},
+        solidity::solc::CodeRange,
+    };
+    use tempfile::tempdir;
+
+    fn vault(file: &str) -> Artifact {
+        let abi: Vec<AbiEntry> = vec!["function deposit() payable".parse().unwrap()];
+        Artifact {
+            name: String::from("Vault"),
+            file: file.to_string(),
+            bytecode: String::from("6080"),
+            deployed_bytecode: String::from("6001"),
+            source_map: None,
+            storage_layout: None,
+            link_references: Default::default(),
+            deployed_link_references: Default::default(),
+            method_identifiers: Artifact::method_identifiers(&abi),
+            abi,
+        }
+    }
+
+    #[test]
+    fn discovers_contracts() {
+        let dir = tempdir().unwrap();
+        for file in [
+            "src/Vault.sol",
+            "src/tokens/Token.vy",
+            "src/Store.huff",
+            "src/lib.rs",
+        ] {
+            let path = dir.path().join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "").unwrap();
+        }
+        let project = Project::new(dir.path()).unwrap();
+        assert_eq!(project.out, "out");
+        assert_eq!(
+            project.files().unwrap(),
+            ["src/Store.huff", "src/Vault.sol", "src/tokens/Token.vy"]
+        );
+    }
+
+    #[test]
+    fn finds_artifacts() {
+        let dir = tempdir().unwrap();
+        let mut project = Project::new(dir.path()).unwrap();
+        assert!(matches!(
+            project.artifact("Vault"),
+            Err(ProjectError::NotFound { .. })
+        ));
+
+        project.format = ArtifactFormat::Hardhat;
+        for file in ["src/Vault.sol", "src/v2/Vault.sol", "src/Other.sol"] {
+            let artifact = vault(file);
+            let path = dir
+                .path()
+                .join("out")
+                .join(project.artifact_path(&artifact));
+            write(&path, &artifact.to_json(project.format)).unwrap();
+        }
+        assert!(matches!(
+            project.artifact("Vault"),
+            Err(ProjectError::Ambiguous { ref paths, .. }) if paths.len() == 3
+        ));
+
+        let (artifact, path) = project.artifact("Other.sol:Vault").unwrap();
+        assert_eq!(path, dir.path().join("out/src/Other.sol/Vault.json"));
+        assert_eq!(artifact.file, "src/Other.sol");
+        assert_eq!(artifact.method_identifiers, vault("").method_identifiers);
+    }
+
+    #[test]
+    fn separates_files_of_the_same_name() {
+        let dir = tempdir().unwrap();
+        let project = Project::new(dir.path()).unwrap();
+        let paths: Vec<_> = ["src/Vault.sol", "src/v2/Vault.sol"]
+            .map(|file| {
+                let artifact = vault(file);
+                let path = project.artifact_path(&artifact);
+                write(
+                    &dir.path().join("out").join(&path),
+                    &artifact.to_json(project.format),
+                )
+                .unwrap();
+                path
+            })
+            .to_vec();
+        assert_eq!(paths, ["Vault.sol/Vault.json", "v2/Vault.sol/Vault.json"]);
+
+        assert!(matches!(
+            project.artifact("Vault"),
+            Err(ProjectError::Ambiguous { ref paths, .. }) if paths.len() == 2
+        ));
+        for (name, file) in [
+            ("Vault.sol:Vault", "src/Vault.sol"),
+            ("src/Vault.sol:Vault", "src/Vault.sol"),
+            ("v2/Vault.sol:Vault", "src/v2/Vault.sol"),
+            ("src/v2/Vault.sol:Vault", "src/v2/Vault.sol"),
+        ] {
+            assert_eq!(project.artifact(name).unwrap().0.file, file, "{name}");
+        }
+    }
+
+    #[test]
+    fn finds_libraries() {
+        let dir = tempdir().unwrap();
+        let project = Project::new(dir.path()).unwrap();
+        let calls = |file: &str, library: &str| {
+            BTreeMap::from([(
+                file.to_string(),
+                BTreeMap::from([(
+                    library.to_string(),
+                    vec![CodeRange {
+                        start: 0,
+                        length: 20,
+                    }],
+                )]),
+            )])
+        };
+        let artifact = |name: &str, link_references| Artifact {
+            name: name.to_string(),
+            link_references,
+            ..vault("src/Math.sol")
+        };
+        let vault = artifact("Vault", calls("src/Math.sol", "Math"));
+        let math = artifact("Math", calls("src/Math.sol", "Inner"));
+        let inner = artifact("Inner", calls("lib/Oracle.sol", "Oracle"));
+        for artifact in [&vault, &math, &inner] {
+            let path = dir.path().join("out").join(project.artifact_path(artifact));
+            write(&path, &artifact.to_json(project.format)).unwrap();
+        }
+
+        // Oracle has no artifact
+        let libraries: Vec<_> = project
+            .libraries(&vault)
+            .unwrap()
+            .into_iter()
+            .map(|(library, _)| library.name)
+            .collect();
+        assert_eq!(libraries, ["Math", "Inner"]);
+    }
+
+    /// Writes the Huff `files` and the manifest of their artifacts, as if they were compiled
+    fn compiled(project: &Project, files: &[&str]) -> Vec<PathBuf> {
+        let root = &project.root;
+        let mut manifest = Manifest::default();
+        let mut paths = vec![];
+        for file in files {
+            let source = root.join(file);
+            fs::create_dir_all(source.parent().unwrap()).unwrap();
+            fs::write(&source, "#define macro MAIN() = takes(0) returns(0) {}").unwrap();
+            let sources = SourceFiles::read(root, file, &CompilerKinds::Huff).unwrap();
+            let artifact = Artifact {
+                name: String::from("Store"),
+                ..vault(file)
+            };
+            let path = project.artifact_path(&artifact);
+            write(
+                &root.join("out").join(&path),
+                &artifact.to_json(project.format),
+            )
+            .unwrap();
+            manifest.files.insert(
+                file.to_string(),
+                ManifestEntry {
+                    key: project.key(&CompilerKinds::Huff, Path::new("huffc"), &sources),
+                    artifacts: vec![path.clone()],
+                },
+            );
+            paths.push(root.join("out").join(path));
+        }
+        fs::write(
+            root.join("out").join(MANIFEST),
+            serde_json::to_string(&manifest).unwrap(),
+        )
+        .unwrap();
+        paths
+    }
+
+    fn modified(path: &Path) -> std::time::SystemTime {
+        fs::metadata(path).unwrap().modified().unwrap()
+    }
+
+    #[test]
+    fn keeps_up_to_date_artifacts() {
+        let dir = tempdir().unwrap();
+        let project = Project::new(dir.path()).unwrap();
+        let paths = compiled(&project, &["src/Store.huff"]);
+        let before = modified(&paths[0]);
+
+        let output = project.compile().unwrap();
+        assert_eq!(output.up_to_date, ["src/Store.huff"]);
+        assert!(output.compiled.is_empty());
+        assert_eq!(output.artifacts, paths);
+        assert_eq!(modified(&paths[0]), before);
+
+        // rebuilt once changed, which fails with or without huffc, the artifact and its entry
+        // being kept until it compiles
+        let manifest = read_manifest(&dir.path().join("out").join(MANIFEST));
+        fs::write(dir.path().join("src/Store.huff"), "#define macro MAIN(").unwrap();
+        match project.compile() {
+            Err(ProjectError::Compiler { files, .. }) => assert_eq!(files, ["src/Store.huff"]),
+            other => panic!("expected a compiler error, got {other:?}"),
+        }
+        assert_eq!(modified(&paths[0]), before);
+        let after = read_manifest(&dir.path().join("out").join(MANIFEST));
+        assert_eq!(
+            after.files["src/Store.huff"].key,
+            manifest.files["src/Store.huff"].key
+        );
+    }
+
+    #[test]
+    fn removes_the_artifacts_of_deleted_files_only() {
+        let dir = tempdir().unwrap();
+        let project = Project::new(dir.path()).unwrap();
+        let paths = compiled(&project, &["src/Store.huff", "src/v2/Store.huff"]);
+        assert_ne!(paths[0], paths[1]);
+
+        fs::remove_file(dir.path().join("src/v2/Store.huff")).unwrap();
+        let output = project.compile().unwrap();
+        assert_eq!(output.up_to_date, ["src/Store.huff"]);
+        assert_eq!(output.artifacts, paths[..1]);
+        assert!(paths[0].is_file());
+        assert!(!paths[1].exists());
+    }
+}
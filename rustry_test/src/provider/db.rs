@@ -1,16 +1,18 @@
 use revm::{
     db::{CacheDB, EmptyDB},
     primitives::{
-        alloy_primitives::Uint, AccountInfo, Address, Bytes, Env, ExecutionResult, Halt, Output,
-        TransactTo, U256,
+        alloy_primitives::Uint, AccountInfo, Address, Bytes, Env, ExecutionResult, Halt, Log,
+        Output, TransactTo, U256,
     },
-    EVM,
+    Database, EVM,
 };
 
 pub struct Provider {
     evm: EVM<CacheDB<EmptyDB>>,
     /// The active sender
     pub sender: Address,
+    /// Logs emitted by the last transaction or call
+    pub logs: Vec<Log>,
 }
 
 impl Default for Provider {
@@ -20,6 +22,7 @@ impl Default for Provider {
         Provider {
             evm,
             sender: Address::ZERO,
+            logs: vec![],
         }
     }
 }
@@ -68,13 +71,29 @@ impl Provider {
         self.evm.db().unwrap().insert_account_info(who, info);
     }
 
+    /// The word in the storage `slot` of `who`
+    pub fn load(&mut self, who: Address, slot: U256) -> U256 {
+        self.evm.db().unwrap().storage(who, slot).unwrap()
+    }
+
+    /// Overwrites the storage `slot` of `who`
+    pub fn store(&mut self, who: Address, slot: U256, value: U256) {
+        self.evm
+            .db()
+            .unwrap()
+            .insert_account_storage(who, slot, value)
+            .unwrap();
+    }
+
     fn deploy_code(&mut self, from: Address, code: Bytes, value: Uint<256, 4>) -> Option<Address> {
         let tx = &mut self.env().tx;
         tx.caller = from;
         tx.transact_to = TransactTo::create();
         tx.data = code;
         tx.value = value;
-        match self.evm.transact_commit().unwrap() {
+        let result = self.evm.transact_commit().unwrap();
+        self.logs = result.logs();
+        match result {
             ExecutionResult::Success { output, .. } => match output {
                 Output::Create(_, addr) => addr,
                 _ => unreachable!(),
@@ -90,7 +109,9 @@ impl Provider {
         tx.data = data;
         tx.value = value;
 
-        match self.evm.transact_commit().unwrap() {
+        let result = self.evm.transact_commit().unwrap();
+        self.logs = result.logs();
+        match result {
             ExecutionResult::Success { output, .. } => ExecRes::Success(output.into_data()),
             ExecutionResult::Revert { output, .. } => ExecRes::Revert(output),
             ExecutionResult::Halt { reason, .. } => ExecRes::Halt(reason),
@@ -104,7 +125,9 @@ impl Provider {
         tx.data = data;
         tx.value = value;
 
-        match self.evm.transact_commit().unwrap() {
+        let result = self.evm.transact_commit().unwrap();
+        self.logs = result.logs();
+        match result {
             ExecutionResult::Success { output, .. } => ExecRes::Success(output.into_data()),
             ExecutionResult::Revert { output, .. } => ExecRes::Revert(output),
             ExecutionResult::Halt { reason, .. } => ExecRes::Halt(reason),
@@ -117,7 +140,9 @@ impl Provider {
         tx.transact_to = TransactTo::Call(to);
         tx.data = data;
 
-        match self.evm.transact().unwrap().result {
+        let result = self.evm.transact().unwrap().result;
+        self.logs = result.logs();
+        match result {
             ExecutionResult::Success { output, .. } => ExecRes::Success(output.into_data()),
             ExecutionResult::Revert { output, .. } => ExecRes::Revert(output),
             ExecutionResult::Halt { reason, .. } => ExecRes::Halt(reason),
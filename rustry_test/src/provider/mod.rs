@@ -1,3 +1,4 @@
 pub mod db;
+pub mod storage;
 
 pub use db::Provider;
@@ -0,0 +1,904 @@
+use crate::{
+    common::contract::Contract,
+    compilers::solidity::solc::{StorageEncoding, StorageEntry, StorageLayout, StorageType},
+    provider::Provider,
+};
+use revm::primitives::{alloy_primitives::I256, keccak256, Address, Bytes, U256};
+use std::{fmt, str::FromStr};
+
+/// The decoded value of a state variable
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StorageValue {
+    /// An unsigned integer, an enum or a user-defined value type
+    Uint(U256),
+    Int(I256),
+    /// An address or a contract
+    Address(Address),
+    Bool(bool),
+    /// A `bytesN`
+    FixedBytes(Vec<u8>),
+    Bytes(Vec<u8>),
+    String(String),
+}
+
+impl StorageValue {
+    pub fn as_uint(&self) -> Option<U256> {
+        match self {
+            StorageValue::Uint(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_int(&self) -> Option<I256> {
+        match self {
+            StorageValue::Int(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_address(&self) -> Option<Address> {
+        match self {
+            StorageValue::Address(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            StorageValue::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// The content of a `bytesN` or `bytes`
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            StorageValue::FixedBytes(value) | StorageValue::Bytes(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            StorageValue::String(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+impl From<U256> for StorageValue {
+    fn from(value: U256) -> Self {
+        StorageValue::Uint(value)
+    }
+}
+
+impl From<I256> for StorageValue {
+    fn from(value: I256) -> Self {
+        StorageValue::Int(value)
+    }
+}
+
+impl From<Address> for StorageValue {
+    fn from(value: Address) -> Self {
+        StorageValue::Address(value)
+    }
+}
+
+impl From<bool> for StorageValue {
+    fn from(value: bool) -> Self {
+        StorageValue::Bool(value)
+    }
+}
+
+impl From<Vec<u8>> for StorageValue {
+    fn from(value: Vec<u8>) -> Self {
+        StorageValue::Bytes(value)
+    }
+}
+
+impl From<String> for StorageValue {
+    fn from(value: String) -> Self {
+        StorageValue::String(value)
+    }
+}
+
+impl From<&str> for StorageValue {
+    fn from(value: &str) -> Self {
+        StorageValue::String(value.to_string())
+    }
+}
+
+/// Errors raised while locating, reading or writing a state variable
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StorageError {
+    /// The contract has no storage layout, only solc outputs one
+    NoLayout,
+    /// The path is not a variable followed by `.member` and `[key]` steps
+    InvalidPath {
+        path: String,
+        reason: String,
+    },
+    UnknownVariable {
+        name: String,
+        variables: Vec<String>,
+    },
+    NoMember {
+        ty: String,
+        member: String,
+    },
+    NotIndexable {
+        ty: String,
+    },
+    /// A mapping key or array index does not parse as its type
+    InvalidKey {
+        ty: String,
+        key: String,
+    },
+    OutOfBounds {
+        ty: String,
+        index: usize,
+    },
+    /// The path ends on a mapping, an array or a struct
+    NotAValue {
+        path: String,
+        ty: String,
+    },
+    /// The value is of another type, or does not fit the variable
+    Mismatch {
+        path: String,
+        ty: String,
+        value: StorageValue,
+    },
+    /// A `string` is not valid UTF-8
+    InvalidString {
+        path: String,
+    },
+    /// The layout refers to a type it does not define, or is otherwise inconsistent
+    InvalidLayout(String),
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoLayout => write!(
+                f,
+                "no storage layout for the contract, only solc outputs one"
+            ),
+            Self::InvalidPath { path, reason } => write!(f, "invalid path `{path}`: {reason}"),
+            Self::UnknownVariable { name, variables } => write!(
+                f,
+                "no state variable `{name}`, the contract has: {}",
+                variables.join(", ")
+            ),
+            Self::NoMember { ty, member } => write!(f, "`{ty}` has no member `{member}`"),
+            Self::NotIndexable { ty } => write!(f, "`{ty}` cannot be indexed"),
+            Self::InvalidKey { ty, key } => write!(f, "invalid `{ty}` key `{key}`"),
+            Self::OutOfBounds { ty, index } => write!(f, "index {index} out of bounds of `{ty}`"),
+            Self::NotAValue { path, ty } => {
+                write!(f, "`{path}` is a `{ty}`, read its members or elements")
+            }
+            Self::Mismatch { path, ty, value } => {
+                write!(f, "cannot write {value:?} to `{path}`, a `{ty}`")
+            }
+            Self::InvalidString { path } => write!(f, "`{path}` is not a UTF-8 string"),
+            Self::InvalidLayout(reason) => write!(f, "invalid storage layout: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for StorageError {}
+
+/// Where a variable is stored
+#[derive(Debug)]
+pub struct Location<'a> {
+    pub slot: U256,
+    /// The first byte of the variable in the slot, from the lowest-order byte
+    pub offset: usize,
+    /// The identifier of the type, as `t_uint256`
+    pub id: &'a str,
+    pub ty: &'a StorageType,
+}
+
+enum Step<'a> {
+    Member(&'a str),
+    Index(&'a str),
+}
+
+/// Splits `positions[0x..].owner` in `positions`, `[0x..]` and `.owner`
+fn steps(path: &str) -> Result<Vec<Step<'_>>, StorageError> {
+    let invalid = |reason: String| StorageError::InvalidPath {
+        path: path.to_string(),
+        reason,
+    };
+    let end = path.find(['.', '[']).unwrap_or(path.len());
+    let mut steps = vec![Step::Member(path[..end].trim())];
+    let mut rest = &path[end..];
+    while !rest.is_empty() {
+        if let Some(member) = rest.strip_prefix('.') {
+            let end = member.find(['.', '[']).unwrap_or(member.len());
+            steps.push(Step::Member(member[..end].trim()));
+            rest = &member[end..];
+        } else if let Some(index) = rest.strip_prefix('[') {
+            let end = index
+                .find(']')
+                .ok_or_else(|| invalid(String::from("unclosed `[`")))?;
+            steps.push(Step::Index(index[..end].trim()));
+            rest = &index[end + 1..];
+        } else {
+            return Err(invalid(format!(
+                "expected `.member` or `[key]`, found `{rest}`"
+            )));
+        }
+    }
+    if steps.iter().any(|step| matches!(step, Step::Member(""))) {
+        return Err(invalid(String::from("empty variable or member name")));
+    }
+    Ok(steps)
+}
+
+fn storage_type<'a>(layout: &'a StorageLayout, id: &str) -> Result<&'a StorageType, StorageError> {
+    layout
+        .types
+        .as_ref()
+        .and_then(|types| types.get(id))
+        .ok_or_else(|| StorageError::InvalidLayout(format!("type {id} is not defined")))
+}
+
+fn size(ty: &StorageType) -> Result<usize, StorageError> {
+    match ty.number_of_bytes.parse() {
+        Ok(size) if size > 0 => Ok(size),
+        _ => Err(StorageError::InvalidLayout(format!(
+            "invalid size `{}` of `{}`",
+            ty.number_of_bytes, ty.label
+        ))),
+    }
+}
+
+/// A field the layout gives for the types of some encoding
+fn field<'a>(field: &'a Option<String>, ty: &StorageType) -> Result<&'a str, StorageError> {
+    field
+        .as_deref()
+        .ok_or_else(|| StorageError::InvalidLayout(format!("incomplete type `{}`", ty.label)))
+}
+
+fn hash(data: &[u8]) -> U256 {
+    U256::from_be_bytes(keccak256(data).0)
+}
+
+impl<'a> Location<'a> {
+    fn of(
+        layout: &'a StorageLayout,
+        entry: &'a StorageEntry,
+        base: U256,
+    ) -> Result<Self, StorageError> {
+        let slot = U256::from_str(&entry.slot).map_err(|_| {
+            StorageError::InvalidLayout(format!(
+                "invalid slot `{}` of `{}`",
+                entry.slot, entry.label
+            ))
+        })?;
+        Ok(Location {
+            slot: base + slot,
+            offset: entry.offset,
+            id: &entry.ty,
+            ty: storage_type(layout, &entry.ty)?,
+        })
+    }
+
+    /// The element `index` of an array whose elements start at `start`
+    fn element(
+        layout: &'a StorageLayout,
+        array: &'a StorageType,
+        start: U256,
+        index: usize,
+    ) -> Result<Self, StorageError> {
+        let id = field(&array.base, array)?;
+        let ty = storage_type(layout, id)?;
+        let size = size(ty)?;
+        // the elements are packed while they fit in a slot, the bigger ones take whole slots
+        let (slot, offset) = if size <= 32 {
+            let per_slot = 32 / size;
+            (
+                start + U256::from(index / per_slot),
+                index % per_slot * size,
+            )
+        } else {
+            (start + U256::from(index * size.div_ceil(32)), 0)
+        };
+        Ok(Location {
+            slot,
+            offset,
+            id,
+            ty,
+        })
+    }
+}
+
+/// Finds where the variable at `path` is stored, as `total`, `balances[0x..]`,
+/// `positions[3].owner` or `names["alice"]`
+pub fn locate<'a>(layout: &'a StorageLayout, path: &str) -> Result<Location<'a>, StorageError> {
+    let mut steps = steps(path)?.into_iter();
+    let Some(Step::Member(name)) = steps.next() else {
+        unreachable!("a path starts with a variable")
+    };
+    let entry = layout
+        .storage
+        .iter()
+        .find(|entry| entry.label == name)
+        .ok_or_else(|| StorageError::UnknownVariable {
+            name: name.to_string(),
+            variables: layout
+                .storage
+                .iter()
+                .map(|entry| entry.label.clone())
+                .collect(),
+        })?;
+
+    let mut location = Location::of(layout, entry, U256::ZERO)?;
+    for step in steps {
+        let ty = location.ty;
+        let index = |index: &str| {
+            index
+                .parse::<usize>()
+                .map_err(|_| StorageError::InvalidKey {
+                    ty: ty.label.clone(),
+                    key: index.to_string(),
+                })
+        };
+        location = match (step, ty.encoding) {
+            (Step::Member(name), StorageEncoding::Inplace) if ty.members.is_some() => {
+                let member = ty
+                    .members
+                    .iter()
+                    .flatten()
+                    .find(|member| member.label == name)
+                    .ok_or_else(|| StorageError::NoMember {
+                        ty: ty.label.clone(),
+                        member: name.to_string(),
+                    })?;
+                Location::of(layout, member, location.slot)?
+            }
+            (Step::Index(key), StorageEncoding::Mapping) => {
+                let key_id = field(&ty.key, ty)?;
+                let mut data = encode_key(key, key_id, storage_type(layout, key_id)?)?;
+                data.extend(location.slot.to_be_bytes::<32>());
+                let id = field(&ty.value, ty)?;
+                Location {
+                    slot: hash(&data),
+                    offset: 0,
+                    id,
+                    ty: storage_type(layout, id)?,
+                }
+            }
+            (Step::Index(key), StorageEncoding::DynamicArray) => {
+                let start = hash(&location.slot.to_be_bytes::<32>());
+                Location::element(layout, ty, start, index(key)?)?
+            }
+            (Step::Index(key), StorageEncoding::Inplace) if ty.base.is_some() => {
+                let index = index(key)?;
+                let len = ty
+                    .label
+                    .rsplit_once('[')
+                    .and_then(|(_, len)| len.trim_end_matches(']').parse::<usize>().ok());
+                if len.is_some_and(|len| index >= len) {
+                    return Err(StorageError::OutOfBounds {
+                        ty: ty.label.clone(),
+                        index,
+                    });
+                }
+                Location::element(layout, ty, location.slot, index)?
+            }
+            (Step::Member(name), _) => {
+                return Err(StorageError::NoMember {
+                    ty: ty.label.clone(),
+                    member: name.to_string(),
+                })
+            }
+            (Step::Index(_), _) => {
+                return Err(StorageError::NotIndexable {
+                    ty: ty.label.clone(),
+                })
+            }
+        };
+    }
+    Ok(location)
+}
+
+/// Parses the key of a mapping, hashed with the slot of the mapping
+fn encode_key(key: &str, id: &str, ty: &StorageType) -> Result<Vec<u8>, StorageError> {
+    let invalid = || StorageError::InvalidKey {
+        ty: ty.label.clone(),
+        key: key.to_string(),
+    };
+    let unquoted = key.trim_matches('"');
+    if id.starts_with("t_string") {
+        return Ok(unquoted.as_bytes().to_vec());
+    }
+    if ty.encoding == StorageEncoding::Bytes {
+        return Ok(Bytes::from_str(unquoted).map_err(|_| invalid())?.to_vec());
+    }
+
+    let value = if id.starts_with("t_bool") {
+        StorageValue::Bool(key.parse().map_err(|_| invalid())?)
+    } else if id.starts_with("t_address") || id.starts_with("t_contract") {
+        StorageValue::Address(key.parse().map_err(|_| invalid())?)
+    } else if id.starts_with("t_int") {
+        StorageValue::Int(key.parse().map_err(|_| invalid())?)
+    } else if id.starts_with("t_bytes") {
+        let mut bytes = Bytes::from_str(key).map_err(|_| invalid())?.to_vec();
+        let size = size(ty)?;
+        if bytes.len() > size.min(32) {
+            return Err(invalid());
+        }
+        bytes.resize(size, 0);
+        StorageValue::FixedBytes(bytes)
+    } else {
+        StorageValue::Uint(key.parse().map_err(|_| invalid())?)
+    };
+    Ok(word(&value).ok_or_else(invalid)?.to_vec())
+}
+
+/// The value padded to a word, as in the ABI, `None` for `bytes`, strings and longer `bytesN`
+fn word(value: &StorageValue) -> Option<[u8; 32]> {
+    let mut word = [0; 32];
+    match value {
+        StorageValue::Uint(value) => word = value.to_be_bytes(),
+        StorageValue::Int(value) => word = value.into_raw().to_be_bytes(),
+        StorageValue::Address(value) => word[12..].copy_from_slice(value.as_slice()),
+        StorageValue::Bool(value) => word[31] = *value as u8,
+        StorageValue::FixedBytes(value) if value.len() <= 32 => {
+            word[..value.len()].copy_from_slice(value)
+        }
+        StorageValue::FixedBytes(_) | StorageValue::Bytes(_) | StorageValue::String(_) => {
+            return None
+        }
+    }
+    Some(word)
+}
+
+/// Decodes a value type from the bytes it takes in its slot
+fn decode(bytes: &[u8], id: &str) -> StorageValue {
+    if id.starts_with("t_bool") {
+        StorageValue::Bool(bytes.iter().any(|byte| *byte != 0))
+    } else if id.starts_with("t_address") || id.starts_with("t_contract") {
+        StorageValue::Address(Address::from_slice(
+            &bytes[bytes.len().saturating_sub(20)..],
+        ))
+    } else if id.starts_with("t_int") {
+        let mut word = if bytes[0] & 0x80 == 0 {
+            [0; 32]
+        } else {
+            [0xff; 32]
+        };
+        word[32 - bytes.len()..].copy_from_slice(bytes);
+        StorageValue::Int(I256::from_raw(U256::from_be_bytes(word)))
+    } else if id.starts_with("t_bytes") {
+        StorageValue::FixedBytes(bytes.to_vec())
+    } else {
+        StorageValue::Uint(U256::try_from_be_slice(bytes).unwrap_or_default())
+    }
+}
+
+/// The bytes a value type takes in its slot, as `end - size..end`
+fn value_range(location: &Location) -> Result<std::ops::Range<usize>, StorageError> {
+    let size = size(location.ty)?;
+    match 32usize.checked_sub(location.offset) {
+        Some(end) if size <= end => Ok(end - size..end),
+        _ => Err(StorageError::InvalidLayout(format!(
+            "`{}` overflows its slot",
+            location.ty.label
+        ))),
+    }
+}
+
+/// Reads a `string` or `bytes` stored from `slot`
+fn read_bytes(provider: &mut Provider, address: Address, slot: U256) -> Vec<u8> {
+    let word = provider.load(address, slot);
+    if !word.bit(0) {
+        let word = word.to_be_bytes::<32>();
+        return word[..(word[31] as usize / 2).min(31)].to_vec();
+    }
+    let len = (word >> 1usize).to::<usize>();
+    let start = hash(&slot.to_be_bytes::<32>());
+    let mut data: Vec<u8> = (0..len.div_ceil(32))
+        .flat_map(|i| {
+            provider
+                .load(address, start + U256::from(i))
+                .to_be_bytes::<32>()
+        })
+        .collect();
+    data.truncate(len);
+    data
+}
+
+/// Writes a `string` or `bytes` from `slot`, in the slot when shorter than 32 bytes
+fn write_bytes(provider: &mut Provider, address: Address, slot: U256, data: &[u8]) {
+    if data.len() < 32 {
+        let mut word = [0; 32];
+        word[..data.len()].copy_from_slice(data);
+        word[31] = data.len() as u8 * 2;
+        provider.store(address, slot, U256::from_be_bytes(word));
+        return;
+    }
+    provider.store(address, slot, U256::from(data.len() * 2 + 1));
+    let start = hash(&slot.to_be_bytes::<32>());
+    for (i, chunk) in data.chunks(32).enumerate() {
+        let mut word = [0; 32];
+        word[..chunk.len()].copy_from_slice(chunk);
+        provider.store(address, start + U256::from(i), U256::from_be_bytes(word));
+    }
+}
+
+/// Reads and writes the state variables of contracts, from their storage layout
+pub trait Storage {
+    /// Reads the variable at `path` in the storage of `contract`, as `balances[0x..]`, ending on
+    /// a value type, a `string` or `bytes`
+    fn read_var(
+        &mut self,
+        contract: &impl Contract,
+        path: &str,
+    ) -> Result<StorageValue, StorageError>;
+    /// Writes the variable at `path` in the storage of `contract`, keeping the variables packed
+    /// in the same slot
+    fn write_var(
+        &mut self,
+        contract: &impl Contract,
+        path: &str,
+        value: impl Into<StorageValue>,
+    ) -> Result<(), StorageError>;
+}
+
+impl Storage for Provider {
+    fn read_var(
+        &mut self,
+        contract: &impl Contract,
+        path: &str,
+    ) -> Result<StorageValue, StorageError> {
+        let (address, layout) = contract.storage_layout().ok_or(StorageError::NoLayout)?;
+        let location = locate(layout, path)?;
+        let ty = location.ty;
+        match ty.encoding {
+            StorageEncoding::Inplace if ty.members.is_none() && ty.base.is_none() => {
+                let range = value_range(&location)?;
+                let word = self.load(address, location.slot).to_be_bytes::<32>();
+                Ok(decode(&word[range], location.id))
+            }
+            StorageEncoding::Bytes => {
+                let data = read_bytes(self, address, location.slot);
+                if location.id.starts_with("t_string") {
+                    String::from_utf8(data)
+                        .map(StorageValue::String)
+                        .map_err(|_| StorageError::InvalidString {
+                            path: path.to_string(),
+                        })
+                } else {
+                    Ok(StorageValue::Bytes(data))
+                }
+            }
+            _ => Err(StorageError::NotAValue {
+                path: path.to_string(),
+                ty: ty.label.clone(),
+            }),
+        }
+    }
+
+    fn write_var(
+        &mut self,
+        contract: &impl Contract,
+        path: &str,
+        value: impl Into<StorageValue>,
+    ) -> Result<(), StorageError> {
+        let (address, layout) = contract.storage_layout().ok_or(StorageError::NoLayout)?;
+        let location = locate(layout, path)?;
+        let ty = location.ty;
+        let value = value.into();
+        let mismatch = |value: StorageValue| StorageError::Mismatch {
+            path: path.to_string(),
+            ty: ty.label.clone(),
+            value,
+        };
+        match (ty.encoding, value) {
+            (StorageEncoding::Bytes, StorageValue::Bytes(data)) => {
+                write_bytes(self, address, location.slot, &data)
+            }
+            (StorageEncoding::Bytes, StorageValue::String(data)) => {
+                write_bytes(self, address, location.slot, data.as_bytes())
+            }
+            (StorageEncoding::Inplace, value) if ty.members.is_none() && ty.base.is_none() => {
+                let range = value_range(&location)?;
+                let size = range.len();
+                let Some(word) = word(&value) else {
+                    return Err(mismatch(value));
+                };
+                let bytes = match &value {
+                    StorageValue::FixedBytes(bytes) => bytes.clone(),
+                    _ => word[32 - size..].to_vec(),
+                };
+                // also rejects the values of other types, decoded differently
+                if bytes.len() != size || decode(&bytes, location.id) != value {
+                    return Err(mismatch(value));
+                }
+
+                let mut word = self.load(address, location.slot).to_be_bytes::<32>();
+                word[range].copy_from_slice(&bytes);
+                self.store(address, location.slot, U256::from_be_bytes(word));
+            }
+            (StorageEncoding::Mapping | StorageEncoding::DynamicArray, _)
+            | (StorageEncoding::Inplace, _) => {
+                return Err(StorageError::NotAValue {
+                    path: path.to_string(),
+                    ty: ty.label.clone(),
+                })
+            }
+            (StorageEncoding::Bytes, value) => return Err(mismatch(value)),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::db::ExecRes;
+    use serde_json::json;
+
+    fn entry(label: &str, slot: u32, offset: usize, ty: &str) -> serde_json::Value {
+        json!({
+            "astId": 1,
+            "contract": "src/Vault.sol:Vault",
+            "label": label,
+            "offset": offset,
+            "slot": slot.to_string(),
+            "type": ty,
+        })
+    }
+
+    fn value_type(label: &str, size: usize) -> serde_json::Value {
+        json!({ "encoding": "inplace", "label": label, "numberOfBytes": size.to_string() })
+    }
+
+    /// The layout of `uint128 total; int8 delta; bool paused; mapping(address => uint256)
+    /// balances; Position[] positions; string name; uint16[3] fees;
+    /// mapping(string => bytes32) tags`
+    fn layout() -> StorageLayout {
+        serde_json::from_value(json!({
+            "storage": [
+                entry("total", 0, 0, "t_uint128"),
+                entry("delta", 0, 16, "t_int8"),
+                entry("paused", 0, 17, "t_bool"),
+                entry("balances", 1, 0, "t_mapping(t_address,t_uint256)"),
+                entry("positions", 2, 0, "t_array(t_struct(Position)1_storage)dyn_storage"),
+                entry("name", 3, 0, "t_string_storage"),
+                entry("fees", 4, 0, "t_array(t_uint16)3_storage"),
+                entry("tags", 5, 0, "t_mapping(t_string_memory_ptr,t_bytes32)"),
+            ],
+            "types": {
+                "t_address": value_type("address", 20),
+                "t_bool": value_type("bool", 1),
+                "t_bytes32": value_type("bytes32", 32),
+                "t_int8": value_type("int8", 1),
+                "t_uint16": value_type("uint16", 2),
+                "t_uint64": value_type("uint64", 8),
+                "t_uint128": value_type("uint128", 16),
+                "t_uint256": value_type("uint256", 32),
+                "t_mapping(t_address,t_uint256)": {
+                    "encoding": "mapping",
+                    "key": "t_address",
+                    "label": "mapping(address => uint256)",
+                    "numberOfBytes": "32",
+                    "value": "t_uint256",
+                },
+                "t_mapping(t_string_memory_ptr,t_bytes32)": {
+                    "encoding": "mapping",
+                    "key": "t_string_memory_ptr",
+                    "label": "mapping(string => bytes32)",
+                    "numberOfBytes": "32",
+                    "value": "t_bytes32",
+                },
+                "t_array(t_struct(Position)1_storage)dyn_storage": {
+                    "base": "t_struct(Position)1_storage",
+                    "encoding": "dynamic_array",
+                    "label": "struct Vault.Position[]",
+                    "numberOfBytes": "32",
+                },
+                "t_struct(Position)1_storage": {
+                    "encoding": "inplace",
+                    "label": "struct Vault.Position",
+                    "members": [
+                        entry("owner", 0, 0, "t_address"),
+                        entry("size", 0, 20, "t_uint64"),
+                        entry("debt", 1, 0, "t_uint256"),
+                    ],
+                    "numberOfBytes": "64",
+                },
+                "t_array(t_uint16)3_storage": {
+                    "base": "t_uint16",
+                    "encoding": "inplace",
+                    "label": "uint16[3]",
+                    "numberOfBytes": "32",
+                },
+                "t_string_memory_ptr": { "encoding": "bytes", "label": "string", "numberOfBytes": "32" },
+                "t_string_storage": { "encoding": "bytes", "label": "string", "numberOfBytes": "32" },
+            },
+        }))
+        .unwrap()
+    }
+
+    struct Vault(StorageLayout);
+
+    impl Vault {
+        fn new() -> Self {
+            Vault(layout())
+        }
+    }
+
+    impl Contract for Vault {
+        fn storage_layout(&self) -> Option<(Address, &StorageLayout)> {
+            Some((Address::with_last_byte(1), &self.0))
+        }
+
+        fn call(&mut self, _: &mut Provider, _: Vec<u8>) -> ExecRes {
+            unreachable!()
+        }
+
+        fn staticcall(&mut self, _: &mut Provider, _: Vec<u8>) -> ExecRes {
+            unreachable!()
+        }
+
+        fn send(&mut self, _: &mut Provider, _: U256) -> ExecRes {
+            unreachable!()
+        }
+    }
+
+    fn word(value: u64) -> [u8; 32] {
+        U256::from(value).to_be_bytes()
+    }
+
+    #[test]
+    fn locates_variables() {
+        let layout = layout();
+        let paused = locate(&layout, "paused").unwrap();
+        assert_eq!((paused.slot, paused.offset), (U256::ZERO, 17));
+
+        let alice = Address::with_last_byte(0xa);
+        let balance = locate(&layout, &format!("balances[{alice}]")).unwrap();
+        assert_eq!(balance.slot, hash(&[alice.into_word().0, word(1)].concat()));
+        assert_eq!(balance.ty.label, "uint256");
+
+        let debt = locate(&layout, "positions[1].debt").unwrap();
+        assert_eq!(debt.slot, hash(&word(2)) + U256::from(3));
+        let size = locate(&layout, "positions[0].size").unwrap();
+        assert_eq!((size.slot, size.offset), (hash(&word(2)), 20));
+
+        let fee = locate(&layout, "fees[2]").unwrap();
+        assert_eq!((fee.slot, fee.offset), (U256::from(4), 4));
+
+        let tag = locate(&layout, "tags[\"alice\"]").unwrap();
+        assert_eq!(tag.slot, hash(&[b"alice".as_slice(), &word(5)].concat()));
+    }
+
+    #[test]
+    fn packs_value_types() {
+        let mut provider = Provider::default();
+        let vault = Vault::new();
+        provider.write_var(&vault, "total", U256::from(5)).unwrap();
+        provider
+            .write_var(&vault, "delta", I256::try_from(-3).unwrap())
+            .unwrap();
+        provider.write_var(&vault, "paused", true).unwrap();
+
+        let mut packed = [0; 32];
+        packed[31] = 5;
+        packed[15] = 0xfd;
+        packed[14] = 1;
+        assert_eq!(
+            provider.load(Address::with_last_byte(1), U256::ZERO),
+            U256::from_be_bytes(packed)
+        );
+        assert_eq!(
+            provider.read_var(&vault, "delta").unwrap().as_int(),
+            Some(I256::try_from(-3).unwrap())
+        );
+        assert_eq!(
+            provider.read_var(&vault, "paused"),
+            Ok(StorageValue::Bool(true))
+        );
+
+        let alice = Address::with_last_byte(0xa);
+        let path = "positions[1].owner";
+        provider.write_var(&vault, path, alice).unwrap();
+        assert_eq!(
+            provider.read_var(&vault, path).unwrap().as_address(),
+            Some(alice)
+        );
+        assert_eq!(
+            provider.read_var(&vault, "positions[1].size"),
+            Ok(StorageValue::Uint(U256::ZERO))
+        );
+    }
+
+    #[test]
+    fn writes_short_and_long_strings() {
+        let mut provider = Provider::default();
+        let vault = Vault::new();
+        provider.write_var(&vault, "name", "vault").unwrap();
+        let mut short = [0; 32];
+        short[..5].copy_from_slice(b"vault");
+        short[31] = 10;
+        assert_eq!(
+            provider.load(Address::with_last_byte(1), U256::from(3)),
+            U256::from_be_bytes(short)
+        );
+        assert_eq!(
+            provider.read_var(&vault, "name").unwrap().as_str(),
+            Some("vault")
+        );
+
+        let long = "a vault named with more than thirty-one bytes";
+        provider.write_var(&vault, "name", long).unwrap();
+        assert_eq!(
+            provider.read_var(&vault, "name").unwrap().as_str(),
+            Some(long)
+        );
+    }
+
+    #[test]
+    fn rejects_values_not_fitting() {
+        let err = Provider::default()
+            .write_var(&Vault::new(), "delta", I256::try_from(200).unwrap())
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "cannot write Int(200) to `delta`, a `int8`"
+        );
+        let err = Provider::default()
+            .write_var(&Vault::new(), "name", true)
+            .unwrap_err();
+        assert!(matches!(err, StorageError::Mismatch { .. }));
+    }
+
+    #[test]
+    fn rejects_invalid_paths() {
+        let layout = layout();
+        let err = locate(&layout, "debt").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "no state variable `debt`, the contract has: total, delta, paused, balances, \
+             positions, name, fees, tags"
+        );
+        assert!(matches!(
+            locate(&layout, "balances[0x01"),
+            Err(StorageError::InvalidPath { .. })
+        ));
+        assert!(matches!(
+            locate(&layout, "balances[bob]"),
+            Err(StorageError::InvalidKey { .. })
+        ));
+        assert!(matches!(
+            locate(&layout, "fees[3]"),
+            Err(StorageError::OutOfBounds { index: 3, .. })
+        ));
+        assert!(matches!(
+            locate(&layout, "total.value"),
+            Err(StorageError::NoMember { .. })
+        ));
+        assert_eq!(
+            Provider::default()
+                .read_var(&Vault::new(), "balances")
+                .unwrap_err()
+                .to_string(),
+            "`balances` is a `mapping(address => uint256)`, read its members or elements"
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_layouts() {
+        let mut layout = layout();
+        layout.types.as_mut().unwrap().remove("t_bool");
+        assert!(matches!(
+            locate(&layout, "paused"),
+            Err(StorageError::InvalidLayout(_))
+        ));
+        assert!(StorageLayout::from_json("{\"storage\": 1}").is_err());
+    }
+}
@@ -1,4 +1,9 @@
-use revm::primitives::Bytes;
+use core::fmt;
+use revm::primitives::{
+    alloy_primitives::{Signed, Uint},
+    Address, Bytes, FixedBytes, U256,
+};
+use std::error::Error;
 use tiny_keccak::{Hasher, Keccak};
 
 pub enum AbiType {
@@ -117,9 +122,554 @@ pub fn get_sig(signature: &str) -> [u8; 4] {
     sig_raw[0..4].try_into().unwrap()
 }
 
+/// Errors raised while decoding ABI encoded data
+#[derive(Debug, PartialEq, Eq)]
+pub enum AbiError {
+    /// Tried to read past the end of the data
+    OutOfBounds { offset: usize, len: usize },
+    /// The data is well-sized but not a valid encoding for the requested type
+    InvalidData { sol_type: String, reason: String },
+}
+
+impl AbiError {
+    pub fn invalid<T: SolType>(reason: impl Into<String>) -> Self {
+        AbiError::InvalidData {
+            sol_type: T::sol_type(),
+            reason: reason.into(),
+        }
+    }
+}
+
+impl fmt::Display for AbiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::OutOfBounds { offset, len } => {
+                write!(
+                    f,
+                    "abi decoding out of bounds: offset {offset} in {len} bytes"
+                )
+            }
+            Self::InvalidData { sol_type, reason } => {
+                write!(f, "invalid abi encoding for {sol_type}: {reason}")
+            }
+        }
+    }
+}
+
+impl Error for AbiError {}
+
+/// A Rust type with a canonical Solidity ABI type, see `#[derive(AbiType)]`
+pub trait SolType {
+    /// The canonical type, as written in a function signature (e.g. `(uint256,address)[]`)
+    fn sol_type() -> String;
+
+    /// Whether the encoding is dynamically sized, i.e. lives in the tail of a tuple
+    fn is_dynamic() -> bool {
+        false
+    }
+
+    /// Number of bytes taken in the head of an enclosing tuple
+    fn head_len() -> usize {
+        32
+    }
+}
+
+pub trait AbiEncode: SolType {
+    /// Static types are encoded in place, dynamic types as their tail (without the offset)
+    fn abi_encode(&self) -> Vec<u8>;
+}
+
+pub trait AbiDecode: SolType + Sized {
+    /// Decodes a value which encoding starts at `offset`
+    fn abi_decode_at(data: &[u8], offset: usize) -> Result<Self, AbiError>;
+
+    fn abi_decode(data: &[u8]) -> Result<Self, AbiError> {
+        Self::abi_decode_at(data, 0)
+    }
+}
+
+/// Encodes the heads then the tails of a tuple, given each field as `(is_dynamic, encoding)`
+pub fn encode_tuple(fields: Vec<(bool, Vec<u8>)>) -> Vec<u8> {
+    let heads_len: usize = fields
+        .iter()
+        .map(|(dynamic, enc)| if *dynamic { 32 } else { enc.len() })
+        .sum();
+    let mut heads = Vec::with_capacity(heads_len);
+    let mut tails = Vec::new();
+    for (dynamic, enc) in fields {
+        if dynamic {
+            heads.extend_from_slice(&encode_usize(heads_len + tails.len()));
+            tails.extend(enc);
+        } else {
+            heads.extend(enc);
+        }
+    }
+    heads.extend(tails);
+    heads
+}
+
+/// Walks over the heads of an encoded tuple, following offsets for dynamic fields
+pub struct TupleDecoder<'a> {
+    data: &'a [u8],
+    base: usize,
+    head: usize,
+}
+
+impl<'a> TupleDecoder<'a> {
+    pub fn new(data: &'a [u8], base: usize) -> Self {
+        Self {
+            data,
+            base,
+            head: base,
+        }
+    }
+
+    pub fn decode<T: AbiDecode>(&mut self) -> Result<T, AbiError> {
+        let value = if T::is_dynamic() {
+            let offset = decode_usize(self.data, self.head)?;
+            let at = self.base.checked_add(offset).ok_or(AbiError::OutOfBounds {
+                offset,
+                len: self.data.len(),
+            })?;
+            T::abi_decode_at(self.data, at)?
+        } else {
+            T::abi_decode_at(self.data, self.head)?
+        };
+        self.head += T::head_len();
+        Ok(value)
+    }
+}
+
+/// Function selector followed by the encoded parameters, `params` being a tuple
+pub fn abi_encode_call<T: AbiEncode>(signature: &str, params: &T) -> Vec<u8> {
+    [get_sig(signature).to_vec(), params.abi_encode()].concat()
+}
+
+fn read(data: &[u8], offset: usize, len: usize) -> Result<&[u8], AbiError> {
+    offset
+        .checked_add(len)
+        .and_then(|end| data.get(offset..end))
+        .ok_or(AbiError::OutOfBounds {
+            offset,
+            len: data.len(),
+        })
+}
+
+fn read_word(data: &[u8], offset: usize) -> Result<&[u8; 32], AbiError> {
+    Ok(read(data, offset, 32)?.try_into().unwrap())
+}
+
+fn encode_usize(value: usize) -> [u8; 32] {
+    let mut word = [0u8; 32];
+    word[24..].copy_from_slice(&(value as u64).to_be_bytes());
+    word
+}
+
+fn decode_usize(data: &[u8], offset: usize) -> Result<usize, AbiError> {
+    let word = read_word(data, offset)?;
+    if word[..24].iter().any(|b| *b != 0) {
+        return Err(AbiError::InvalidData {
+            sol_type: String::from("uint256"),
+            reason: String::from("length or offset overflows"),
+        });
+    }
+    Ok(u64::from_be_bytes(word[24..].try_into().unwrap()) as usize)
+}
+
+/// Right pads to a multiple of 32 bytes
+fn pad_right(data: &[u8]) -> Vec<u8> {
+    let mut padded = data.to_vec();
+    padded.resize(data.len().div_ceil(32) * 32, 0);
+    padded
+}
+
+fn encode_packed_bytes(data: &[u8]) -> Vec<u8> {
+    [encode_usize(data.len()).to_vec(), pad_right(data)].concat()
+}
+
+fn decode_packed_bytes(data: &[u8], offset: usize) -> Result<&[u8], AbiError> {
+    let len = decode_usize(data, offset)?;
+    read(data, offset + 32, len)
+}
+
+impl SolType for bool {
+    fn sol_type() -> String {
+        String::from("bool")
+    }
+}
+
+impl AbiEncode for bool {
+    fn abi_encode(&self) -> Vec<u8> {
+        let mut word = [0u8; 32];
+        word[31] = *self as u8;
+        word.to_vec()
+    }
+}
+
+impl AbiDecode for bool {
+    fn abi_decode_at(data: &[u8], offset: usize) -> Result<Self, AbiError> {
+        let word = read_word(data, offset)?;
+        match U256::from_be_bytes(*word) {
+            v if v == U256::ZERO => Ok(false),
+            v if v == U256::from(1) => Ok(true),
+            _ => Err(AbiError::invalid::<Self>("neither 0 nor 1")),
+        }
+    }
+}
+
+macro_rules! impl_abi_native_int {
+    ($($ty:ty => $sol:literal),*) => {$(
+        impl SolType for $ty {
+            fn sol_type() -> String {
+                String::from($sol)
+            }
+        }
+
+        impl AbiEncode for $ty {
+            #[allow(unused_comparisons)]
+            fn abi_encode(&self) -> Vec<u8> {
+                // sign extension, zeros for unsigned types
+                let fill = if *self < (0 as $ty) { 0xff } else { 0 };
+                let mut word = [fill; 32];
+                let bytes = self.to_be_bytes();
+                word[(32 - bytes.len())..].copy_from_slice(&bytes);
+                word.to_vec()
+            }
+        }
+
+        impl AbiDecode for $ty {
+            #[allow(unused_comparisons)]
+            fn abi_decode_at(data: &[u8], offset: usize) -> Result<Self, AbiError> {
+                let word = read_word(data, offset)?;
+                let size = std::mem::size_of::<$ty>();
+                let value = <$ty>::from_be_bytes(word[(32 - size)..].try_into().unwrap());
+                let fill = if value < (0 as $ty) { 0xff } else { 0 };
+                if word[..(32 - size)].iter().any(|b| *b != fill) {
+                    return Err(AbiError::invalid::<Self>("value out of range"));
+                }
+                Ok(value)
+            }
+        }
+    )*};
+}
+
+impl_abi_native_int!(
+    u8 => "uint8", u16 => "uint16", u32 => "uint32", u64 => "uint64", u128 => "uint128",
+    i8 => "int8", i16 => "int16", i32 => "int32", i64 => "int64", i128 => "int128"
+);
+
+/// Solidity integers are 8 to 256 bits wide, by steps of 8
+struct IntBits<const BITS: usize>;
+
+impl<const BITS: usize> IntBits<BITS> {
+    const VALID: () = assert!(
+        BITS > 0 && BITS <= 256 && BITS.is_multiple_of(8),
+        "Solidity integers are 8 to 256 bits wide, by steps of 8"
+    );
+}
+
+/// Only the Solidity widths compile:
+///
+/// ```compile_fail
+/// use revm::primitives::alloy_primitives::Uint;
+/// use rustry_test::utils::abi::SolType;
+///
+/// Uint::<7, 1>::sol_type();
+/// ```
+impl<const BITS: usize, const LIMBS: usize> SolType for Uint<BITS, LIMBS> {
+    fn sol_type() -> String {
+        let () = IntBits::<BITS>::VALID;
+        format!("uint{BITS}")
+    }
+}
+
+impl<const BITS: usize, const LIMBS: usize> AbiEncode for Uint<BITS, LIMBS> {
+    fn abi_encode(&self) -> Vec<u8> {
+        let () = IntBits::<BITS>::VALID;
+        let bytes = self.to_be_bytes_vec();
+        let mut word = vec![0u8; 32 - bytes.len()];
+        word.extend(bytes);
+        word
+    }
+}
+
+impl<const BITS: usize, const LIMBS: usize> AbiDecode for Uint<BITS, LIMBS> {
+    fn abi_decode_at(data: &[u8], offset: usize) -> Result<Self, AbiError> {
+        let () = IntBits::<BITS>::VALID;
+        let word = read_word(data, offset)?;
+        Self::try_from_be_slice(word).ok_or_else(|| AbiError::invalid::<Self>("value out of range"))
+    }
+}
+
+impl<const BITS: usize, const LIMBS: usize> SolType for Signed<BITS, LIMBS> {
+    fn sol_type() -> String {
+        let () = IntBits::<BITS>::VALID;
+        format!("int{BITS}")
+    }
+}
+
+impl<const BITS: usize, const LIMBS: usize> AbiEncode for Signed<BITS, LIMBS> {
+    fn abi_encode(&self) -> Vec<u8> {
+        let () = IntBits::<BITS>::VALID;
+        let bytes = self.into_raw().to_be_bytes_vec();
+        let fill = if self.is_negative() { 0xff } else { 0 };
+        let mut word = vec![fill; 32 - bytes.len()];
+        word.extend(bytes);
+        word
+    }
+}
+
+impl<const BITS: usize, const LIMBS: usize> AbiDecode for Signed<BITS, LIMBS> {
+    fn abi_decode_at(data: &[u8], offset: usize) -> Result<Self, AbiError> {
+        let () = IntBits::<BITS>::VALID;
+        let word = read_word(data, offset)?;
+        let size = BITS / 8;
+        let value = Self::try_from_be_slice(&word[(32 - size)..])
+            .ok_or_else(|| AbiError::invalid::<Self>("value out of range"))?;
+        let fill = if value.is_negative() { 0xff } else { 0 };
+        if word[..(32 - size)].iter().any(|b| *b != fill) {
+            return Err(AbiError::invalid::<Self>("value out of range"));
+        }
+        Ok(value)
+    }
+}
+
+impl SolType for Address {
+    fn sol_type() -> String {
+        String::from("address")
+    }
+}
+
+impl AbiEncode for Address {
+    fn abi_encode(&self) -> Vec<u8> {
+        [[0u8; 12].as_slice(), self.as_slice()].concat()
+    }
+}
+
+impl AbiDecode for Address {
+    fn abi_decode_at(data: &[u8], offset: usize) -> Result<Self, AbiError> {
+        let word = read_word(data, offset)?;
+        if word[..12].iter().any(|b| *b != 0) {
+            return Err(AbiError::invalid::<Self>("dirty high bytes"));
+        }
+        Ok(Address::from_slice(&word[12..]))
+    }
+}
+
+impl<const N: usize> SolType for FixedBytes<N> {
+    fn sol_type() -> String {
+        format!("bytes{N}")
+    }
+}
+
+impl<const N: usize> AbiEncode for FixedBytes<N> {
+    fn abi_encode(&self) -> Vec<u8> {
+        pad_right(self.as_slice())
+    }
+}
+
+impl<const N: usize> AbiDecode for FixedBytes<N> {
+    fn abi_decode_at(data: &[u8], offset: usize) -> Result<Self, AbiError> {
+        let word = read_word(data, offset)?;
+        if N > 32 || word[N..].iter().any(|b| *b != 0) {
+            return Err(AbiError::invalid::<Self>("dirty low bytes"));
+        }
+        Ok(FixedBytes::from_slice(&word[..N]))
+    }
+}
+
+impl SolType for Bytes {
+    fn sol_type() -> String {
+        String::from("bytes")
+    }
+
+    fn is_dynamic() -> bool {
+        true
+    }
+}
+
+impl AbiEncode for Bytes {
+    fn abi_encode(&self) -> Vec<u8> {
+        encode_packed_bytes(self)
+    }
+}
+
+impl AbiDecode for Bytes {
+    fn abi_decode_at(data: &[u8], offset: usize) -> Result<Self, AbiError> {
+        Ok(decode_packed_bytes(data, offset)?.to_vec().into())
+    }
+}
+
+impl SolType for String {
+    fn sol_type() -> String {
+        String::from("string")
+    }
+
+    fn is_dynamic() -> bool {
+        true
+    }
+}
+
+impl AbiEncode for String {
+    fn abi_encode(&self) -> Vec<u8> {
+        encode_packed_bytes(self.as_bytes())
+    }
+}
+
+impl AbiDecode for String {
+    fn abi_decode_at(data: &[u8], offset: usize) -> Result<Self, AbiError> {
+        String::from_utf8(decode_packed_bytes(data, offset)?.to_vec())
+            .map_err(|e| AbiError::invalid::<Self>(e.to_string()))
+    }
+}
+
+impl<T: SolType> SolType for Vec<T> {
+    fn sol_type() -> String {
+        format!("{}[]", T::sol_type())
+    }
+
+    fn is_dynamic() -> bool {
+        true
+    }
+}
+
+impl<T: AbiEncode> AbiEncode for Vec<T> {
+    fn abi_encode(&self) -> Vec<u8> {
+        let items = self
+            .iter()
+            .map(|item| (T::is_dynamic(), item.abi_encode()))
+            .collect();
+        [encode_usize(self.len()).to_vec(), encode_tuple(items)].concat()
+    }
+}
+
+impl<T: AbiDecode> AbiDecode for Vec<T> {
+    fn abi_decode_at(data: &[u8], offset: usize) -> Result<Self, AbiError> {
+        let len = decode_usize(data, offset)?;
+        // each item takes at least a word, don't trust the length for allocating
+        read(data, offset + 32, len.saturating_mul(32))?;
+        let mut decoder = TupleDecoder::new(data, offset + 32);
+        (0..len).map(|_| decoder.decode()).collect()
+    }
+}
+
+impl<T: SolType, const N: usize> SolType for [T; N] {
+    fn sol_type() -> String {
+        format!("{}[{N}]", T::sol_type())
+    }
+
+    fn is_dynamic() -> bool {
+        T::is_dynamic()
+    }
+
+    fn head_len() -> usize {
+        if Self::is_dynamic() {
+            32
+        } else {
+            N * T::head_len()
+        }
+    }
+}
+
+impl<T: AbiEncode, const N: usize> AbiEncode for [T; N] {
+    fn abi_encode(&self) -> Vec<u8> {
+        encode_tuple(
+            self.iter()
+                .map(|item| (T::is_dynamic(), item.abi_encode()))
+                .collect(),
+        )
+    }
+}
+
+impl<T: AbiDecode, const N: usize> AbiDecode for [T; N] {
+    fn abi_decode_at(data: &[u8], offset: usize) -> Result<Self, AbiError> {
+        let mut decoder = TupleDecoder::new(data, offset);
+        let items = (0..N)
+            .map(|_| decoder.decode())
+            .collect::<Result<Vec<T>, _>>()?;
+        Ok(items.try_into().unwrap_or_else(|_| unreachable!()))
+    }
+}
+
+impl<T: SolType + ?Sized> SolType for &T {
+    fn sol_type() -> String {
+        T::sol_type()
+    }
+
+    fn is_dynamic() -> bool {
+        T::is_dynamic()
+    }
+
+    fn head_len() -> usize {
+        T::head_len()
+    }
+}
+
+impl<T: AbiEncode + ?Sized> AbiEncode for &T {
+    fn abi_encode(&self) -> Vec<u8> {
+        (**self).abi_encode()
+    }
+}
+
+macro_rules! impl_abi_tuple {
+    ($($ty:ident),*) => {
+        impl<$($ty: SolType),*> SolType for ($($ty,)*) {
+            fn sol_type() -> String {
+                let types: Vec<String> = vec![$($ty::sol_type()),*];
+                format!("({})", types.join(","))
+            }
+
+            fn is_dynamic() -> bool {
+                false $(|| $ty::is_dynamic())*
+            }
+
+            fn head_len() -> usize {
+                if Self::is_dynamic() {
+                    32
+                } else {
+                    0 $(+ $ty::head_len())*
+                }
+            }
+        }
+
+        impl<$($ty: AbiEncode),*> AbiEncode for ($($ty,)*) {
+            #[allow(non_snake_case)]
+            fn abi_encode(&self) -> Vec<u8> {
+                let ($($ty,)*) = self;
+                encode_tuple(vec![$(($ty::is_dynamic(), $ty.abi_encode())),*])
+            }
+        }
+
+        impl<$($ty: AbiDecode),*> AbiDecode for ($($ty,)*) {
+            #[allow(unused_mut, unused_variables)]
+            fn abi_decode_at(data: &[u8], offset: usize) -> Result<Self, AbiError> {
+                let mut decoder = TupleDecoder::new(data, offset);
+                Ok(($(decoder.decode::<$ty>()?,)*))
+            }
+        }
+    };
+}
+
+impl_abi_tuple!();
+impl_abi_tuple!(A);
+impl_abi_tuple!(A, B);
+impl_abi_tuple!(A, B, C);
+impl_abi_tuple!(A, B, C, D);
+impl_abi_tuple!(A, B, C, D, E);
+impl_abi_tuple!(A, B, C, D, E, F);
+impl_abi_tuple!(A, B, C, D, E, F, G);
+impl_abi_tuple!(A, B, C, D, E, F, G, H);
+impl_abi_tuple!(A, B, C, D, E, F, G, H, I);
+impl_abi_tuple!(A, B, C, D, E, F, G, H, I, J);
+impl_abi_tuple!(A, B, C, D, E, F, G, H, I, J, K);
+impl_abi_tuple!(A, B, C, D, E, F, G, H, I, J, K, L);
+
 #[cfg(test)]
 mod tests {
-    use super::{abi_encode_signature, get_sig};
+    use super::{
+        abi_encode_call, abi_encode_signature, get_sig, AbiDecode, AbiEncode, AbiError, SolType,
+    };
+    use revm::primitives::{alloy_primitives::Signed, Address, Bytes, FixedBytes, U256};
 
     #[test]
     fn good_sig() {
@@ -142,4 +692,72 @@ mod tests {
             .concat()
         );
     }
+
+    #[test]
+    fn sol_types() {
+        assert_eq!(<(U256, Address)>::sol_type(), "(uint256,address)");
+        assert_eq!(<Vec<[u8; 2]>>::sol_type(), "uint8[2][]");
+        assert_eq!(<(bool, Vec<String>)>::sol_type(), "(bool,string[])");
+        assert_eq!(<FixedBytes<4>>::sol_type(), "bytes4");
+        assert_eq!(<Signed<24, 1>>::sol_type(), "int24");
+        assert_eq!(<Vec<u8>>::sol_type(), "uint8[]");
+        assert!(!<[U256; 3]>::is_dynamic());
+        assert_eq!(<([U256; 3], bool)>::head_len(), 128);
+        assert!(<[Bytes; 3]>::is_dynamic());
+    }
+
+    #[test]
+    fn encode_static() {
+        assert_eq!(
+            (U256::from(1), -1i8).abi_encode(),
+            [[0u8; 31].to_vec(), vec![1], [0xffu8; 32].to_vec()].concat()
+        );
+        assert_eq!(
+            abi_encode_call("transfer(uint256,address)", &(U256::ZERO, Address::ZERO)),
+            [[0xb7, 0x76, 0x0c, 0x8f].to_vec(), [0; 64].to_vec()].concat()
+        );
+    }
+
+    #[test]
+    fn encode_dynamic() {
+        // f(uint256,string) with (1, "abc")
+        let encoded = (U256::from(1), String::from("abc")).abi_encode();
+        let expected = [
+            U256::from(1).to_be_bytes::<32>().to_vec(),
+            U256::from(64).to_be_bytes::<32>().to_vec(),
+            U256::from(3).to_be_bytes::<32>().to_vec(),
+            [b"abc".to_vec(), vec![0; 29]].concat(),
+        ]
+        .concat();
+        assert_eq!(encoded, expected);
+    }
+
+    #[test]
+    fn roundtrip() {
+        let value = (
+            vec![String::from("hello"), String::new()],
+            [U256::from(7), U256::MAX],
+            Address::repeat_byte(0x42),
+            Bytes::from(vec![1, 2, 3]),
+            (true, -42i128, Signed::<256, 4>::try_from(-1).unwrap()),
+        );
+        let encoded = value.abi_encode();
+        assert_eq!(AbiDecode::abi_decode(&encoded), Ok(value));
+    }
+
+    #[test]
+    fn decode_errors() {
+        assert!(matches!(
+            U256::abi_decode(&[0u8; 31]),
+            Err(AbiError::OutOfBounds { .. })
+        ));
+        assert!(matches!(
+            bool::abi_decode(&[2u8; 32]),
+            Err(AbiError::InvalidData { .. })
+        ));
+        assert!(matches!(
+            u8::abi_decode(&U256::from(256).to_be_bytes::<32>()),
+            Err(AbiError::InvalidData { .. })
+        ));
+    }
 }
@@ -0,0 +1,489 @@
+//! Assembler and disassembler of EVM bytecode, for the contracts too small to be worth a compiler
+//!
+//! A listing is made of whitespace-separated instructions, `//` or `;` starting a comment:
+//!
+//! ```text
+//! PUSH1 0x00 CALLDATALOAD      // mnemonics are case-insensitive
+//! PUSH end JUMPI               // `PUSH` alone picks the smallest size, labels take 2 bytes
+//! PUSH0 PUSH0 REVERT
+//! end: JUMPDEST                // labels mark an offset, the `JUMPDEST` is explicit
+//! PUSH2 1000 STOP              // immediates are decimal, hex or a label
+//! 0xdeadbeef                   // hex literals are emitted as is
+//! ```
+
+use core::fmt;
+use revm::primitives::U256;
+use std::collections::HashMap;
+
+/// Opcodes with a fixed name, the families (`PUSHn`, `DUPn`, `SWAPn`, `LOGn`) being computed
+const OPCODES: &[(u8, &str)] = &[
+    (0x00, "STOP"),
+    (0x01, "ADD"),
+    (0x02, "MUL"),
+    (0x03, "SUB"),
+    (0x04, "DIV"),
+    (0x05, "SDIV"),
+    (0x06, "MOD"),
+    (0x07, "SMOD"),
+    (0x08, "ADDMOD"),
+    (0x09, "MULMOD"),
+    (0x0a, "EXP"),
+    (0x0b, "SIGNEXTEND"),
+    (0x10, "LT"),
+    (0x11, "GT"),
+    (0x12, "SLT"),
+    (0x13, "SGT"),
+    (0x14, "EQ"),
+    (0x15, "ISZERO"),
+    (0x16, "AND"),
+    (0x17, "OR"),
+    (0x18, "XOR"),
+    (0x19, "NOT"),
+    (0x1a, "BYTE"),
+    (0x1b, "SHL"),
+    (0x1c, "SHR"),
+    (0x1d, "SAR"),
+    (0x20, "KECCAK256"),
+    (0x30, "ADDRESS"),
+    (0x31, "BALANCE"),
+    (0x32, "ORIGIN"),
+    (0x33, "CALLER"),
+    (0x34, "CALLVALUE"),
+    (0x35, "CALLDATALOAD"),
+    (0x36, "CALLDATASIZE"),
+    (0x37, "CALLDATACOPY"),
+    (0x38, "CODESIZE"),
+    (0x39, "CODECOPY"),
+    (0x3a, "GASPRICE"),
+    (0x3b, "EXTCODESIZE"),
+    (0x3c, "EXTCODECOPY"),
+    (0x3d, "RETURNDATASIZE"),
+    (0x3e, "RETURNDATACOPY"),
+    (0x3f, "EXTCODEHASH"),
+    (0x40, "BLOCKHASH"),
+    (0x41, "COINBASE"),
+    (0x42, "TIMESTAMP"),
+    (0x43, "NUMBER"),
+    (0x44, "PREVRANDAO"),
+    (0x45, "GASLIMIT"),
+    (0x46, "CHAINID"),
+    (0x47, "SELFBALANCE"),
+    (0x48, "BASEFEE"),
+    (0x49, "BLOBHASH"),
+    (0x4a, "BLOBBASEFEE"),
+    (0x50, "POP"),
+    (0x51, "MLOAD"),
+    (0x52, "MSTORE"),
+    (0x53, "MSTORE8"),
+    (0x54, "SLOAD"),
+    (0x55, "SSTORE"),
+    (0x56, "JUMP"),
+    (0x57, "JUMPI"),
+    (0x58, "PC"),
+    (0x59, "MSIZE"),
+    (0x5a, "GAS"),
+    (0x5b, "JUMPDEST"),
+    (0x5c, "TLOAD"),
+    (0x5d, "TSTORE"),
+    (0x5e, "MCOPY"),
+    (0x5f, "PUSH0"),
+    (0xf0, "CREATE"),
+    (0xf1, "CALL"),
+    (0xf2, "CALLCODE"),
+    (0xf3, "RETURN"),
+    (0xf4, "DELEGATECALL"),
+    (0xf5, "CREATE2"),
+    (0xfa, "STATICCALL"),
+    (0xfd, "REVERT"),
+    (0xfe, "INVALID"),
+    (0xff, "SELFDESTRUCT"),
+];
+
+/// Former names of opcodes
+const ALIASES: &[(&str, u8)] = &[("SHA3", 0x20), ("DIFFICULTY", 0x44), ("SUICIDE", 0xff)];
+
+/// The mnemonic of an opcode, `None` when it is unassigned
+pub fn mnemonic(opcode: u8) -> Option<String> {
+    match opcode {
+        0x60..=0x7f => Some(format!("PUSH{}", opcode - 0x5f)),
+        0x80..=0x8f => Some(format!("DUP{}", opcode - 0x7f)),
+        0x90..=0x9f => Some(format!("SWAP{}", opcode - 0x8f)),
+        0xa0..=0xa4 => Some(format!("LOG{}", opcode - 0xa0)),
+        _ => OPCODES
+            .iter()
+            .find(|(op, _)| *op == opcode)
+            .map(|(_, name)| name.to_string()),
+    }
+}
+
+/// The opcode of a mnemonic, in any case
+pub fn opcode(mnemonic: &str) -> Option<u8> {
+    let mnemonic = mnemonic.to_ascii_uppercase();
+    let family = |prefix: &str, first: u8, range: std::ops::RangeInclusive<u8>| {
+        let n: u8 = mnemonic.strip_prefix(prefix)?.parse().ok()?;
+        range.contains(&n).then(|| first + n - range.start())
+    };
+    family("PUSH", 0x60, 1..=32)
+        .or_else(|| family("DUP", 0x80, 1..=16))
+        .or_else(|| family("SWAP", 0x90, 1..=16))
+        .or_else(|| family("LOG", 0xa0, 0..=4))
+        .or_else(|| {
+            OPCODES
+                .iter()
+                .find(|(_, name)| *name == mnemonic)
+                .map(|(op, _)| *op)
+        })
+        .or_else(|| {
+            ALIASES
+                .iter()
+                .find(|(name, _)| *name == mnemonic)
+                .map(|(_, op)| *op)
+        })
+}
+
+/// Size of the immediate following an opcode
+fn immediate_size(opcode: u8) -> usize {
+    match opcode {
+        0x60..=0x7f => usize::from(opcode - 0x5f),
+        _ => 0,
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct AsmError {
+    /// Line of the listing, from 1
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+enum Item<'a> {
+    Label(&'a str),
+    Bytes(Vec<u8>),
+    /// A push of the offset of a label
+    Ref {
+        opcode: u8,
+        label: &'a str,
+    },
+}
+
+/// Bytes of a hex literal, left-padded to a whole byte
+fn hex_bytes(digits: &str) -> Option<Vec<u8>> {
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let padded = if digits.len() % 2 == 1 {
+        format!("0{digits}")
+    } else {
+        digits.to_string()
+    };
+    (0..padded.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&padded[i..i + 2], 16).ok())
+        .collect()
+}
+
+/// The minimal big-endian bytes of a hex or decimal immediate
+fn number(token: &str) -> Option<Vec<u8>> {
+    let value = match token.strip_prefix("0x") {
+        Some(digits) => U256::try_from_be_slice(&hex_bytes(digits)?)?,
+        None if token.starts_with(|c: char| c.is_ascii_digit()) => {
+            U256::from_str_radix(token, 10).ok()?
+        }
+        None => return None,
+    };
+    let bytes = value.to_be_bytes::<32>();
+    let start = bytes.iter().position(|byte| *byte != 0).unwrap_or(31);
+    Some(bytes[start..].to_vec())
+}
+
+fn is_label(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Assembles a listing into bytecode
+pub fn assemble(listing: &str) -> Result<Vec<u8>, AsmError> {
+    let mut tokens = listing.lines().enumerate().flat_map(|(i, line)| {
+        let line_end = [line.find("//"), line.find(';')]
+            .into_iter()
+            .flatten()
+            .min()
+            .unwrap_or(line.len());
+        line[..line_end]
+            .split_whitespace()
+            .map(move |token| (i + 1, token))
+    });
+
+    let mut items = vec![];
+    while let Some((line, token)) = tokens.next() {
+        let error = |message: String| AsmError { line, message };
+
+        if let Some(label) = token.strip_suffix(':') {
+            if !is_label(label) {
+                return Err(error(format!("invalid label `{label}`")));
+            }
+            items.push((line, Item::Label(label)));
+            continue;
+        }
+        if let Some(digits) = token.strip_prefix("0x") {
+            let bytes = hex_bytes(digits).ok_or_else(|| error(format!("invalid hex `{token}`")))?;
+            items.push((line, Item::Bytes(bytes)));
+            continue;
+        }
+
+        let minimal = token.eq_ignore_ascii_case("PUSH");
+        let op = if minimal {
+            None
+        } else {
+            Some(opcode(token).ok_or_else(|| error(format!("unknown instruction `{token}`")))?)
+        };
+        let size = op.map_or(0, immediate_size);
+        if !minimal && size == 0 {
+            items.push((line, Item::Bytes(vec![op.unwrap()])));
+            continue;
+        }
+
+        let (_, immediate) = tokens
+            .next()
+            .ok_or_else(|| error(format!("`{token}` is missing its immediate")))?;
+        if is_label(immediate) {
+            let opcode = op.unwrap_or(0x61);
+            items.push((
+                line,
+                Item::Ref {
+                    opcode,
+                    label: immediate,
+                },
+            ));
+            continue;
+        }
+        let value = number(immediate)
+            .ok_or_else(|| error(format!("invalid immediate `{immediate}` of `{token}`")))?;
+        let size = if minimal { value.len() } else { size };
+        if value.len() > size {
+            return Err(error(format!("{immediate} doesn't fit in `{token}`")));
+        }
+        let mut bytes = vec![0x5f + size as u8];
+        bytes.resize(1 + size - value.len(), 0);
+        bytes.extend(value);
+        items.push((line, Item::Bytes(bytes)));
+    }
+
+    let mut labels = HashMap::new();
+    let mut offset = 0;
+    for (line, item) in &items {
+        match item {
+            Item::Label(label) => {
+                if labels.insert(*label, offset).is_some() {
+                    return Err(AsmError {
+                        line: *line,
+                        message: format!("label `{label}` defined twice"),
+                    });
+                }
+            }
+            Item::Bytes(bytes) => offset += bytes.len(),
+            Item::Ref { opcode, .. } => offset += 1 + immediate_size(*opcode),
+        }
+    }
+
+    let mut code = Vec::with_capacity(offset);
+    for (line, item) in items {
+        match item {
+            Item::Label(_) => {}
+            Item::Bytes(bytes) => code.extend(bytes),
+            Item::Ref { opcode, label } => {
+                let error = |message: String| AsmError { line, message };
+                let target = *labels
+                    .get(label)
+                    .ok_or_else(|| error(format!("unknown label `{label}`")))?;
+                let size = immediate_size(opcode);
+                let bytes = target.to_be_bytes();
+                if bytes[..bytes.len().saturating_sub(size)]
+                    .iter()
+                    .any(|byte| *byte != 0)
+                {
+                    return Err(error(format!(
+                        "the offset of `{label}` doesn't fit in {size} bytes"
+                    )));
+                }
+                code.push(opcode);
+                code.extend(std::iter::repeat_n(0, size.saturating_sub(bytes.len())));
+                code.extend(&bytes[bytes.len().saturating_sub(size)..]);
+            }
+        }
+    }
+    Ok(code)
+}
+
+/// Creation code deploying `runtime` as is, which its `PUSH2` limits to 64KiB
+pub fn deployer(runtime: &[u8]) -> Result<Vec<u8>, String> {
+    let len = u16::try_from(runtime.len()).map_err(|_| {
+        format!(
+            "the code is {} bytes long, over the 65535 that the deployer can copy",
+            runtime.len()
+        )
+    })?;
+    let [high, low] = len.to_be_bytes();
+    // PUSH2 len DUP1 PUSH1 12 PUSH1 0 CODECOPY PUSH1 0 RETURN, without PUSH0 for the EVM versions
+    // before Shanghai
+    let mut code = vec![
+        0x61, high, low, 0x80, 0x60, 0x0c, 0x60, 0x00, 0x39, 0x60, 0x00, 0xf3,
+    ];
+    code.extend(runtime);
+    Ok(code)
+}
+
+/// An instruction of disassembled code
+#[derive(Debug, Clone, PartialEq)]
+pub struct Instruction {
+    pub offset: usize,
+    pub opcode: u8,
+    /// The immediate of a push, truncated at the end of the code
+    pub immediate: Vec<u8>,
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match mnemonic(self.opcode) {
+            Some(name) => write!(f, "{name}")?,
+            None => write!(f, "UNKNOWN(0x{:02x})", self.opcode)?,
+        }
+        if !self.immediate.is_empty() {
+            write!(f, " 0x")?;
+            for byte in &self.immediate {
+                write!(f, "{byte:02x}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+pub fn disassemble(code: &[u8]) -> Vec<Instruction> {
+    let mut instructions = vec![];
+    let mut offset = 0;
+    while offset < code.len() {
+        let opcode = code[offset];
+        let end = (offset + 1 + immediate_size(opcode)).min(code.len());
+        instructions.push(Instruction {
+            offset,
+            opcode,
+            immediate: code[offset + 1..end].to_vec(),
+        });
+        offset = end;
+    }
+    instructions
+}
+
+/// The disassembled code, one instruction per line prefixed by its offset
+pub fn listing(code: &[u8]) -> String {
+    disassemble(code)
+        .iter()
+        .map(|instruction| format!("{:04x}  {instruction}\n", instruction.offset))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{assemble, deployer, disassemble, listing, mnemonic, opcode, AsmError};
+
+    #[test]
+    fn opcodes() {
+        for op in 0..=u8::MAX {
+            if let Some(name) = mnemonic(op) {
+                assert_eq!(opcode(&name), Some(op), "{name}");
+            }
+        }
+        assert_eq!(opcode("push32"), Some(0x7f));
+        assert_eq!(opcode("SHA3"), Some(0x20));
+        assert_eq!(opcode("PUSH33"), None);
+        assert_eq!(opcode("LOG5"), None);
+        assert_eq!(mnemonic(0x0c), None);
+    }
+
+    #[test]
+    fn assembly() {
+        let code = assemble(
+            "
+            PUSH1 0x00 CALLDATALOAD   // the first word
+            push end JUMPI            ; to the end if non-zero
+            PUSH0 PUSH0 REVERT
+            end: JUMPDEST
+            PUSH 256 PUSH2 42 PUSH32 1
+            0xdeadbeef
+            ",
+        )
+        .unwrap();
+        let mut expected = vec![
+            0x60, 0x00, 0x35, 0x61, 0x00, 0x0a, 0x57, 0x5f, 0x5f, 0xfd, 0x5b, 0x61, 0x01, 0x00,
+            0x61, 0x00, 0x2a, 0x7f,
+        ];
+        expected.extend([0; 31]);
+        expected.extend([0x01, 0xde, 0xad, 0xbe, 0xef]);
+        assert_eq!(code, expected);
+
+        assert_eq!(assemble("0x602a5f52").unwrap(), [0x60, 0x2a, 0x5f, 0x52]);
+        assert_eq!(
+            assemble("PUSH1 0x1 PUSH 0").unwrap(),
+            [0x60, 0x01, 0x60, 0x00]
+        );
+
+        let error = |line, message: &str| {
+            Err(AsmError {
+                line,
+                message: message.to_string(),
+            })
+        };
+        assert_eq!(assemble("ADD\nFOO"), error(2, "unknown instruction `FOO`"));
+        assert_eq!(
+            assemble("PUSH1 256"),
+            error(1, "256 doesn't fit in `PUSH1`")
+        );
+        assert_eq!(
+            assemble("PUSH1"),
+            error(1, "`PUSH1` is missing its immediate")
+        );
+        assert_eq!(
+            assemble("PUSH2 nowhere"),
+            error(1, "unknown label `nowhere`")
+        );
+        assert_eq!(assemble("a: a:"), error(1, "label `a` defined twice"));
+        assert_eq!(assemble("0xzz"), error(1, "invalid hex `0xzz`"));
+
+        assert_eq!(deployer(&[0; 0xffff]).unwrap().len(), 12 + 0xffff);
+        assert_eq!(
+            deployer(&[0; 0x10000]),
+            Err(String::from(
+                "the code is 65536 bytes long, over the 65535 that the deployer can copy"
+            ))
+        );
+    }
+
+    #[test]
+    fn disassembly() {
+        let code = [0x60, 0x80, 0x60, 0x40, 0x52, 0x0c, 0x61, 0x01];
+        let instructions = disassemble(&code);
+        assert_eq!(instructions.len(), 5);
+        assert_eq!(instructions[3].to_string(), "UNKNOWN(0x0c)");
+        assert_eq!(instructions[4].immediate, [0x01]);
+        assert_eq!(
+            listing(&code),
+            "0000  PUSH1 0x80\n0002  PUSH1 0x40\n0004  MSTORE\n0005  UNKNOWN(0x0c)\n0006  PUSH2 0x01\n"
+        );
+
+        // the listing of valid code assembles back to it
+        let code = deployer(&assemble("CALLDATASIZE PUSH0 PUSH0 CALLDATACOPY").unwrap()).unwrap();
+        let lines: Vec<_> = listing(&code)
+            .lines()
+            .map(|line| line[6..].to_string())
+            .collect();
+        assert_eq!(assemble(&lines.join("\n")).unwrap(), code);
+    }
+}
@@ -1,4 +1,5 @@
 pub mod abi;
+pub mod asm;
 pub mod constants;
 
 #[allow(clippy::module_inception)]